@@ -1,26 +1,111 @@
 use color_eyre::Result;
+use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use ratatui::backend::Backend;
+use ratatui::crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Position, Rect};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{self, Block, Paragraph, Widget};
 use ratatui::{crossterm::event, style::Stylize};
 
-use crate::search::{self, SearchConfig, SearchResult};
+use crate::audio::Speaker;
+use crate::frequency::Commonness;
+use crate::html;
+use crate::http::HttpClient;
+use crate::japanese::furigana::{self, Segment};
+use crate::japanese::kanji::{self, KanjiInfo};
+use crate::japanese::pitch;
+use crate::search::{self, LanguageTag, SearchConfig, SearchResult};
 
 #[derive(Debug)]
 pub struct App {
+    client: HttpClient,
     search_config: Rc<SearchConfig>,
     results: Option<search::SearchResult>,
+    /// The furigana of the result, aligned when it arrives rather than on every draw
+    furigana: Furigana,
     running_state: RunningState,
+    /// The kanji panel, if shown: the kanji of the query and result, or why they can't be listed
+    kanji: Option<Result<Vec<KanjiInfo>, String>>,
+    /// The word of the sentence selected in the word strip
+    selected_token: usize,
+    /// Where the words of the strip were drawn, to find the word clicked
+    token_areas: RefCell<Vec<Rect>>,
+    /// Whether the mouse is captured to click the words of the strip, which stops the text of
+    /// the terminal from being selected
+    mouse_captured: bool,
+    /// The searches before the words looked up from the strip, to go back to
+    history: Vec<Rc<SearchConfig>>,
+    speaker: Arc<Speaker>,
+    /// Why the last text couldn't be spoken
+    speech_error: Option<String>,
+    /// The speech synthesized in the background, which may wait for Google Translate
+    speaking: Option<Receiver<Result<(), String>>>,
+    /// The text copied, to look up, when watching the clipboard
+    clipboard: Option<Receiver<String>>,
+    /// The search running in the background, which may look up many words of a sentence
+    pending: Option<Receiver<Result<SearchResult, String>>>,
 }
 
+/// The readings of a result aligned to its kanji
+#[derive(Debug, Default)]
+struct Furigana {
+    /// The furigana of the headword of every definition
+    headwords: Vec<Option<Vec<Segment>>>,
+    /// The furigana of the original text and of its translation
+    original: Option<Vec<Segment>>,
+    translated: Option<Vec<Segment>>,
+}
+
+impl Furigana {
+    fn new(result: &SearchResult) -> Self {
+        let headwords = result
+            .definitions
+            .iter()
+            .flatten()
+            .map(|definition| {
+                let word = definition.headword.as_ref()?;
+                furigana::align(&word.text, word.reading.as_ref()?)
+            })
+            .collect();
+        let literation = result.literation.as_ref();
+        let original = literation
+            .and_then(|literation| literation.orig.as_ref())
+            .and_then(|original| {
+                let text = translation_texts(result, |translation| &translation.orig);
+                furigana::align_romaji(&text, original)
+            });
+        let translated = literation
+            .and_then(|literation| literation.translated.as_ref())
+            .and_then(|translated| {
+                let text = translation_texts(result, |translation| &translation.translated);
+                furigana::align_romaji(&text, translated)
+            });
+        Self {
+            headwords,
+            original,
+            translated,
+        }
+    }
+}
+
+/// How often the search, the speech and the clipboard are checked, while waiting for events
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Default, PartialEq, Eq)]
 enum RunningState {
     #[default]
     Start,
     Searching,
     Result,
+    /// The search failed, for this reason
+    Failed(String),
     Finished,
 }
 
@@ -28,7 +113,25 @@ enum RunningState {
 enum Message {
     QueryReceived(Rc<SearchConfig>),
     Searching(Rc<SearchConfig>),
-    ResultReceived(SearchResult),
+    ResultReceived(Box<SearchResult>),
+    /// The search failed, and the app waits for another query
+    SearchFailed(String),
+    /// Re-run the search with the n-th detected source language candidate
+    SourceLanguageSelected(usize),
+    /// Show or hide the kanji panel
+    KanjiToggled,
+    /// Move the selection of the word strip by this many words
+    TokenMoved(isize),
+    /// Look up a word of the strip: the selected one, or the n-th one
+    TokenOpened(Option<usize>),
+    /// Go back to the search before the word looked up
+    Back,
+    /// Hear the query
+    QuerySpoken,
+    /// Hear the translation of the query
+    TranslationSpoken,
+    /// The text was synthesized and is playing, or couldn't be, for this reason
+    SpeechEnded(Option<String>),
     Quit,
 }
 
@@ -38,7 +141,22 @@ impl Widget for &App {
         Self: Sized,
     {
         let title = Line::from("What Do You Mean?".bold());
-        let bottom_title = Line::from("Press <q> to quit");
+        let has_tokens = self
+            .results
+            .as_ref()
+            .is_some_and(|results| results.tokens.is_some());
+        let bottom_title = match &self.speech_error {
+            Some(err) => Line::from(err.as_str().red()),
+            None => Line::from(match (has_tokens, self.history.is_empty()) {
+                (true, _) => {
+                    "Press <q> to quit, <k> for kanji, <p/t> to hear, <←/→> and <enter> to look up a word"
+                }
+                (false, false) => {
+                    "Press <q> to quit, <k> for kanji, <p/t> to hear, <backspace> to go back"
+                }
+                (false, true) => "Press <q> to quit, <k> for kanji, <p/t> to hear",
+            }),
+        };
         let block = Block::bordered()
             .border_type(widgets::BorderType::Rounded)
             .title(title.centered())
@@ -50,6 +168,11 @@ impl Widget for &App {
         block.render(area, buf);
 
         match self.running_state {
+            RunningState::Start if self.clipboard.is_some() => {
+                "Copy some text to look it up"
+                    .italic()
+                    .render(inner_area, buf);
+            }
             RunningState::Start => {
                 "Starting".italic().render(inner_area, buf);
             }
@@ -65,34 +188,67 @@ impl Widget for &App {
                 ])
                 .render(inner_area, buf);
             }
+            RunningState::Failed(ref err) => {
+                Paragraph::new(Line::from(vec![
+                    "Search failed: ".red().bold(),
+                    err.as_str().red(),
+                ]))
+                .wrap(widgets::Wrap { trim: true })
+                .render(inner_area, buf);
+            }
             RunningState::Result => {
                 render_result(
                     self.results.as_ref().expect("Should have a result"),
+                    &self.furigana,
                     &self.search_config,
+                    self.selected_token,
+                    &mut self.token_areas.borrow_mut(),
                     inner_area,
                     buf,
                 );
             }
             _ => {}
         };
+
+        if let Some(kanji) = &self.kanji {
+            render_kanji(kanji, inner_area, buf);
+        }
     }
 }
 
 impl App {
-    pub fn new(search_config: search::SearchConfig) -> App {
+    pub fn new(search_config: search::SearchConfig, client: HttpClient, speaker: Speaker) -> App {
         App {
+            client,
             results: None,
+            furigana: Furigana::default(),
             running_state: Default::default(),
             search_config: Rc::new(search_config),
+            kanji: None,
+            selected_token: 0,
+            token_areas: RefCell::new(Vec::new()),
+            mouse_captured: false,
+            history: Vec::new(),
+            speaker: Arc::new(speaker),
+            speech_error: None,
+            speaking: None,
+            clipboard: None,
+            pending: None,
         }
     }
 
+    /// Looks up the text copied, as it is received
+    pub fn watch_clipboard(&mut self, copies: Receiver<String>) {
+        self.clipboard = Some(copies);
+    }
+
     pub fn run(&mut self, terminal: &mut ratatui::Terminal<impl Backend>) -> Result<()> {
-        // Search the user-given query first
-        let mut cur_message: Option<Message> =
-            Some(Message::QueryReceived(Rc::clone(&self.search_config)));
+        // Search the user-given query first, if any, or wait for the clipboard
+        let mut cur_message: Option<Message> = (!self.search_config.query.is_empty())
+            .then(|| Message::QueryReceived(Rc::clone(&self.search_config)));
 
         while self.running_state != RunningState::Finished {
+            self.capture_mouse(self.tokens().is_some())?;
             terminal.draw(|f| self.view(f))?;
 
             if cur_message.is_none() {
@@ -103,10 +259,69 @@ impl App {
                 cur_message = self.update(msg)?;
             }
         }
+        self.capture_mouse(false)
+    }
+
+    /// Captures the mouse only while the words of a sentence can be clicked, so that results can
+    /// be selected and copied otherwise
+    fn capture_mouse(&mut self, capture: bool) -> Result<()> {
+        if capture != self.mouse_captured {
+            match capture {
+                true => execute!(std::io::stdout(), EnableMouseCapture)?,
+                false => execute!(std::io::stdout(), DisableMouseCapture)?,
+            }
+            self.mouse_captured = capture;
+        }
         Ok(())
     }
 
-    fn handle_event(&self) -> Result<Option<Message>> {
+    fn handle_event(&mut self) -> Result<Option<Message>> {
+        if let Some(pending) = &self.pending {
+            match pending.try_recv() {
+                Ok(result) => {
+                    self.pending = None;
+                    return Ok(Some(match result {
+                        Ok(result) => Message::ResultReceived(Box::new(result)),
+                        Err(err) => Message::SearchFailed(err),
+                    }));
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    return Ok(Some(Message::SearchFailed(
+                        "the search stopped unexpectedly".to_string(),
+                    )));
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+        }
+        if let Some(speaking) = &self.speaking {
+            match speaking.try_recv() {
+                Ok(result) => {
+                    self.speaking = None;
+                    return Ok(Some(Message::SpeechEnded(result.err())));
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.speaking = None;
+                    return Ok(Some(Message::SpeechEnded(Some(
+                        "the speech stopped unexpectedly".to_string(),
+                    ))));
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+        }
+        if let Some(copies) = &self.clipboard {
+            // Only the last text copied while busy is looked up
+            if let Some(query) = copies.try_iter().last() {
+                return Ok(Some(Message::QueryReceived(Rc::new(SearchConfig {
+                    query,
+                    ..(*self.search_config).clone()
+                }))));
+            }
+        }
+        let waiting = self.clipboard.is_some() || self.pending.is_some() || self.speaking.is_some();
+        if waiting && !event::poll(POLL_INTERVAL)? {
+            return Ok(None);
+        }
         let event = event::read()?;
         match event {
             event::Event::Key(key_event) => {
@@ -117,7 +332,17 @@ impl App {
             event::Event::Resize(_, _) => {} // Return None, and it will update itself next loop
             event::Event::FocusGained => {}
             event::Event::FocusLost => {}
-            event::Event::Mouse(_mouse_event) => {}
+            event::Event::Mouse(mouse_event) => {
+                if mouse_event.kind == event::MouseEventKind::Down(event::MouseButton::Left) {
+                    let position = Position::new(mouse_event.column, mouse_event.row);
+                    return Ok(self
+                        .token_areas
+                        .borrow()
+                        .iter()
+                        .position(|area| area.contains(position))
+                        .map(|index| Message::TokenOpened(Some(index))));
+                }
+            }
             event::Event::Paste(_) => {}
         }
 
@@ -127,8 +352,13 @@ impl App {
     fn update(&mut self, msg: Message) -> Result<Option<Message>> {
         match msg {
             Message::ResultReceived(search_result) => {
-                self.results = Some(search_result);
+                self.furigana = Furigana::new(&search_result);
+                self.results = Some(*search_result);
                 self.running_state = RunningState::Result;
+                self.kanji = None;
+                self.selected_token = 0;
+                self.token_areas.borrow_mut().clear();
+                self.speech_error = None;
                 Ok(None)
             }
             Message::Quit => {
@@ -136,15 +366,168 @@ impl App {
                 Ok(None)
             }
             Message::QueryReceived(search_config) => {
+                // A new query, from the clipboard, starts over
+                if !Rc::ptr_eq(&search_config, &self.search_config) {
+                    self.search_config = Rc::clone(&search_config);
+                    self.history.clear();
+                }
                 self.running_state = RunningState::Searching;
                 Ok(Some(Message::Searching(search_config)))
             }
+            Message::SourceLanguageSelected(index) => {
+                let candidate = self
+                    .results
+                    .as_ref()
+                    .and_then(|results| results.src_lang_candidates.as_ref())
+                    .and_then(|candidates| candidates.get(index));
+                match candidate {
+                    Some(candidate) => {
+                        let mut search_config = (*self.search_config).clone();
+                        search_config.source_language = Some(candidate.lang.clone());
+                        self.search_config = Rc::new(search_config);
+                        Ok(Some(Message::QueryReceived(Rc::clone(&self.search_config))))
+                    }
+                    None => Ok(None),
+                }
+            }
+            Message::KanjiToggled => {
+                self.kanji = match self.kanji {
+                    Some(_) => None,
+                    None => {
+                        let text = self.kanji_text();
+                        Some(kanji::lookup_kanji(&text).map_err(|err| err.to_string()))
+                    }
+                };
+                Ok(None)
+            }
+            Message::TokenMoved(delta) => {
+                let count = self.tokens().map_or(0, <[search::Token]>::len);
+                if count > 0 {
+                    self.selected_token = self
+                        .selected_token
+                        .saturating_add_signed(delta)
+                        .min(count - 1);
+                }
+                Ok(None)
+            }
+            Message::TokenOpened(index) => {
+                let index = index.unwrap_or(self.selected_token);
+                let Some(token) = self.tokens().and_then(|tokens| tokens.get(index)) else {
+                    return Ok(None);
+                };
+                let search_config = SearchConfig {
+                    query: token.text.clone(),
+                    ..(*self.search_config).clone()
+                };
+                self.history.push(Rc::clone(&self.search_config));
+                self.search_config = Rc::new(search_config);
+                Ok(Some(Message::QueryReceived(Rc::clone(&self.search_config))))
+            }
+            Message::Back => match self.history.pop() {
+                Some(search_config) => {
+                    self.search_config = search_config;
+                    Ok(Some(Message::QueryReceived(Rc::clone(&self.search_config))))
+                }
+                None => Ok(None),
+            },
+            // Synthesized in the background, as it may wait for Google Translate or the command,
+            // replacing the speech synthesized if any
+            Message::QuerySpoken | Message::TranslationSpoken => {
+                let text = match msg {
+                    Message::QuerySpoken => self.query_speech(),
+                    _ => self.translation_speech(),
+                };
+                let Some((text, language)) = text else {
+                    self.speech_error = Some("There is nothing to hear yet".to_string());
+                    return Ok(None);
+                };
+                self.speech_error = None;
+                let (sender, receiver) = mpsc::channel();
+                let speaker = Arc::clone(&self.speaker);
+                thread::spawn(move || {
+                    let _ = sender.send(
+                        speaker
+                            .speak(&text, &language)
+                            .map_err(|err| err.to_string()),
+                    );
+                });
+                self.speaking = Some(receiver);
+                Ok(None)
+            }
+            Message::SpeechEnded(err) => {
+                self.speech_error = err;
+                Ok(None)
+            }
+            // Searched in the background, replacing the search running if any. Failures are
+            // shown, so that the next query or copy can be looked up.
             Message::Searching(search_config) => {
-                Ok(Some(Message::ResultReceived(search(&search_config)?)))
+                let (sender, receiver) = mpsc::channel();
+                let client = self.client.clone();
+                let search_config = (*search_config).clone();
+                thread::spawn(move || {
+                    let _ =
+                        sender.send(search(&client, &search_config).map_err(|err| err.to_string()));
+                });
+                self.pending = Some(receiver);
+                Ok(None)
+            }
+            Message::SearchFailed(err) => {
+                self.results = None;
+                self.running_state = RunningState::Failed(err);
+                self.kanji = None;
+                self.token_areas.borrow_mut().clear();
+                Ok(None)
             }
         }
     }
 
+    /// The words of the sentence looked up, if it was split
+    fn tokens(&self) -> Option<&[search::Token]> {
+        self.results.as_ref()?.tokens.as_deref()
+    }
+
+    /// The query, and its language
+    fn query_speech(&self) -> Option<(String, LanguageTag)> {
+        let language = match &self.search_config.source_language {
+            Some(language) => language.clone(),
+            None => search::parse_lang(self.results.as_ref()?.src_lang.as_ref()?).ok()?,
+        };
+        Some((self.search_config.query.clone(), language))
+    }
+
+    /// The translation of the query, or its first definition, and the target language
+    fn translation_speech(&self) -> Option<(String, LanguageTag)> {
+        let result = self.results.as_ref()?;
+        let mut text = translation_texts(result, |translation| &translation.translated);
+        if text.trim().is_empty() {
+            text = result.definitions.as_ref()?.first()?.meaning.clone();
+        }
+        let language = self
+            .search_config
+            .target_language
+            .clone()
+            .unwrap_or_default();
+        Some((text, language))
+    }
+
+    /// The text of the query and of the result, to list its kanji
+    fn kanji_text(&self) -> String {
+        let mut text = self.search_config.query.clone();
+        let Some(result) = &self.results else {
+            return text;
+        };
+        for definition in result.definitions.iter().flatten() {
+            if let Some(headword) = &definition.headword {
+                text.push_str(&headword.text);
+            }
+        }
+        text.push_str(&translation_texts(result, |translation| &translation.orig));
+        text.push_str(&translation_texts(result, |translation| {
+            &translation.translated
+        }));
+        text
+    }
+
     fn view(&self, frame: &mut ratatui::Frame) {
         frame.render_widget(self, frame.area());
     }
@@ -152,7 +535,10 @@ impl App {
 
 fn render_result(
     result: &SearchResult,
+    furigana: &Furigana,
     config: &SearchConfig,
+    selected_token: usize,
+    token_areas: &mut Vec<Rect>,
     area: ratatui::prelude::Rect,
     buf: &mut ratatui::prelude::Buffer,
 ) {
@@ -160,34 +546,113 @@ fn render_result(
     let language = Line::from(vec![
         config
             .source_language
+            .clone()
             .unwrap_or_else(|| {
                 search::parse_lang(result.src_lang.as_ref().unwrap_or(&"".to_string()))
-                    .unwrap_or(isolang::Language::Und)
+                    .unwrap_or_default()
             })
-            .to_string()
+            .name()
             .italic(),
+        match (&config.source_language, result.src_lang_confidence) {
+            (None, Some(confidence)) => format!(" ({:.3})", confidence).dim(),
+            _ => "".into(),
+        },
         " -> ".dim(),
         config
             .target_language
-            .unwrap_or(isolang::Language::Und)
-            .to_string()
+            .clone()
+            .unwrap_or_default()
+            .name()
             .italic(),
     ])
     .cyan()
     .right_aligned();
-    let block: Block = Block::bordered()
+    let mut block: Block = Block::bordered()
         .title(provider)
         .title(language)
         .padding(widgets::Padding::horizontal(1));
+    if let Some(candidates) = &result.src_lang_candidates {
+        block = block.title_bottom(render_candidates(candidates, config));
+    }
     let mut res: Vec<Line> = Vec::new();
+    // Inside the borders and padding
+    let width = area.width.saturating_sub(4) as usize;
+
+    if let Some(tokens) = &result.tokens {
+        // The strip is the first line, inside the borders and padding
+        let origin = Position::new(area.x + 2, area.y + 1);
+        res.push(render_tokens(
+            tokens,
+            selected_token,
+            origin,
+            width,
+            token_areas,
+        ));
+    }
+
+    for warning in result.warnings.iter().flatten() {
+        res.push(Line::from(warning.clone().yellow()));
+    }
+
+    if let Some(suggestion) = &result.spelling_suggestion {
+        res.push(Line::from(vec![
+            "Did you mean ".yellow(),
+            suggestion.clone().bold().yellow(),
+            "?".yellow(),
+        ]));
+    }
+
+    if let Some(inflection) = &result.inflection {
+        let mut line: Vec<Span> =
+            vec!["Inflected from ".dim(), inflection.base_form.clone().bold()];
+        for reason in &inflection.reasons {
+            line.push(" → ".dim());
+            line.push(reason.clone().italic());
+        }
+        res.push(Line::from(line));
+    }
 
     if let Some(definitions) = &result.definitions {
         make_title(&mut res, "Definitions");
-        for definition in definitions {
-            let mut line: Vec<Span> = vec![
-                definition.meaning.clone().underlined().cyan(),
-                format!(" ({})", definition.pos.to_lowercase()).green(),
-            ];
+        let mut headword: Option<&search::Word> = None;
+        for (index, definition) in definitions.iter().enumerate() {
+            // Dictionary entries start with their word, followed by its meanings
+            if definition.headword.is_some() && definition.headword.as_ref() != headword {
+                headword = definition.headword.as_ref();
+                let segments = furigana.headwords.get(index).and_then(Option::as_deref);
+                res.append(&mut render_word(
+                    headword.expect("Checked above"),
+                    segments,
+                    width,
+                ));
+                if let Some(accents) = &definition.pitch_accents {
+                    res.append(&mut render_pitch(headword.expect("Checked above"), accents));
+                }
+                if let Some(spellings) = &definition.other_spellings {
+                    res.push(Line::from(vec![
+                        "Also written ".dim(),
+                        spellings.join("、").italic(),
+                    ]));
+                }
+            }
+
+            // Styled meanings are shown as they are, starting on the line of the part of speech
+            let mut rich_lines = definition.rich_meaning.iter().flatten();
+            let mut line: Vec<Span> = match rich_lines.next() {
+                Some(first) => html::to_spans(first)
+                    .into_iter()
+                    .map(|span| span.cyan())
+                    .collect(),
+                None => vec![definition.meaning.clone().underlined().cyan()],
+            };
+            if !definition.pos.is_empty() {
+                line.push(format!(" ({})", definition.pos.to_lowercase()).green());
+            }
+
+            if let Some(rank) = definition.frequency {
+                line.push(" ".into());
+                line.push(render_commonness(rank));
+            }
 
             if let Some(reverse_translation) = &definition.reverse_translation {
                 let mut translations: Vec<Span> = reverse_translation
@@ -202,13 +667,29 @@ fn render_result(
                 line.push(format!("({:.3})", confidence).dim().italic());
             }
             res.push(Line::from(line));
+            for rich_line in rich_lines {
+                res.push(Line::from(html::to_spans(rich_line)));
+            }
 
             // Start new line for examples
             if let Some(examples) = &definition.examples {
                 for example in examples {
-                    res.push(Line::from(example.clone().italic().dim()));
+                    res.push(Line::from(example.text.clone().italic().dim()));
+                    if let Some(translation) = &example.translation {
+                        res.push(Line::from(vec![
+                            "→ ".dim(),
+                            translation.clone().italic().dim(),
+                        ]));
+                    }
                 }
             }
+
+            for relation in definition.relations.iter().flatten() {
+                res.push(Line::from(vec![
+                    format!("{}: ", relation.kind).dim(),
+                    relation.words.join(", ").italic(),
+                ]));
+            }
         }
     }
 
@@ -228,12 +709,44 @@ fn render_result(
         }
     }
 
+    if let Some(alternatives) = &result.alternatives {
+        make_title(&mut res, "Alternatives");
+        for alternative in alternatives {
+            res.push(Line::from(vec![
+                alternative.orig.clone().italic(),
+                ": ".into(),
+                alternative.alternatives.join(", ").into(),
+            ]));
+        }
+    }
+
+    if let Some(synonyms) = &result.synonyms {
+        make_title(&mut res, "Synonyms");
+        for synonym in synonyms {
+            res.push(Line::from(vec![
+                format!("({}) ", synonym.pos.to_lowercase()).green(),
+                synonym.words.join(", ").italic(),
+            ]));
+        }
+    }
+
+    if let Some(related_words) = &result.related_words {
+        make_title(&mut res, "Related words");
+        res.push(Line::from(related_words.join(", ").italic()));
+    }
+
     if let Some(literation) = &result.literation {
         make_title(&mut res, "Literations");
         if let Some(original) = &literation.orig {
+            if let Some(segments) = &furigana.original {
+                res.append(&mut render_furigana(segments, width));
+            }
             res.push(vec!["Original  : ".dim(), original.clone().italic()].into());
         }
         if let Some(translated) = &literation.translated {
+            if let Some(segments) = &furigana.translated {
+                res.append(&mut render_furigana(segments, width));
+            }
             res.push(vec!["Translated: ".dim(), translated.clone().into()].into());
         }
     }
@@ -244,18 +757,293 @@ fn render_result(
         .render(area, buf);
 }
 
+/// Joins one side of the translations
+fn translation_texts(
+    result: &SearchResult,
+    side: impl Fn(&search::Translation) -> &Option<String>,
+) -> String {
+    result
+        .translations
+        .iter()
+        .flatten()
+        .filter_map(|translation| side(translation).as_deref())
+        .collect()
+}
+
+/// Renders a dictionary word, with furigana when its reading could be aligned
+fn render_word<'a>(
+    word: &search::Word,
+    segments: Option<&[Segment]>,
+    width: usize,
+) -> Vec<Line<'a>> {
+    match (segments, &word.reading) {
+        (Some(segments), _) => render_furigana(segments, width),
+        (None, Some(reading)) if *reading != word.text => {
+            vec![Line::from(format!("{}【{}】", word.text, reading).bold())]
+        }
+        (None, _) => vec![Line::from(word.text.clone().bold())],
+    }
+}
+
+/// A badge of how common the word is, with its rank in the frequency lists
+fn render_commonness<'a>(rank: u32) -> Span<'a> {
+    let commonness = Commonness::of(rank);
+    let badge = format!("[{} #{}]", commonness, rank);
+    match commonness {
+        Commonness::VeryCommon => badge.light_green().bold(),
+        Commonness::Common => badge.light_green(),
+        Commonness::Uncommon => badge.yellow(),
+        Commonness::Rare => badge.dim(),
+    }
+}
+
+/// Renders the pitch of the first accent of a word, followed by all of its accents
+///
+/// The reading isn't repeated, as it is drawn with the word just above.
+fn render_pitch<'a>(word: &search::Word, accents: &[u8]) -> Vec<Line<'a>> {
+    // Words written in kana have no reading
+    let reading = word.reading.as_ref().unwrap_or(&word.text);
+    let Some(first) = accents.first() else {
+        return Vec::new();
+    };
+    let accents: Vec<String> = accents.iter().map(u8::to_string).collect();
+    vec![Line::from(
+        format!("{} [{}]", pitch::graph(reading, *first), accents.join(", ")).dim(),
+    )]
+}
+
+/// Renders Japanese text with its readings on the line above, or in brackets after the kanji
+/// when it doesn't fit on one line
+fn render_furigana<'a>(segments: &[Segment], width: usize) -> Vec<Line<'a>> {
+    let (readings, text) = furigana::ruby(segments);
+    if furigana::width(&text) > width {
+        return vec![Line::from(furigana::inline(segments).bold())];
+    }
+    // The paragraph trims the leading spaces of a line, but keeps no-break spaces
+    let no_break = |line: String| line.replace(' ', "\u{a0}");
+    vec![
+        Line::from(no_break(readings).dim()),
+        Line::from(no_break(text).bold()),
+    ]
+}
+
+/// Renders the words of the sentence on one line, and records where they are drawn
+///
+/// Words not found in the dictionary are dimmed, and the ones not looked up yet are also in
+/// italics. Words past the width of the line aren't recorded, as the line wraps.
+fn render_tokens<'a>(
+    tokens: &[search::Token],
+    selected: usize,
+    origin: Position,
+    width: usize,
+    areas: &mut Vec<Rect>,
+) -> Line<'a> {
+    let mut line: Vec<Span> = vec!["Words: ".dim()];
+    let mut x = furigana::width("Words: ");
+    areas.clear();
+    for (index, token) in tokens.iter().enumerate() {
+        let token_width = furigana::width(&token.text);
+        if x + token_width <= width {
+            areas.push(Rect::new(
+                origin.x + x as u16,
+                origin.y,
+                token_width as u16,
+                1,
+            ));
+        }
+        let mut span: Span = token.text.clone().into();
+        if !token.looked_up {
+            span = span.dim().italic();
+        } else if token.headword.is_none() {
+            span = span.dim();
+        }
+        if index == selected {
+            span = span.reversed();
+        }
+        line.push(span);
+        line.push(" ".into());
+        x += token_width + 1;
+    }
+    Line::from(line)
+}
+
+/// Lists the source language candidates, with the keys to pick them
+fn render_candidates<'a>(
+    candidates: &[search::LanguageCandidate],
+    config: &SearchConfig,
+) -> Line<'a> {
+    let mut line: Vec<Span> = vec!["Source: ".dim()];
+    for (index, candidate) in candidates.iter().take(9).enumerate() {
+        line.push(format!("<{}> ", index + 1).dim());
+        let name = if config.source_language.as_ref() == Some(&candidate.lang) {
+            candidate.lang.name().bold()
+        } else {
+            candidate.lang.name().into()
+        };
+        line.push(name);
+        if let Some(confidence) = candidate.confidence {
+            line.push(format!(" {:.3}", confidence).dim());
+        }
+        line.push(" ".into());
+    }
+    Line::from(line)
+}
+
+/// Renders the kanji panel over the result
+fn render_kanji(
+    kanji: &Result<Vec<KanjiInfo>, String>,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+) {
+    let area = area.inner(ratatui::layout::Margin::new(2, 1));
+    let block = Block::bordered()
+        .title("Kanji".bold().cyan())
+        .title_bottom(Line::from("Press <k> to close").right_aligned())
+        .padding(widgets::Padding::horizontal(1));
+
+    let mut res: Vec<Line> = Vec::new();
+    match kanji {
+        Err(err) => res.push(Line::from(err.clone().red())),
+        Ok(kanji) if kanji.is_empty() => res.push(Line::from("No kanji found".italic())),
+        Ok(kanji) => {
+            for info in kanji {
+                let mut details: Vec<String> = Vec::new();
+                if let Some(radical) = info.radical {
+                    details.push(format!("radical {}", radical));
+                }
+                if let Some(strokes) = info.stroke_count {
+                    details.push(format!("{} strokes", strokes));
+                }
+                if let Some(grade) = info.grade {
+                    details.push(format!("grade {}", grade));
+                }
+                if let Some(jlpt) = info.jlpt {
+                    details.push(format!("JLPT {}", jlpt));
+                }
+                if let Some(frequency) = info.frequency {
+                    details.push(format!("frequency #{}", frequency));
+                }
+                res.push(Line::from(vec![
+                    info.literal.to_string().bold().cyan(),
+                    format!("  {}", details.join(", ")).dim(),
+                ]));
+                if !info.on_readings.is_empty() {
+                    res.push(Line::from(vec![
+                        "On : ".dim(),
+                        info.on_readings.join("、").into(),
+                    ]));
+                }
+                if !info.kun_readings.is_empty() {
+                    res.push(Line::from(vec![
+                        "Kun: ".dim(),
+                        info.kun_readings.join("、").into(),
+                    ]));
+                }
+                res.push(Line::from(info.meanings.join(", ").italic()));
+                res.push("".into());
+            }
+        }
+    }
+
+    widgets::Clear.render(area, buf);
+    Paragraph::new(res)
+        .wrap(widgets::Wrap { trim: true })
+        .block(block)
+        .render(area, buf);
+}
+
 fn make_title<'a>(res: &mut Vec<Line<'a>>, title: &'a str) {
     res.push("".into());
     res.push(title.bold().blue().into());
 }
 
-fn search(search_config: &SearchConfig) -> Result<SearchResult> {
-    search::lookup(search_config)
+fn search(client: &HttpClient, search_config: &SearchConfig) -> Result<SearchResult> {
+    search::lookup_with_client(client, search_config)
 }
 
 fn handle_key(key: event::KeyEvent) -> Option<Message> {
     match key.code {
         event::KeyCode::Char('q') => Some(Message::Quit),
+        event::KeyCode::Char('k') => Some(Message::KanjiToggled),
+        event::KeyCode::Char('p') => Some(Message::QuerySpoken),
+        event::KeyCode::Char('t') => Some(Message::TranslationSpoken),
+        event::KeyCode::Left => Some(Message::TokenMoved(-1)),
+        event::KeyCode::Right => Some(Message::TokenMoved(1)),
+        event::KeyCode::Enter => Some(Message::TokenOpened(None)),
+        event::KeyCode::Backspace => Some(Message::Back),
+        event::KeyCode::Char(c @ '1'..='9') => Some(Message::SourceLanguageSelected(
+            c.to_digit(10).expect("Should be a digit") as usize - 1,
+        )),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::audio::AudioConfig;
+    use crate::translators::SearchProvider;
+
+    #[test]
+    fn keep_running_after_failed_search() {
+        let client = HttpClient::default();
+        let speaker = Speaker::new(client.clone(), AudioConfig::default());
+        let mut app = App::new(SearchConfig::default(), client, speaker);
+
+        // Google Translate rejects queries without a target language before sending them
+        let mut message = Some(Message::QueryReceived(Rc::new(SearchConfig {
+            query: "cat".to_string(),
+            provider: SearchProvider::GoogleTranslate,
+            target_language: None,
+            ..Default::default()
+        })));
+        while let Some(msg) = message.take() {
+            message = app.update(msg).unwrap();
+        }
+        // The search runs in the background
+        let result = app
+            .pending
+            .take()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(5));
+        let mut message = Some(Message::SearchFailed(result.unwrap().unwrap_err()));
+        while let Some(msg) = message.take() {
+            message = app.update(msg).unwrap();
+        }
+        assert!(matches!(app.running_state, RunningState::Failed(_)));
+        assert_eq!(app.search_config.query, "cat");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn show_speech_error() {
+        let client = HttpClient::default();
+        let speaker = Speaker::new(
+            client.clone(),
+            AudioConfig {
+                tts_command: Some("false".to_string()),
+                player: "true".to_string(),
+                cache_dir: std::env::temp_dir()
+                    .join(format!("wdym-app-speech-{}", std::process::id())),
+            },
+        );
+        let search_config = SearchConfig {
+            query: "cat".to_string(),
+            source_language: Some(search::parse_lang("en").unwrap()),
+            ..Default::default()
+        };
+        let mut app = App::new(search_config, client, speaker);
+
+        assert!(app.update(Message::QuerySpoken).unwrap().is_none());
+        assert!(app.speech_error.is_none());
+        // The speech is synthesized in the background
+        let result = app
+            .speaking
+            .take()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        app.update(Message::SpeechEnded(result.err())).unwrap();
+        assert!(app.speech_error.unwrap().contains("false exited"));
+    }
+}
@@ -0,0 +1,631 @@
+//! Text to speech, to hear the pronunciation of the query and its translation
+//!
+//! Speech is synthesized by the `translate_tts` endpoint of Google Translate, or by a local
+//! command like `espeak-ng` or `piper` when one is configured. The audio files are cached, the
+//! least recently played first removed once the cache grows past `MAX_CACHE_SIZE`, and played by
+//! a player command.
+//!
+//! Commands are split like a shell would, without running one, and these placeholders are
+//! replaced in their arguments:
+//! - `{text}`: the text to speak. Without it, the text is written to the standard input.
+//! - `{lang}`: the language of the text, as a BCP 47 tag: `en`, `zh-TW`.
+//! - `{output}`: the audio file to write. Without it, the audio is read from the standard output.
+//! - `{file}`: for the player, the audio file to play. Without it, the file is the last argument.
+//!
+//! Text starting with `-` is refused as an argument of its own, as it would be read as an option,
+//! unless it follows `--`. The text may be copied from anywhere, so don't paste `{text}` into the
+//! script of `sh -c`, pass it as a positional parameter of the script:
+//! `sh -c 'espeak-ng -w "$2" -- "$1"' -- {text} {output}`.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use wdym::audio::{AudioConfig, Speaker};
+//! use wdym::http::HttpClient;
+//! use wdym::search::parse_lang;
+//!
+//! let speaker = Speaker::new(
+//!     HttpClient::default(),
+//!     AudioConfig {
+//!         tts_command: Some("espeak-ng -v {lang} -w {output} -- {text}".to_string()),
+//!         ..Default::default()
+//!     },
+//! );
+//! speaker.speak("hello", &parse_lang("en").unwrap()).unwrap();
+//! speaker.wait().unwrap();
+//! ```
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use color_eyre::Result;
+use reqwest::Url;
+use ripemd::{Digest, Ripemd160};
+
+use crate::http::HttpClient;
+use crate::search::LanguageTag;
+use crate::translators::google_translate;
+use crate::translators::SearchProvider;
+
+/// The player used if none is configured
+pub const DEFAULT_PLAYER: &str = "mpv --really-quiet --no-video";
+
+/// Largest size of the audio cache, in bytes
+pub const MAX_CACHE_SIZE: u64 = 50 << 20;
+
+/// Google reads at most this many characters at once, longer texts are read in parts
+const GOOGLE_MAX_CHARS: usize = 200;
+
+/// Options of the text to speech
+#[derive(Debug, Clone)]
+pub struct AudioConfig {
+    /// Command synthesizing speech, instead of Google Translate
+    pub tts_command: Option<String>,
+    /// Command playing an audio file
+    pub player: String,
+    /// Where the synthesized audio is cached
+    pub cache_dir: PathBuf,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            tts_command: None,
+            player: DEFAULT_PLAYER.to_string(),
+            cache_dir: cache_dir(),
+        }
+    }
+}
+
+/// The `wdym/audio` directory of the user cache directory: `~/.cache/wdym/audio` on Linux
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("wdym")
+        .join("audio")
+}
+
+/// Failing to synthesize or play speech
+#[derive(Debug, Clone)]
+pub struct AudioError(String);
+
+impl Error for AudioError {}
+
+impl std::fmt::Display for AudioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to play speech: {}", self.0)
+    }
+}
+
+/// Synthesizes and plays speech, one text at a time
+#[derive(Debug)]
+pub struct Speaker {
+    client: HttpClient,
+    config: AudioConfig,
+    /// The player of the last text spoken
+    playing: Mutex<Option<Child>>,
+}
+
+impl Speaker {
+    pub fn new(client: HttpClient, config: AudioConfig) -> Self {
+        Self {
+            client,
+            config,
+            playing: Mutex::new(None),
+        }
+    }
+
+    /// The audio file of the text, synthesized on first use
+    pub fn synthesize(&self, text: &str, language: &LanguageTag) -> Result<PathBuf> {
+        let text = text.trim();
+        if text.is_empty() {
+            Err(AudioError("there is no text to speak".to_string()))?;
+        }
+        let (engine, extension) = match &self.config.tts_command {
+            Some(command) => (command.as_str(), "wav"),
+            None => ("google", "mp3"),
+        };
+        let mut hasher = Ripemd160::new();
+        for part in [engine, &language.to_string(), text] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let name = hasher
+            .finalize()
+            .iter()
+            .fold(String::new(), |mut name, byte| {
+                let _ = write!(name, "{:02x}", byte);
+                name
+            });
+        let path = self
+            .config
+            .cache_dir
+            .join(format!("{}.{}", name, extension));
+        if path.is_file() {
+            // Keeps the files played often in the cache
+            let _ = File::options()
+                .append(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+            return Ok(path);
+        }
+
+        std::fs::create_dir_all(&self.config.cache_dir)?;
+        // Written aside first, so that a failure doesn't leave a broken file in the cache
+        let partial = self
+            .config
+            .cache_dir
+            .join(format!("{}.partial.{}", name, extension));
+        let res = match &self.config.tts_command {
+            Some(command) => run_tts_command(command, text, language, &partial),
+            None => self.fetch_google(text, language, &partial),
+        };
+        if let Err(err) = res {
+            let _ = std::fs::remove_file(&partial);
+            return Err(err);
+        }
+        std::fs::rename(&partial, &path)?;
+        // The audio is there, even if the cache can't be pruned
+        let _ = prune_cache(&self.config.cache_dir, MAX_CACHE_SIZE);
+        Ok(path)
+    }
+
+    /// Plays the audio file, stopping the audio playing
+    pub fn play(&self, path: &Path) -> Result<()> {
+        let mut playing = self.playing.lock().expect("Lock poisoned");
+        if let Some(mut child) = playing.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let file = path.to_string_lossy();
+        let mut command = parse_command(&self.config.player, &[("{file}", &file)])?;
+        if !self.config.player.contains("{file}") {
+            command.arg(path);
+        }
+        // The output of the player would be drawn over the TUI
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| AudioError(format!("{}: {}", self.config.player, err)))?;
+        *playing = Some(child);
+        Ok(())
+    }
+
+    /// Synthesizes the text, and plays it without waiting for the end
+    pub fn speak(&self, text: &str, language: &LanguageTag) -> Result<()> {
+        let path = self.synthesize(text, language)?;
+        self.play(&path)
+    }
+
+    /// Waits for the audio playing to end
+    pub fn wait(&self) -> Result<()> {
+        if let Some(mut child) = self.playing.lock().expect("Lock poisoned").take() {
+            let status = child.wait()?;
+            if !status.success() {
+                Err(AudioError(format!("the player exited with {}", status)))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fetch_google(&self, text: &str, language: &LanguageTag, output: &Path) -> Result<()> {
+        let code = google_translate::language_code(language).ok_or_else(|| {
+            AudioError(format!("Google Translate can't speak {}", language.name()))
+        })?;
+        // MP3 frames stand on their own, so the parts are played one after another when joined
+        let mut audio = Vec::new();
+        for part in split_text(text, GOOGLE_MAX_CHARS) {
+            let url = Url::parse_with_params(
+                "https://translate.google.com/translate_tts",
+                &[
+                    ("ie", "UTF-8"),
+                    ("client", "tw-ob"),
+                    ("tl", &code),
+                    ("q", part),
+                ],
+            )?;
+            let response = self
+                .client
+                .get(SearchProvider::GoogleTranslate, url)?
+                .error_for_status()?;
+            if !response
+                .content_type()
+                .is_some_and(|content_type| content_type.starts_with("audio/"))
+            {
+                Err(AudioError(
+                    "Google Translate didn't answer audio".to_string(),
+                ))?;
+            }
+            audio.extend_from_slice(&response.body);
+        }
+        std::fs::write(output, &audio)?;
+        Ok(())
+    }
+}
+
+impl Drop for Speaker {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.playing.get_mut().expect("Lock poisoned").take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Splits the text into parts of at most `max_chars` characters, after the end of a sentence or
+/// else a space when there is one, so that words aren't cut
+fn split_text(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text.trim();
+    while rest.chars().count() > max_chars {
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(index, _)| index);
+        let after = |is_break: fn(char) -> bool| {
+            rest[..limit]
+                .char_indices()
+                .rfind(|(_, c)| is_break(*c))
+                .map(|(index, c)| index + c.len_utf8())
+        };
+        let end = after(|c| matches!(c, '.' | '!' | '?' | '。' | '！' | '？' | '\n'))
+            .or_else(|| after(char::is_whitespace))
+            .unwrap_or(limit);
+        let part = rest[..end].trim();
+        if !part.is_empty() {
+            parts.push(part);
+        }
+        rest = rest[end..].trim_start();
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// Removes the least recently modified files of the cache until it is at most `max_size` bytes
+fn prune_cache(dir: &Path, max_size: u64) -> Result<()> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+    for (_, len, path) in files {
+        if size <= max_size {
+            break;
+        }
+        std::fs::remove_file(path)?;
+        size -= len;
+    }
+    Ok(())
+}
+
+/// Splits the command into its arguments, replacing the placeholders
+fn parse_command(command: &str, placeholders: &[(&str, &str)]) -> Result<Command> {
+    let words = shell_words::split(command)
+        .map_err(|err| AudioError(format!("invalid command {}: {}", command, err)))?;
+    let mut words = words
+        .into_iter()
+        .map(|word| replace_placeholders(&word, placeholders));
+    let program = words
+        .next()
+        .ok_or_else(|| AudioError("the command is empty".to_string()))?;
+    let mut res = Command::new(program);
+    res.args(words);
+    Ok(res)
+}
+
+/// Replaces the placeholders of the word in a single pass, so that the values inserted, like a
+/// text containing `{output}`, aren't replaced in turn
+fn replace_placeholders(word: &str, placeholders: &[(&str, &str)]) -> String {
+    let mut res = String::with_capacity(word.len());
+    let mut rest = word;
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match placeholders
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                res.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                res.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Runs a local text to speech command, writing the audio to `output`
+fn run_tts_command(command: &str, text: &str, language: &LanguageTag, output: &Path) -> Result<()> {
+    // Arguments after `--` aren't options
+    let text_is_option = text.starts_with('-')
+        && shell_words::split(command).is_ok_and(|words| {
+            words.iter().enumerate().any(|(index, word)| {
+                word.starts_with("{text}") && (index == 0 || words[index - 1] != "--")
+            })
+        });
+    if text_is_option {
+        Err(AudioError(
+            "text starting with - would be read as an option, put -- before {text}".to_string(),
+        ))?;
+    }
+    let output_path = output.to_string_lossy();
+    let language = language.to_string();
+    let mut tts = parse_command(
+        command,
+        &[
+            ("{text}", text),
+            ("{lang}", &language),
+            ("{output}", &output_path),
+        ],
+    )?;
+    let to_stdout = !command.contains("{output}");
+    let mut child = tts
+        .stdin(Stdio::piped())
+        .stdout(if to_stdout {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| AudioError(format!("{}: {}", command, err)))?;
+    let mut stdin = child.stdin.take().expect("Piped above");
+    if !command.contains("{text}") {
+        // A command exiting without reading the text is reported by its exit status below
+        let _ = writeln!(stdin, "{}", text);
+    }
+    drop(stdin);
+
+    let result = child.wait_with_output()?;
+    if !result.status.success() {
+        Err(AudioError(format!(
+            "{} exited with {}: {}",
+            command,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )))?;
+    }
+    if to_stdout {
+        std::fs::write(output, &result.stdout)?;
+    }
+    if !output.is_file() {
+        Err(AudioError(format!("{} wrote no audio", command)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::http::{HttpConfig, Response, Transport};
+    use crate::search::parse_lang;
+
+    #[test]
+    #[cfg(unix)]
+    fn synthesize_and_play() {
+        let dir = std::env::temp_dir().join(format!("wdym-audio-{}", std::process::id()));
+        let played = dir.join("played.txt");
+        let speaker = Speaker::new(
+            HttpClient::default(),
+            AudioConfig {
+                // Stubs writing the text and its language, and copying the file played
+                tts_command: Some(
+                    "sh -c 'printf \"%s:%s\" \"$0\" \"$1\" > \"$2\"' {lang} {text} {output}"
+                        .to_string(),
+                ),
+                player: format!("cp {{file}} '{}'", played.display()),
+                cache_dir: dir.clone(),
+            },
+        );
+        let english = parse_lang("en").unwrap();
+
+        speaker.speak(" it's a cat ", &english).unwrap();
+        speaker.wait().unwrap();
+        assert_eq!(std::fs::read_to_string(&played).unwrap(), "en:it's a cat");
+
+        // Cached files are played again without the command
+        let path = speaker.synthesize("it's a cat", &english).unwrap();
+        std::fs::write(&path, "cached").unwrap();
+        speaker.speak("it's a cat", &english).unwrap();
+        speaker.wait().unwrap();
+        assert_eq!(std::fs::read_to_string(&played).unwrap(), "cached");
+
+        // Text read from the standard input, and audio written to the standard output
+        let speaker = Speaker::new(
+            HttpClient::default(),
+            AudioConfig {
+                tts_command: Some("tr a-z A-Z".to_string()),
+                player: format!("cp {{file}} '{}'", played.display()),
+                cache_dir: dir.clone(),
+            },
+        );
+        speaker.speak("neko", &parse_lang("ja").unwrap()).unwrap();
+        speaker.wait().unwrap();
+        assert_eq!(std::fs::read_to_string(&played).unwrap(), "NEKO\n");
+
+        let failing = Speaker::new(
+            HttpClient::default(),
+            AudioConfig {
+                tts_command: Some("false".to_string()),
+                player: "true".to_string(),
+                cache_dir: dir.clone(),
+            },
+        );
+        assert!(failing.speak("inu", &english).is_err());
+        assert!(failing.speak("  ", &english).is_err());
+
+        // Text that would be read as an option of the command
+        let speaker = Speaker::new(
+            HttpClient::default(),
+            AudioConfig {
+                tts_command: Some("espeak-ng -w {output} {text}".to_string()),
+                player: "true".to_string(),
+                cache_dir: dir.clone(),
+            },
+        );
+        assert!(speaker.synthesize("--version", &english).is_err());
+        let speaker = Speaker::new(
+            HttpClient::default(),
+            AudioConfig {
+                tts_command: Some(
+                    "sh -c 'printf %s \"$1\" > \"$2\"' -- {text} {output}".to_string(),
+                ),
+                player: format!("cp {{file}} '{}'", played.display()),
+                cache_dir: dir.clone(),
+            },
+        );
+        speaker.speak("-1", &english).unwrap();
+        speaker.wait().unwrap();
+        assert_eq!(std::fs::read_to_string(&played).unwrap(), "-1");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn split_long_text() {
+        assert_eq!(split_text(" short ", 10), vec!["short"]);
+        assert_eq!(
+            split_text("One two. Three four five", 12),
+            vec!["One two.", "Three four", "five"]
+        );
+        assert_eq!(
+            split_text("猫が好きです。犬も", 8),
+            vec!["猫が好きです。", "犬も"]
+        );
+        assert_eq!(split_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn replace_placeholders_once() {
+        let placeholders = [
+            ("{text}", "say {output} in {lang}"),
+            ("{lang}", "en"),
+            ("{output}", "/tmp/out.wav"),
+        ];
+        assert_eq!(
+            replace_placeholders("--text={text}", &placeholders),
+            "--text=say {output} in {lang}"
+        );
+        assert_eq!(
+            replace_placeholders("{output}.{lang}{", &placeholders),
+            "/tmp/out.wav.en{"
+        );
+        assert_eq!(replace_placeholders("{{lang}}", &placeholders), "{en}");
+
+        let command =
+            parse_command("espeak-ng -v {lang} -w {output} -- {text}", &placeholders).unwrap();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "-v",
+                "en",
+                "-w",
+                "/tmp/out.wav",
+                "--",
+                "say {output} in {lang}"
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_least_recent_files() {
+        let dir = std::env::temp_dir().join(format!("wdym-audio-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("old.mp3", 30), ("recent.mp3", 10), ("new.mp3", 0)] {
+            let path = dir.join(name);
+            std::fs::write(&path, [0; 100]).unwrap();
+            File::options()
+                .append(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+
+        prune_cache(&dir, 300).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        prune_cache(&dir, 250).unwrap();
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(left, vec!["new.mp3", "recent.mp3"]);
+    }
+
+    #[test]
+    fn fetch_from_google() {
+        #[derive(Debug)]
+        struct AudioTransport;
+
+        impl Transport for AudioTransport {
+            fn get(&self, url: &Url) -> Result<Response> {
+                let mut headers = HeaderMap::new();
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("audio/mpeg"));
+                Ok(Response {
+                    status: StatusCode::OK,
+                    url: url.clone(),
+                    headers,
+                    body: url.query().unwrap_or_default().as_bytes().to_vec(),
+                })
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("wdym-google-tts-{}", std::process::id()));
+        let speaker = Speaker::new(
+            HttpClient::with_transport(HttpConfig::default(), Arc::new(AudioTransport)),
+            AudioConfig {
+                tts_command: None,
+                player: "true".to_string(),
+                cache_dir: dir.clone(),
+            },
+        );
+        let path = speaker
+            .synthesize("猫", &parse_lang("zh-Hant").unwrap())
+            .unwrap();
+        assert_eq!(path.extension().unwrap(), "mp3");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "ie=UTF-8&client=tw-ob&tl=zh-TW&q=%E7%8C%AB"
+        );
+
+        // Long texts are read in parts, joined in order
+        let english = parse_lang("en").unwrap();
+        let text = format!("{}. {}", "a".repeat(150), "b".repeat(100));
+        let path = speaker.synthesize(&text, &english).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!(
+                "ie=UTF-8&client=tw-ob&tl=en&q={}.ie=UTF-8&client=tw-ob&tl=en&q={}",
+                "a".repeat(150),
+                "b".repeat(100)
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
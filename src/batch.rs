@@ -0,0 +1,435 @@
+//! Looks up many queries with the same search options
+//!
+//! # Examples
+//! ```rust,no_run
+//! use wdym::batch::{lookup_batch, BatchConfig, BatchFormat};
+//! use wdym::http::HttpClient;
+//! use wdym::search::SearchConfig;
+//!
+//! let search_config = SearchConfig {
+//!     target_language: Some(isolang::Language::Jpn.into()),
+//!     ..Default::default()
+//! };
+//! let queries = vec!["Open file".to_string(), "Save as".to_string()];
+//! lookup_batch(
+//!     &HttpClient::default(),
+//!     queries,
+//!     &search_config,
+//!     &BatchConfig::default(),
+//!     |entry| {
+//!         println!("{}", BatchFormat::Tsv.format(&entry));
+//!         Ok(())
+//!     },
+//! )?;
+//! # Ok::<(), color_eyre::Report>(())
+//! ```
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use color_eyre::Result;
+
+use crate::http::HttpClient;
+use crate::japanese::{furigana, pitch};
+use crate::search::{self, Definition, SearchConfig, SearchResult};
+
+/// Options of a batch run
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// How many lookups may run at the same time
+    pub concurrency: usize,
+    /// Minimum time between the start of two lookups, shared by all workers
+    pub interval: Option<Duration>,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            interval: None,
+        }
+    }
+}
+
+/// The result of looking up one query of the batch
+#[derive(Debug)]
+pub struct BatchEntry {
+    pub query: String,
+    pub result: Result<SearchResult>,
+}
+
+impl BatchEntry {
+    /// The translated sentences, joined together
+    pub fn translation(&self) -> Option<String> {
+        let translations = self.result.as_ref().ok()?.translations.as_ref()?;
+        Some(
+            translations
+                .iter()
+                .filter_map(|translation| translation.translated.as_deref())
+                .collect(),
+        )
+    }
+
+    /// The transliteration of the translation
+    pub fn transliteration(&self) -> Option<String> {
+        self.result
+            .as_ref()
+            .ok()?
+            .literation
+            .as_ref()?
+            .translated
+            .clone()
+    }
+
+    /// The definitions of the first dictionary word found
+    fn word_definitions(&self) -> Vec<&Definition> {
+        let Some(definitions) = self
+            .result
+            .as_ref()
+            .ok()
+            .and_then(|res| res.definitions.as_ref())
+        else {
+            return Vec::new();
+        };
+        let Some(headword) = definitions.iter().find_map(|def| def.headword.as_ref()) else {
+            return Vec::new();
+        };
+        definitions
+            .iter()
+            .filter(|def| def.headword.as_ref() == Some(headword))
+            .collect()
+    }
+}
+
+/// Joins the fields with tabs, replacing the tabs and line breaks inside them with spaces
+fn tsv_line<const N: usize>(fields: [Option<String>; N]) -> String {
+    fields
+        .into_iter()
+        .map(|field| field.unwrap_or_default().replace(['\t', '\n', '\r'], " "))
+        .collect::<Vec<String>>()
+        .join("\t")
+}
+
+/// Writes the word in the furigana syntax of Anki: `食[た]べ 物[もの]`
+///
+/// Anki reads the kanji before a reading up to the previous space.
+fn anki_furigana(text: &str, reading: Option<&str>) -> String {
+    let Some(segments) = reading.and_then(|reading| furigana::align(text, reading)) else {
+        return text.to_string();
+    };
+    let mut res = String::new();
+    for segment in segments {
+        match &segment.reading {
+            Some(reading) => {
+                if !res.is_empty() {
+                    res.push(' ');
+                }
+                res.push_str(&format!("{}[{}]", segment.text, reading));
+            }
+            None => res.push_str(&segment.text),
+        }
+    }
+    res
+}
+
+/// Output formats of a batch run
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum BatchFormat {
+    /// Tab separated: source, translation, transliteration and error
+    #[default]
+    Tsv,
+    /// One JSON object per line
+    Jsonl,
+    /// Tab separated notes to import into Anki: word, reading in furigana, meaning and pitch
+    /// accent
+    Anki,
+}
+
+impl BatchFormat {
+    /// Formats the entry as one line, without the line break
+    pub fn format(&self, entry: &BatchEntry) -> String {
+        let error = entry.result.as_ref().err().map(|err| err.to_string());
+        let definitions = entry.word_definitions();
+        let headword = definitions.first().and_then(|def| def.headword.as_ref());
+        let pitch_accents = definitions
+            .first()
+            .and_then(|def| def.pitch_accents.as_ref());
+        match self {
+            BatchFormat::Tsv => tsv_line([
+                Some(entry.query.clone()),
+                entry.translation(),
+                entry.transliteration(),
+                error,
+            ]),
+            BatchFormat::Jsonl => serde_json::json!({
+                "source": entry.query,
+                "translation": entry.translation(),
+                "transliteration": entry.transliteration(),
+                "reading": headword.and_then(|word| word.reading.as_ref()),
+                "pitch_accents": pitch_accents,
+                "frequency": definitions.first().and_then(|def| def.frequency),
+                "error": error,
+            })
+            .to_string(),
+            BatchFormat::Anki => {
+                let word = headword.map_or(&entry.query, |word| &word.text);
+                let reading = headword.and_then(|word| word.reading.as_deref());
+                let meaning = match definitions.is_empty() {
+                    true => entry.translation(),
+                    false => Some(
+                        definitions
+                            .iter()
+                            .map(|def| def.meaning.as_str())
+                            .collect::<Vec<&str>>()
+                            .join("; "),
+                    ),
+                };
+                let pitch = pitch_accents
+                    .and_then(|accents| accents.first())
+                    .map(|accent| pitch::html(reading.unwrap_or(word), *accent));
+                tsv_line([
+                    Some(word.clone()),
+                    Some(anki_furigana(word, reading)),
+                    meaning,
+                    pitch,
+                ])
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BatchFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("No variables should be skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// Looks up every query with the options of `search_config`
+///
+/// The lookups share the connections of `client`. A failing query is reported in its own entry,
+/// and doesn't stop the others. Each entry is passed to `on_entry` in the order of `queries`, as
+/// soon as the entries before it are done; only the entries that finished early are held back.
+/// An error from `on_entry` stops the batch, and is returned.
+pub fn lookup_batch<I, F>(
+    client: &HttpClient,
+    queries: I,
+    search_config: &SearchConfig,
+    batch_config: &BatchConfig,
+    mut on_entry: F,
+) -> Result<()>
+where
+    I: IntoIterator<Item = String>,
+    I::IntoIter: Send,
+    F: FnMut(BatchEntry) -> Result<()>,
+{
+    let queries = Mutex::new(queries.into_iter().enumerate());
+    let next_start = Mutex::new(Instant::now());
+    let (sender, receiver) = mpsc::channel::<(usize, BatchEntry)>();
+
+    thread::scope(|scope| {
+        for _ in 0..batch_config.concurrency.max(1) {
+            let sender = sender.clone();
+            let (queries, next_start) = (&queries, &next_start);
+            scope.spawn(move || loop {
+                let Some((index, query)) = queries.lock().expect("Lock poisoned").next() else {
+                    break;
+                };
+
+                if let Some(interval) = batch_config.interval {
+                    let start = {
+                        let mut next_start = next_start.lock().expect("Lock poisoned");
+                        let start = (*next_start).max(Instant::now());
+                        *next_start = start + interval;
+                        start
+                    };
+                    thread::sleep(start.saturating_duration_since(Instant::now()));
+                }
+
+                let config = SearchConfig {
+                    query,
+                    ..search_config.clone()
+                };
+                let result = search::lookup_with_client(client, &config);
+                let entry = BatchEntry {
+                    query: config.query,
+                    result,
+                };
+                if sender.send((index, entry)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Entries that finished before an earlier one, by index
+        let mut finished = BTreeMap::new();
+        let mut next = 0;
+        // Returning early drops the receiver, which stops the workers at their next entry
+        for (index, entry) in receiver {
+            finished.insert(index, entry);
+            while let Some(entry) = finished.remove(&next) {
+                on_entry(entry)?;
+                next += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::{Literation, Translation, Word};
+    use crate::translators::SearchProvider;
+
+    fn entry(result: Result<SearchResult>) -> BatchEntry {
+        BatchEntry {
+            query: "Open\tfile".to_string(),
+            result,
+        }
+    }
+
+    #[test]
+    fn format_entries() {
+        let result = SearchResult {
+            provider: SearchProvider::GoogleTranslate,
+            translations: Some(vec![Translation {
+                orig: Some("Open file".to_string()),
+                translated: Some("ファイルを開く".to_string()),
+            }]),
+            definitions: None,
+            src_lang: Some("en".to_string()),
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: Some(Literation {
+                orig: None,
+                translated: Some("Fairu o hiraku".to_string()),
+            }),
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: None,
+            tokens: None,
+            warnings: None,
+        };
+        let ok = entry(Ok(result));
+        assert_eq!(
+            BatchFormat::Tsv.format(&ok),
+            "Open file\tファイルを開く\tFairu o hiraku\t"
+        );
+
+        let err = entry(Err(color_eyre::eyre::eyre!("offline")));
+        assert_eq!(BatchFormat::Tsv.format(&err), "Open file\t\t\toffline");
+        let json: serde_json::Value =
+            serde_json::from_str(&BatchFormat::Jsonl.format(&err)).unwrap();
+        assert_eq!(json["source"], "Open\tfile");
+        assert_eq!(json["error"], "offline");
+        assert!(json["translation"].is_null());
+    }
+
+    #[test]
+    fn format_words() {
+        let definition = |meaning: &str| Definition {
+            headword: Some(Word {
+                text: "食べ物".to_string(),
+                reading: Some("たべもの".to_string()),
+            }),
+            pitch_accents: Some(vec![3, 2]),
+            other_spellings: None,
+            meaning: meaning.to_string(),
+            rich_meaning: None,
+            pos: "Noun".to_string(),
+            reverse_translation: None,
+            confidence: None,
+            frequency: Some(1520),
+            examples: None,
+            relations: None,
+        };
+        let result = SearchResult {
+            provider: SearchProvider::Jisho,
+            translations: None,
+            definitions: Some(vec![definition("food"), definition("provisions")]),
+            src_lang: None,
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: None,
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: None,
+            tokens: None,
+            warnings: None,
+        };
+        let ok = entry(Ok(result));
+        assert_eq!(
+            BatchFormat::Anki.format(&ok),
+            "食べ物\t食[た]べ 物[もの]\tfood; provisions\t\
+            た<span style=\"text-decoration: overline\">べも</span>の"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&BatchFormat::Jsonl.format(&ok)).unwrap();
+        assert_eq!(json["reading"], "たべもの");
+        assert_eq!(json["pitch_accents"], serde_json::json!([3, 2]));
+        assert_eq!(json["frequency"], 1520);
+    }
+
+    #[test]
+    fn errors_are_reported_per_query() {
+        // Google translate requires a target language, so every lookup fails before sending a
+        // request
+        let mut entries = Vec::new();
+        lookup_batch(
+            &HttpClient::default(),
+            (0..10).map(|i| i.to_string()),
+            &SearchConfig::default(),
+            &BatchConfig {
+                concurrency: 3,
+                interval: Some(Duration::from_millis(1)),
+            },
+            |entry| {
+                entries.push(entry);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(entries.len(), 10);
+        for (i, entry) in entries.iter().enumerate() {
+            assert_eq!(entry.query, i.to_string());
+            assert!(entry.result.is_err());
+        }
+    }
+
+    #[test]
+    fn stop_on_callback_error() {
+        let mut count = 0;
+        let result = lookup_batch(
+            &HttpClient::default(),
+            (0..100).map(|i| i.to_string()),
+            &SearchConfig::default(),
+            &BatchConfig {
+                concurrency: 3,
+                interval: None,
+            },
+            |entry| {
+                assert_eq!(entry.query, count.to_string());
+                count += 1;
+                match count {
+                    5 => Err(color_eyre::eyre::eyre!("closed")),
+                    _ => Ok(()),
+                }
+            },
+        );
+
+        assert_eq!(result.unwrap_err().to_string(), "closed");
+        assert_eq!(count, 5);
+    }
+}
@@ -0,0 +1,3 @@
+//! Tools for looking up Chinese text
+pub mod pinyin;
+pub mod segment;
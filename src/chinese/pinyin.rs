@@ -0,0 +1,139 @@
+//! Converts pinyin between tone numbers and tone marks
+//!
+//! Dictionaries like CC-CEDICT write the tones with numbers after the syllables, 5 being the
+//! neutral tone, and `u:` for ü.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::chinese::pinyin::{strip_tones, to_tone_marks};
+//!
+//! assert_eq!(to_tone_marks("ni3 hao3"), "nǐ hǎo");
+//! assert_eq!(to_tone_marks("lu:4 se4"), "lǜ sè");
+//! assert_eq!(strip_tones("Nǐ hǎo"), "ni hao");
+//! ```
+
+/// The vowels with their four tone marks
+const TONE_MARKS: &[(char, [char; 4])] = &[
+    ('a', ['ā', 'á', 'ǎ', 'à']),
+    ('e', ['ē', 'é', 'ě', 'è']),
+    ('i', ['ī', 'í', 'ǐ', 'ì']),
+    ('o', ['ō', 'ó', 'ǒ', 'ò']),
+    ('u', ['ū', 'ú', 'ǔ', 'ù']),
+    ('ü', ['ǖ', 'ǘ', 'ǚ', 'ǜ']),
+    ('A', ['Ā', 'Á', 'Ǎ', 'À']),
+    ('E', ['Ē', 'É', 'Ě', 'È']),
+    ('I', ['Ī', 'Í', 'Ǐ', 'Ì']),
+    ('O', ['Ō', 'Ó', 'Ǒ', 'Ò']),
+    ('U', ['Ū', 'Ú', 'Ǔ', 'Ù']),
+    ('Ü', ['Ǖ', 'Ǘ', 'Ǚ', 'Ǜ']),
+];
+
+/// Writes the tones of the syllables with marks: `zhong1 guo2` is `zhōng guó`
+///
+/// Words that aren't numbered syllables, like punctuation, are kept as they are.
+pub fn to_tone_marks(numbered: &str) -> String {
+    numbered
+        .split(' ')
+        .map(syllable_tone_marks)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn syllable_tone_marks(syllable: &str) -> String {
+    let Some(tone) = syllable
+        .chars()
+        .last()
+        .and_then(|last| last.to_digit(10))
+        .filter(|tone| (1..=5).contains(tone))
+    else {
+        return syllable.to_string();
+    };
+    let letters = syllable[..syllable.len() - 1]
+        .replace("u:", "ü")
+        .replace("U:", "Ü")
+        .replace('v', "ü");
+    if tone == 5 {
+        return letters;
+    }
+
+    // The mark goes on a or e, on the o of ou, or else on the last vowel
+    let is_vowel = |c: char| TONE_MARKS.iter().any(|(vowel, _)| *vowel == c);
+    let lower = letters.to_lowercase();
+    let position = lower
+        .find(['a', 'e'])
+        .or_else(|| lower.find("ou"))
+        .or_else(|| {
+            letters
+                .char_indices()
+                .rfind(|(_, c)| is_vowel(*c))
+                .map(|(i, _)| i)
+        });
+    let Some(position) = position else {
+        return letters;
+    };
+
+    letters
+        .char_indices()
+        .map(|(i, c)| match i == position {
+            true => TONE_MARKS
+                .iter()
+                .find(|(vowel, _)| *vowel == c)
+                .map_or(c, |(_, marks)| marks[tone as usize - 1]),
+            false => c,
+        })
+        .collect()
+}
+
+/// Removes the tone marks and numbers, and lowers the case, to compare pinyin without tones
+pub fn strip_tones(pinyin: &str) -> String {
+    pinyin
+        .chars()
+        .filter(|c| !c.is_ascii_digit())
+        .map(|c| {
+            TONE_MARKS
+                .iter()
+                .find(|(_, marks)| marks.contains(&c))
+                .map_or(c, |(vowel, _)| *vowel)
+        })
+        .collect::<String>()
+        .replace("u:", "ü")
+        .replace("U:", "Ü")
+        .to_lowercase()
+}
+
+/// Whether the text may be pinyin, with or without tone numbers or marks
+pub fn is_pinyin(text: &str) -> bool {
+    let stripped = strip_tones(text);
+    !stripped.trim().is_empty()
+        && stripped
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || matches!(c, ' ' | '\'' | 'ü'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tone_marks() {
+        assert_eq!(to_tone_marks("zhong1 guo2"), "zhōng guó");
+        assert_eq!(to_tone_marks("Bei3 jing1"), "Běi jīng");
+        assert_eq!(to_tone_marks("xiu1 xi5"), "xiū xi");
+        assert_eq!(to_tone_marks("gou3 dui4"), "gǒu duì");
+        assert_eq!(to_tone_marks("nu:3 er2"), "nǚ ér");
+        assert_eq!(to_tone_marks("A1 la1 bo2"), "Ā lā bó");
+        assert_eq!(to_tone_marks("xx5"), "xx");
+        assert_eq!(to_tone_marks("· Ka3 er3"), "· Kǎ ěr");
+    }
+
+    #[test]
+    fn pinyin_without_tones() {
+        assert_eq!(strip_tones("ni3 hao3"), "ni hao");
+        assert_eq!(strip_tones("lǜ sè"), "lü se");
+        assert_eq!(strip_tones("lu:4 se4"), "lü se");
+        assert!(is_pinyin("ni3hao3"));
+        assert!(is_pinyin("nǐ hǎo"));
+        assert!(!is_pinyin("你好"));
+        assert!(!is_pinyin("hello!"));
+    }
+}
@@ -0,0 +1,65 @@
+//! Splits sentences written without spaces into words
+//!
+//! The sentence is read from the start, taking the longest word of the dictionary at every step.
+//! Characters that start no word are words on their own.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::chinese::segment::longest_match;
+//!
+//! let words = ["我", "喜欢", "学生", "学", "生活"];
+//! let is_word = |text: &str| words.contains(&text);
+//! assert_eq!(longest_match("我喜欢学生活", 2, is_word), vec!["我", "喜欢", "学生", "活"]);
+//! ```
+
+/// Splits the text into the longest words, of at most `max_len` characters
+///
+/// Runs of letters and digits, like `DVD` or `2024`, are kept together. Whitespace and
+/// punctuation split the text, and are dropped.
+pub fn longest_match(text: &str, max_len: usize, is_word: impl Fn(&str) -> bool) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let c = chars[start];
+        if c.is_whitespace() || !c.is_alphanumeric() {
+            start += 1;
+            continue;
+        }
+        let longest = (2..=max_len.min(chars.len() - start))
+            .rev()
+            .find(|len| is_word(&chars[start..start + len].iter().collect::<String>()));
+        let len = match longest {
+            Some(len) => len,
+            None if c.is_ascii_alphanumeric() => chars[start..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .count(),
+            None => 1,
+        };
+        words.push(chars[start..start + len].iter().collect());
+        start += len;
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_sentences() {
+        let words = ["我们", "是", "学生", "大学", "大学生", "北京"];
+        let is_word = |text: &str| words.contains(&text);
+        assert_eq!(
+            longest_match("我们是大学生。", 3, is_word),
+            vec!["我们", "是", "大学生"]
+        );
+        // Unknown characters are words on their own
+        assert_eq!(
+            longest_match("他在北京 买了DVD", 3, is_word),
+            vec!["他", "在", "北京", "买", "了", "DVD"]
+        );
+        assert_eq!(longest_match("", 3, is_word), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,308 @@
+//! Watches the clipboard, to look up the words copied while reading
+//!
+//! The clipboard is read by polling a command: `wl-paste` on Wayland, `xclip` or `xsel` on X11,
+//! `pbpaste` on macOS and PowerShell on Windows, or the command configured. On Linux, the
+//! primary selection, the text selected with the mouse, can be watched instead.
+//!
+//! Changes are only sent once the content stops changing for a while, so that selecting text
+//! doesn't look up every step of the selection. The content found when the watch starts is not
+//! sent, and neither is empty or oversized content.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use wdym::clipboard::{self, ClipboardConfig};
+//!
+//! let copies = clipboard::watch(ClipboardConfig::default()).unwrap();
+//! for text in copies {
+//!     println!("copied {}", text);
+//! }
+//! ```
+use std::error::Error;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use color_eyre::Result;
+
+/// Which clipboard to watch
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Selection {
+    /// The clipboard, copied to with Ctrl+C
+    #[default]
+    Clipboard,
+    /// The text selected with the mouse, only on Linux
+    Primary,
+}
+
+impl std::fmt::Display for Selection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("No variables should be skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// Options of the clipboard watch
+#[derive(Debug, Clone)]
+pub struct ClipboardConfig {
+    pub selection: Selection,
+    /// Command printing the content of the clipboard, instead of the one of the platform
+    pub command: Option<String>,
+    /// Time between two reads of the clipboard
+    pub interval: Duration,
+    /// How long the content must stay the same to be sent
+    pub debounce: Duration,
+    /// Content longer than this many characters is skipped
+    pub max_chars: usize,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            selection: Selection::Clipboard,
+            command: None,
+            interval: Duration::from_millis(250),
+            debounce: Duration::from_millis(500),
+            max_chars: 500,
+        }
+    }
+}
+
+/// Failing to read the clipboard
+#[derive(Debug, Clone)]
+pub struct ClipboardError(String);
+
+impl Error for ClipboardError {}
+
+impl std::fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to read the clipboard: {}", self.0)
+    }
+}
+
+/// A command reading the clipboard
+#[derive(Debug, Clone)]
+struct Reader {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Reader {
+    fn new(command: &str) -> Result<Self> {
+        let mut words = shell_words::split(command)
+            .map_err(|err| ClipboardError(format!("invalid command {}: {}", command, err)))?
+            .into_iter();
+        let program = words
+            .next()
+            .ok_or_else(|| ClipboardError("the command is empty".to_string()))?;
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// The first of the commands of the platform that can be run
+    fn detect(selection: Selection) -> Result<Self> {
+        let primary = selection == Selection::Primary;
+        let candidates: Vec<&str> = if cfg!(target_os = "macos") {
+            vec!["pbpaste"]
+        } else if cfg!(windows) {
+            vec!["powershell -NoProfile -Command Get-Clipboard -Raw"]
+        } else if primary {
+            vec![
+                "wl-paste --no-newline --primary",
+                "xclip -o -selection primary",
+                "xsel --output --primary",
+            ]
+        } else {
+            vec![
+                "wl-paste --no-newline",
+                "xclip -o -selection clipboard",
+                "xsel --output --clipboard",
+            ]
+        };
+        if primary && !cfg!(target_os = "linux") {
+            Err(ClipboardError(
+                "the primary selection is only on Linux".to_string(),
+            ))?;
+        }
+        // wl-paste is installed along xclip on some X11 systems, where it can't connect
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        candidates
+            .into_iter()
+            .filter(|command| wayland || !command.starts_with("wl-paste"))
+            .map(Reader::new)
+            .find(|reader| reader.as_ref().is_ok_and(Reader::is_installed))
+            .unwrap_or_else(|| {
+                Err(ClipboardError(
+                    "no clipboard command found, install wl-clipboard, xclip or xsel".to_string(),
+                )
+                .into())
+            })
+    }
+
+    fn is_installed(&self) -> bool {
+        Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+    }
+
+    /// The content of the clipboard, `None` if it is empty or not text
+    fn read(&self) -> Result<Option<String>> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| ClipboardError(format!("{}: {}", self.program, err)))?;
+        // The commands fail when the clipboard is empty
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(String::from_utf8(output.stdout).ok())
+    }
+}
+
+/// Sends the content of the clipboard once it stops changing
+#[derive(Debug)]
+struct Debouncer {
+    debounce: Duration,
+    max_chars: usize,
+    /// The content last seen, sent or not
+    last: Option<String>,
+    /// When the content last seen was first seen, if it is waiting to be sent
+    changed: Option<Instant>,
+}
+
+impl Debouncer {
+    /// Starts from the content of the clipboard, which is not sent
+    fn new(config: &ClipboardConfig, initial: Option<String>) -> Self {
+        Self {
+            debounce: config.debounce,
+            max_chars: config.max_chars,
+            last: initial,
+            changed: None,
+        }
+    }
+
+    /// Reads the content of the clipboard at `now`, and returns the text to send if any
+    fn update(&mut self, content: Option<String>, now: Instant) -> Option<String> {
+        if content != self.last {
+            self.last = content;
+            self.changed = Some(now);
+            return None;
+        }
+        if now.duration_since(self.changed?) < self.debounce {
+            return None;
+        }
+        self.changed = None;
+        let text = self.last.as_deref()?.trim();
+        if text.is_empty() || text.chars().count() > self.max_chars {
+            return None;
+        }
+        Some(text.to_string())
+    }
+}
+
+/// Watches the clipboard in the background, sending the text copied
+///
+/// Fails if the clipboard can't be read. The watch stops when the receiver is dropped.
+pub fn watch(config: ClipboardConfig) -> Result<Receiver<String>> {
+    let reader = match &config.command {
+        Some(command) => Reader::new(command)?,
+        None => Reader::detect(config.selection)?,
+    };
+    let mut debouncer = Debouncer::new(&config, reader.read()?);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || loop {
+        thread::sleep(config.interval);
+        // Failures to read are transient, like while the clipboard owner exits
+        let Ok(content) = reader.read() else {
+            continue;
+        };
+        if let Some(text) = debouncer.update(content, Instant::now()) {
+            if sender.send(text).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn debounce_changes() {
+        let config = ClipboardConfig {
+            max_chars: 10,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut debouncer = Debouncer::new(&config, Some("old".to_string()));
+
+        // The initial content is not sent
+        assert_eq!(debouncer.update(Some("old".to_string()), at(1000)), None);
+
+        // Selecting a word, then keeping it
+        assert_eq!(debouncer.update(Some("ca".to_string()), at(1100)), None);
+        assert_eq!(debouncer.update(Some(" cat\n".to_string()), at(1200)), None);
+        assert_eq!(debouncer.update(Some(" cat\n".to_string()), at(1500)), None);
+        assert_eq!(
+            debouncer.update(Some(" cat\n".to_string()), at(1700)),
+            Some("cat".to_string())
+        );
+        assert_eq!(debouncer.update(Some(" cat\n".to_string()), at(2500)), None);
+
+        // Oversized, empty and non-text content
+        for content in [
+            Some("a long sentence".to_string()),
+            Some(" ".to_string()),
+            None,
+        ] {
+            assert_eq!(debouncer.update(content.clone(), at(3000)), None);
+            assert_eq!(debouncer.update(content, at(4000)), None);
+        }
+
+        // Copying the same word again after something else
+        assert_eq!(debouncer.update(Some(" cat\n".to_string()), at(5000)), None);
+        assert_eq!(
+            debouncer.update(Some(" cat\n".to_string()), at(6000)),
+            Some("cat".to_string())
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn read_with_command() {
+        let reader = Reader::new("printf '%s' 'copied text'").unwrap();
+        assert_eq!(reader.read().unwrap(), Some("copied text".to_string()));
+        assert_eq!(Reader::new("false").unwrap().read().unwrap(), None);
+        assert!(Reader::new("wdym-no-such-command").unwrap().read().is_err());
+        assert!(Reader::new("").is_err());
+
+        // A file standing for the clipboard, whose first content is skipped
+        let path = std::env::temp_dir().join(format!("wdym-clipboard-{}.txt", std::process::id()));
+        std::fs::write(&path, "before").unwrap();
+        let copies = watch(ClipboardConfig {
+            command: Some(format!("cat '{}'", path.display())),
+            interval: Duration::from_millis(10),
+            debounce: Duration::from_millis(20),
+            ..Default::default()
+        })
+        .unwrap();
+        std::fs::write(&path, "猫").unwrap();
+        let copied = copies.recv_timeout(Duration::from_secs(5));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(copied.unwrap(), "猫");
+    }
+}
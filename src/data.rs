@@ -0,0 +1,71 @@
+//! Local data files, like offline dictionaries
+//!
+//! Data files are looked up in `$WDYM_DATA_DIR` if set, or in the `wdym` directory of the user
+//! data directory: `~/.local/share/wdym` on Linux.
+use std::path::PathBuf;
+
+/// Set this environment variable to look up data files in another directory
+pub const DATA_DIR_ENV: &str = "WDYM_DATA_DIR";
+
+/// The directory of the data files
+pub fn data_dir() -> Option<PathBuf> {
+    match std::env::var_os(DATA_DIR_ENV) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => Some(dirs::data_dir()?.join("wdym")),
+    }
+}
+
+/// Finds the first of the files present in the data directory
+pub fn find(names: &[&str]) -> Option<PathBuf> {
+    let dir = data_dir()?;
+    names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Finds the first of the directories present in the data directory
+pub fn find_dir(names: &[&str]) -> Option<PathBuf> {
+    let dir = data_dir()?;
+    names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_dir())
+}
+
+/// Opens a data file, decompressing it if its name ends in `.gz`
+pub fn open(path: &std::path::Path) -> std::io::Result<Box<dyn std::io::BufRead>> {
+    let file = std::fs::File::open(path)?;
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        Ok(Box::new(std::io::BufReader::new(
+            flate2::read::GzDecoder::new(file),
+        )))
+    } else {
+        Ok(Box::new(std::io::BufReader::new(file)))
+    }
+}
+
+/// A data file isn't installed
+#[derive(Debug, Clone)]
+pub struct MissingDataError {
+    /// What the data is, like `KANJIDIC2`
+    pub name: String,
+    /// The file names looked for
+    pub files: Vec<String>,
+}
+
+impl std::fmt::Display for MissingDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is not installed, put {} in {}",
+            self.name,
+            self.files.join(" or "),
+            data_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or(format!("${}", DATA_DIR_ENV))
+        )
+    }
+}
+
+impl std::error::Error for MissingDataError {}
@@ -0,0 +1,365 @@
+//! Markdown documents
+//!
+//! Code blocks, front matter, HTML blocks and link targets are kept verbatim, as well as the
+//! markers of headings, quotes, lists and tables.
+use super::{split_line_ending, Part, Segments};
+
+pub(super) fn parse(input: &str) -> Segments {
+    let mut segments = Segments::default();
+    let mut fence: Option<&str> = None;
+    let mut in_front_matter = false;
+    let mut prev_blank = true;
+    let mut in_list = false;
+
+    for (index, line) in input.split_inclusive('\n').enumerate() {
+        let (content, ending) = split_line_ending(line);
+        let trimmed = content.trim_start();
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            segments.verbatim(line);
+            continue;
+        }
+        if in_front_matter {
+            in_front_matter = content.trim_end() != "---";
+            segments.verbatim(line);
+            continue;
+        }
+
+        let blank = trimmed.is_empty();
+        let indented = content.starts_with("    ") || content.starts_with('\t');
+        if is_list_item(&content[block_prefix_len(content)..]) {
+            in_list = true;
+        } else if !blank && !indented && prev_blank {
+            in_list = false;
+        }
+
+        if index == 0 && content.trim_end() == "---" {
+            in_front_matter = true;
+            segments.verbatim(line);
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            segments.verbatim(line);
+        } else if blank
+            || (indented && prev_blank && !in_list)
+            || trimmed.starts_with('<')
+            || is_reference_definition(trimmed)
+            || is_table_separator(trimmed)
+            || is_thematic_break(trimmed)
+        {
+            segments.verbatim(line);
+        } else {
+            let prefix_len = block_prefix_len(content);
+            let (prefix, text) =
+                content.split_at(prefix_len + list_marker_len(&content[prefix_len..]));
+            segments.verbatim(prefix);
+            if text.trim_start().starts_with('|') {
+                table_row(&mut segments, text);
+            } else {
+                inline(&mut segments, text);
+            }
+            segments.verbatim(ending);
+        }
+        prev_blank = blank;
+    }
+    segments
+}
+
+/// Length of the indentation, quote markers and heading markers starting the line
+fn block_prefix_len(line: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &line[len..];
+        let trimmed = rest.trim_start_matches([' ', '\t']);
+        len += rest.len() - trimmed.len();
+        if trimmed.starts_with('>') {
+            len += 1;
+            continue;
+        }
+        let hashes = trimmed.len() - trimmed.trim_start_matches('#').len();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with([' ', '\t']) {
+            len += hashes;
+            continue;
+        }
+        return len;
+    }
+}
+
+/// Length of the list marker starting the text, with its task box and spacing
+fn list_marker_len(text: &str) -> usize {
+    if !is_list_item(text) {
+        return 0;
+    }
+    let marker = text
+        .find(|c: char| !c.is_ascii_digit())
+        .map(|end| end + 1)
+        .unwrap_or(0);
+    let rest = &text[marker..];
+    let mut len = marker + rest.len() - rest.trim_start().len();
+    for task in ["[ ] ", "[x] ", "[X] "] {
+        if text[len..].starts_with(task) {
+            len += task.len();
+        }
+    }
+    len
+}
+
+fn is_list_item(text: &str) -> bool {
+    if let Some(rest) = text.strip_prefix(['-', '*', '+']) {
+        return rest.starts_with([' ', '\t']);
+    }
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    (1..=9).contains(&digits)
+        && text[digits..].starts_with(['.', ')'])
+        && text[digits + 1..].starts_with([' ', '\t'])
+}
+
+/// `[id]: https://example.com`
+fn is_reference_definition(line: &str) -> bool {
+    line.starts_with('[') && line.contains("]:")
+}
+
+/// `| --- | :---: |`
+fn is_table_separator(line: &str) -> bool {
+    line.contains('-') && line.chars().all(|c| "|-: \t".contains(c))
+}
+
+/// `---`, `***` or `___`
+fn is_thematic_break(line: &str) -> bool {
+    let chars: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|marker| chars.chars().all(|c| c.to_string() == *marker))
+}
+
+/// Adds a table row, translating every cell on its own
+fn table_row(segments: &mut Segments, row: &str) {
+    let mut cells = row.split('|').peekable();
+    while let Some(cell) = cells.next() {
+        inline(segments, cell);
+        if cells.peek().is_some() {
+            segments.verbatim("|");
+        }
+    }
+}
+
+/// Adds the inline text of a block as a sentence, with its code spans, emphasis, link targets
+/// and autolinks as markup
+fn inline(segments: &mut Segments, text: &str) {
+    let mut parts = Vec::new();
+    // Where the text of the links opened ends, and where their target does
+    let mut links: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        // Links whose text ended in a code span or a tag aren't links after all
+        while links.last().is_some_and(|(close, _)| *close < i) {
+            links.pop();
+        }
+        let markup_len = if let Some(&(_, end)) = links.last().filter(|(close, _)| *close == i) {
+            links.pop();
+            Some(end - i)
+        } else if let Some(escaped) = rest.strip_prefix('\\') {
+            // Escaped punctuation is text
+            i += 1 + escaped.chars().next().map_or(0, char::len_utf8);
+            continue;
+        } else if rest.starts_with('`') {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            rest[ticks..]
+                .find(&rest[..ticks])
+                .map(|end| ticks + end + ticks)
+        } else if let Some((open, close, end)) = link(rest) {
+            links.push((i + close, i + end));
+            Some(open)
+        } else if rest.starts_with('<') {
+            rest.find('>').map(|end| end + 1)
+        } else if rest.starts_with(['*', '~']) || is_underscore_emphasis(text, i) {
+            let marker = rest.chars().next().expect("Not empty");
+            Some(rest.len() - rest.trim_start_matches(marker).len())
+        } else {
+            None
+        };
+
+        match markup_len {
+            Some(len) => {
+                parts.push(Part::Text(&text[start..i]));
+                parts.push(Part::Markup(&rest[..len]));
+                i += len;
+                start = i;
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    parts.push(Part::Text(&text[start..]));
+    segments.sentence(&parts);
+}
+
+/// The link or image starting the text: the length of its opening bracket, the offset of its
+/// closing bracket, and the end of its target
+///
+/// The brackets and parentheses are matched, so that `[a] b (c)` or `a ] b ( c` aren't links.
+fn link(text: &str) -> Option<(usize, usize, usize)> {
+    let open = match text {
+        _ if text.starts_with("![") => 2,
+        _ if text.starts_with('[') => 1,
+        _ => return None,
+    };
+    let close = open + closing(&text[open..], '[', ']')?;
+    let target = &text[close + 1..];
+    if !target.starts_with('(') {
+        return None;
+    }
+    let end = close + 1 + 1 + closing(&target[1..], '(', ')')? + 1;
+    Some((open, close, end))
+}
+
+/// The offset of the bracket closing the one before the text, skipping nested brackets, code
+/// spans and escaped characters
+fn closing(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        if c == '\\' {
+            i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+            continue;
+        }
+        if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            i += rest[ticks..]
+                .find(&rest[..ticks])
+                .map_or(ticks, |end| ticks + end + ticks);
+            continue;
+        }
+        if c == close {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        } else if c == open {
+            depth += 1;
+        }
+        i += c.len_utf8();
+    }
+    None
+}
+
+/// Whether the `_` at `index` of the text marks emphasis, rather than joining the words of
+/// `snake_case`
+fn is_underscore_emphasis(text: &str, index: usize) -> bool {
+    if !text[index..].starts_with('_') {
+        return false;
+    }
+    let before = text[..index].chars().next_back();
+    let after = text[index..].trim_start_matches('_').chars().next();
+    !(before.is_some_and(char::is_alphanumeric) && after.is_some_and(char::is_alphanumeric))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::document::{Document, DocumentFormat};
+
+    const MARKDOWN: &str = r#"---
+title: Example
+---
+# What Do You Mean?
+
+`wdym` is a program that looks up words.
+See the [guide](https://example.com/guide) for details.
+
+> [!NOTE] This software is still under development.
+
+- [x] Google translate
+- [ ] jisho.org
+  continued item
+
+1. First step
+
+```
+cargo run --release -- --help
+```
+
+    indented code
+
+| Name | Description |
+| ---- | ----------- |
+| wdym | Translator  |
+
+***
+<div align="center">logo</div>
+[guide]: https://example.com
+"#;
+
+    #[test]
+    fn markdown_round_trip() {
+        let document = Document::parse(DocumentFormat::Markdown, MARKDOWN);
+        assert_eq!(document.render(&[]), MARKDOWN);
+        assert_eq!(
+            document.texts(),
+            vec![
+                "What Do You Mean?",
+                "is a program that looks up words.",
+                "See the {0}guide{1} for details.",
+                "[!NOTE] This software is still under development.",
+                "Google translate",
+                "jisho.org",
+                "continued item",
+                "First step",
+                "Name",
+                "Description",
+                "wdym",
+                "Translator",
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_translation_keeps_markup() {
+        let document = Document::parse(DocumentFormat::Markdown, "## Usage\n\n- Run `wdym` now\n");
+        assert_eq!(document.texts(), vec!["Usage", "Run {0} now"]);
+        let translated = document.render(&[
+            Some("使い方".to_string()),
+            Some("今すぐ {0} を実行".to_string()),
+        ]);
+        assert_eq!(translated, "## 使い方\n\n- 今すぐ `wdym` を実行\n");
+
+        // A translation that lost markup is left untranslated
+        let translated = document.render(&[None, Some("今すぐ実行".to_string())]);
+        assert_eq!(translated, "## Usage\n\n- Run `wdym` now\n");
+    }
+
+    #[test]
+    fn markdown_sentences_are_whole() {
+        let document = Document::parse(
+            DocumentFormat::Markdown,
+            "It is **very** fast, see ![the chart](img/chart (1).png) and [*the* docs](https://example.com).\n",
+        );
+        assert_eq!(
+            document.texts(),
+            vec!["It is {0}very{1} fast, see {2}the chart{3} and {4}the{5} docs{6}."]
+        );
+        let translated = document.render(&[Some(
+            "{0}とても{1}速い、{2}図{3}と{4}その{5}文書{6}を見て。".to_string(),
+        )]);
+        assert_eq!(
+            translated,
+            "**とても**速い、![図](img/chart (1).png)と[*その*文書](https://example.com)を見て。\n"
+        );
+    }
+
+    #[test]
+    fn unmatched_brackets_are_text() {
+        let document = Document::parse(
+            DocumentFormat::Markdown,
+            "Keep a ] b ( c, [d] (e) and [f](g as snake_case text.\n",
+        );
+        assert_eq!(
+            document.texts(),
+            vec!["Keep a ] b ( c, [d] (e) and [f](g as snake_case text."]
+        );
+    }
+}
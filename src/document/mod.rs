@@ -0,0 +1,450 @@
+//! Translates the text of structured documents, keeping their structure
+//!
+//! A document is parsed into segments. The verbatim segments, like markup, timings and code, are
+//! written back byte for byte, and only the text segments are translated.
+//!
+//! A sentence is translated whole, even when markup or line breaks split it: they are replaced by
+//! placeholders like `{0}` in the text to translate, and put back in the translation.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::document::{Document, DocumentFormat};
+//!
+//! let input = "1\n00:00:01,000 --> 00:00:02,000\nHello!\n";
+//! let document = Document::parse(DocumentFormat::Srt, input);
+//! assert_eq!(document.texts(), vec!["Hello!"]);
+//! assert_eq!(document.render(&[None]), input);
+//! assert_eq!(
+//!     document.render(&[Some("こんにちは！".to_string())]),
+//!     "1\n00:00:01,000 --> 00:00:02,000\nこんにちは！\n"
+//! );
+//! ```
+use std::path::Path;
+
+use clap::ValueEnum;
+use color_eyre::eyre::eyre;
+
+use crate::batch::{lookup_batch, BatchConfig, BatchEntry};
+use crate::http::HttpClient;
+use crate::search::SearchConfig;
+
+mod markdown;
+mod po;
+mod subtitles;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Markdown,
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT subtitles
+    Vtt,
+    /// Gettext translation catalog
+    Po,
+}
+
+impl DocumentFormat {
+    /// Guesses the format from the file extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(DocumentFormat::Markdown),
+            "srt" => Some(DocumentFormat::Srt),
+            "vtt" => Some(DocumentFormat::Vtt),
+            "po" | "pot" => Some(DocumentFormat::Po),
+            _ => None,
+        }
+    }
+
+    /// Formats a translation to replace the raw text of a segment
+    fn encode(&self, translation: &str) -> String {
+        match self {
+            DocumentFormat::Po => po::quote(translation),
+            // The formats are line based, so a translation can't span several lines
+            _ => translation.replace(['\r', '\n'], " "),
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("No variables should be skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Written back as is
+    Verbatim(String),
+    /// `raw` is the text as written in the document, and `text` the text to translate
+    Text {
+        raw: String,
+        text: String,
+        /// The placeholders of `text`, and the markup they stand for
+        placeholders: Vec<(String, String)>,
+    },
+}
+
+/// A part of a sentence being parsed
+#[derive(Debug, Clone, Copy)]
+enum Part<'a> {
+    Text(&'a str),
+    /// Markup in the sentence, like a code span, a tag or a line break
+    Markup(&'a str),
+}
+
+/// A parsed document
+#[derive(Debug, Clone)]
+pub struct Document {
+    format: DocumentFormat,
+    segments: Vec<Segment>,
+}
+
+impl Document {
+    pub fn parse(format: DocumentFormat, input: &str) -> Self {
+        let segments = match format {
+            DocumentFormat::Markdown => markdown::parse(input),
+            DocumentFormat::Srt | DocumentFormat::Vtt => subtitles::parse(input),
+            DocumentFormat::Po => po::parse(input),
+        };
+        Self {
+            format,
+            segments: segments.segments,
+        }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The texts to translate, in order
+    pub fn texts(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Text { text, .. } => Some(text.as_str()),
+                Segment::Verbatim(_) => None,
+            })
+            .collect()
+    }
+
+    /// Writes the document, replacing every text with its translation
+    ///
+    /// `translations` is in the order of `texts`. A text without a translation, or whose
+    /// translation lost some of its placeholders, is written as is.
+    pub fn render(&self, translations: &[Option<String>]) -> String {
+        let mut translations = translations.iter();
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Verbatim(verbatim) => output.push_str(verbatim),
+                Segment::Text {
+                    raw, placeholders, ..
+                } => {
+                    let translation = translations.next().and_then(Option::as_ref);
+                    match translation.and_then(|translation| {
+                        restore(&self.format.encode(translation), placeholders)
+                    }) {
+                        Some(translation) => output.push_str(&translation),
+                        None => output.push_str(raw),
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    /// Translates the texts of the document, as a batch
+    ///
+    /// Returns the translated document, and the entries of the texts that failed to translate, or
+    /// whose translation lost some of their markup. Those texts are left untranslated.
+    pub fn translate(
+        &self,
+        client: &HttpClient,
+        search_config: &SearchConfig,
+        batch_config: &BatchConfig,
+    ) -> (String, Vec<BatchEntry>) {
+        let queries: Vec<String> = self.texts().into_iter().map(str::to_string).collect();
+        let mut placeholders = self.segments.iter().filter_map(|segment| match segment {
+            Segment::Text { placeholders, .. } => Some(placeholders),
+            Segment::Verbatim(_) => None,
+        });
+        let mut translations = Vec::with_capacity(queries.len());
+        let mut failures = Vec::new();
+        lookup_batch(client, queries, search_config, batch_config, |mut entry| {
+            let placeholders = placeholders.next().expect("One entry per text");
+            let translation = entry.translation();
+            let lost_markup = translation.as_ref().is_some_and(|translation| {
+                restore(&self.format.encode(translation), placeholders).is_none()
+            });
+            if lost_markup {
+                entry.result = Err(eyre!("the translation lost some of the markup of the text"));
+            }
+            translations.push(translation.filter(|_| !lost_markup));
+            if entry.result.is_err() {
+                failures.push(entry);
+            }
+            Ok(())
+        })
+        .expect("Collecting the entries doesn't fail");
+
+        (self.render(&translations), failures)
+    }
+}
+
+/// Collects the segments of a document while it is parsed
+#[derive(Debug, Default)]
+struct Segments {
+    segments: Vec<Segment>,
+}
+
+impl Segments {
+    fn verbatim(&mut self, verbatim: &str) {
+        if verbatim.is_empty() {
+            return;
+        }
+        match self.segments.last_mut() {
+            Some(Segment::Verbatim(last)) => last.push_str(verbatim),
+            _ => self.segments.push(Segment::Verbatim(verbatim.to_string())),
+        }
+    }
+
+    /// Adds a text written as is, keeping its surrounding whitespace verbatim
+    fn plain(&mut self, text: &str) {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return self.verbatim(text);
+        }
+        let start = text.len() - text.trim_start().len();
+        self.verbatim(&text[..start]);
+        self.segments.push(Segment::Text {
+            raw: trimmed.to_string(),
+            text: trimmed.to_string(),
+            placeholders: Vec::new(),
+        });
+        self.verbatim(&text[start + trimmed.len()..]);
+    }
+
+    fn text(&mut self, raw: &str, text: String) {
+        self.segments.push(Segment::Text {
+            raw: raw.to_string(),
+            text,
+            placeholders: Vec::new(),
+        });
+    }
+
+    /// Adds a sentence split by markup, to translate whole
+    ///
+    /// The markup and whitespace around the text are kept verbatim, and the markup inside is
+    /// replaced by placeholders.
+    fn sentence(&mut self, parts: &[Part]) {
+        let has_text = |part: &Part| matches!(part, Part::Text(text) if !text.trim().is_empty());
+        let (Some(first), Some(last)) = (
+            parts.iter().position(has_text),
+            parts.iter().rposition(has_text),
+        ) else {
+            for part in parts {
+                let (Part::Text(verbatim) | Part::Markup(verbatim)) = part;
+                self.verbatim(verbatim);
+            }
+            return;
+        };
+        for part in &parts[..first] {
+            let (Part::Text(verbatim) | Part::Markup(verbatim)) = part;
+            self.verbatim(verbatim);
+        }
+        if first == last {
+            let Part::Text(text) = parts[first] else {
+                unreachable!("Only text parts have text")
+            };
+            self.plain(text);
+        } else {
+            self.split_sentence(&parts[first..=last]);
+        }
+        for part in &parts[last + 1..] {
+            let (Part::Text(verbatim) | Part::Markup(verbatim)) = part;
+            self.verbatim(verbatim);
+        }
+    }
+
+    /// Adds a sentence starting and ending with text, with markup inside
+    fn split_sentence(&mut self, parts: &[Part]) {
+        let (Part::Text(head), Part::Text(tail)) = (parts[0], parts[parts.len() - 1]) else {
+            unreachable!("The sentence starts and ends with text")
+        };
+        let head_trimmed = head.trim_start();
+        let tail_trimmed = tail.trim_end();
+        self.verbatim(&head[..head.len() - head_trimmed.len()]);
+
+        // The markup next to each other, and the blanks between, stand for a single placeholder
+        let mut pieces: Vec<(bool, String)> = Vec::new();
+        for (index, part) in parts.iter().enumerate() {
+            let (is_markup, mut content) = match part {
+                Part::Markup(markup) => (true, *markup),
+                Part::Text(text) => (text.trim().is_empty(), *text),
+            };
+            if index == 0 {
+                content = head_trimmed;
+            }
+            if index == parts.len() - 1 {
+                content = &content[..content.len() - (tail.len() - tail_trimmed.len())];
+            }
+            match pieces.last_mut() {
+                Some((last_is_markup, last)) if *last_is_markup == is_markup => {
+                    last.push_str(content)
+                }
+                _ => pieces.push((is_markup, content.to_string())),
+            }
+        }
+
+        // Numbered past the placeholders the text may already contain
+        let texts: Vec<&str> = pieces
+            .iter()
+            .filter(|(is_markup, _)| !is_markup)
+            .map(|(_, text)| text.as_str())
+            .collect();
+        let mut number = 0;
+        let mut placeholders = Vec::new();
+        let mut raw = String::new();
+        let mut text = String::new();
+        for (is_markup, content) in &pieces {
+            raw.push_str(content);
+            if !is_markup {
+                text.push_str(content);
+                continue;
+            }
+            let placeholder = loop {
+                let placeholder = format!("{{{}}}", number);
+                number += 1;
+                if !texts.iter().any(|text| text.contains(&placeholder)) {
+                    break placeholder;
+                }
+            };
+            if content.starts_with(char::is_whitespace) {
+                text.push(' ');
+            }
+            text.push_str(&placeholder);
+            if content.ends_with(char::is_whitespace) {
+                text.push(' ');
+            }
+            placeholders.push((placeholder, content.clone()));
+        }
+        self.segments.push(Segment::Text {
+            raw,
+            text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+            placeholders,
+        });
+        self.verbatim(&tail[tail_trimmed.len()..]);
+    }
+}
+
+/// Puts the markup back in place of the placeholders of the translation
+///
+/// Returns `None` if a placeholder is missing or repeated, as the markup can't be put back. The
+/// spaces the text has around a placeholder standing for blanks are dropped.
+fn restore(translation: &str, placeholders: &[(String, String)]) -> Option<String> {
+    let mut found = Vec::new();
+    for (placeholder, markup) in placeholders {
+        let mut matches = translation.match_indices(placeholder.as_str());
+        let (index, _) = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+        found.push((index, placeholder.len(), markup));
+    }
+    found.sort_unstable();
+
+    let mut res = String::with_capacity(translation.len());
+    let mut start = 0;
+    for (index, len, markup) in found {
+        res.push_str(&translation[start..index]);
+        if markup.starts_with(char::is_whitespace) {
+            res.truncate(res.trim_end().len());
+        }
+        res.push_str(markup);
+        start = index + len;
+        if markup.ends_with(char::is_whitespace) {
+            start += translation[start..].len() - translation[start..].trim_start().len();
+        }
+    }
+    res.push_str(&translation[start..]);
+    Some(res)
+}
+
+/// Splits the line ending off a line
+fn split_line_ending(line: &str) -> (&str, &str) {
+    let content = line.trim_end_matches(['\r', '\n']);
+    (content, &line[content.len()..])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            DocumentFormat::from_path("subs/movie.SRT"),
+            Some(DocumentFormat::Srt)
+        );
+        assert_eq!(
+            DocumentFormat::from_path("README.md"),
+            Some(DocumentFormat::Markdown)
+        );
+        assert_eq!(
+            DocumentFormat::from_path("messages.pot"),
+            Some(DocumentFormat::Po)
+        );
+        assert_eq!(DocumentFormat::from_path("notes.txt"), None);
+    }
+
+    #[test]
+    fn missing_translations_are_kept() {
+        let document = Document::parse(DocumentFormat::Markdown, "# Title\n\nBody\n");
+        assert_eq!(document.texts(), vec!["Title", "Body"]);
+        assert_eq!(
+            document.render(&[Some("題名".to_string())]),
+            "# 題名\n\nBody\n"
+        );
+        assert_eq!(
+            document.render(&[None, Some("本文\nです".to_string())]),
+            "# Title\n\n本文 です\n"
+        );
+    }
+
+    #[test]
+    fn restore_placeholders() {
+        let placeholders = vec![
+            ("{0}".to_string(), "`wdym`".to_string()),
+            ("{1}".to_string(), "\n".to_string()),
+        ];
+        assert_eq!(
+            restore("{0} で調べる。 {1} 簡単！", &placeholders).unwrap(),
+            "`wdym` で調べる。\n簡単！"
+        );
+        assert!(restore("調べる。{1}", &placeholders).is_none());
+        assert!(restore("{0}{0}{1}", &placeholders).is_none());
+        assert_eq!(restore("{0}", &[]).unwrap(), "{0}");
+    }
+
+    #[test]
+    fn placeholders_past_the_text() {
+        let mut segments = Segments::default();
+        segments.sentence(&[
+            Part::Text("Use {0} as "),
+            Part::Markup("`{0}`"),
+            Part::Text(" here"),
+        ]);
+        let Segment::Text {
+            text, placeholders, ..
+        } = &segments.segments[0]
+        else {
+            panic!("the sentence should be text");
+        };
+        assert_eq!(text, "Use {0} as {1} here");
+        assert_eq!(placeholders, &[("{1}".to_string(), "`{0}`".to_string())]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Gettext translation catalogs (`.po`, `.pot`)
+//!
+//! Every empty `msgstr` is filled with the translation of its `msgid`, or of its `msgid_plural`
+//! for the plural forms past the first. Entries already translated, the header and obsolete
+//! entries are kept verbatim.
+use super::{split_line_ending, Segments};
+
+pub(super) fn parse(input: &str) -> Segments {
+    let mut segments = Segments::default();
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut msgid = String::new();
+    let mut msgid_plural: Option<String> = None;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (content, _) = split_line_ending(lines[i]);
+        let (keyword, _) = content.split_once(' ').unwrap_or((content, ""));
+        // The string of a keyword may continue on the following lines
+        let end = i
+            + 1
+            + lines[i + 1..]
+                .iter()
+                .take_while(|line| line.trim_start().starts_with('"'))
+                .count();
+        let value: String = lines[i..end].iter().map(|line| unquote(line)).collect();
+
+        match keyword {
+            "msgid" => {
+                msgid = value.clone();
+                msgid_plural = None;
+            }
+            "msgid_plural" => msgid_plural = Some(value.clone()),
+            _ => {}
+        }
+
+        let source = match keyword {
+            "msgstr" | "msgstr[0]" => Some(&msgid),
+            _ if keyword.starts_with("msgstr[") => msgid_plural.as_ref(),
+            _ => None,
+        };
+        match source {
+            // Fill the first string of the msgstr, and keep its empty continuations
+            Some(source) if value.is_empty() && !source.is_empty() => {
+                let (line, _) = split_line_ending(lines[i]);
+                let quote = line.find('"').unwrap_or(line.len());
+                let close = line[quote..]
+                    .find("\"\"")
+                    .map(|start| quote + start + 2)
+                    .unwrap_or(line.len());
+                segments.verbatim(&line[..quote]);
+                segments.text(&line[quote..close], source.clone());
+                segments.verbatim(&lines[i][close..]);
+                for line in &lines[i + 1..end] {
+                    segments.verbatim(line);
+                }
+            }
+            _ => {
+                for line in &lines[i..end] {
+                    segments.verbatim(line);
+                }
+            }
+        }
+        i = end;
+    }
+    segments
+}
+
+/// Reads the string literal of a line, like `msgid "Hello\n"`
+fn unquote(line: &str) -> String {
+    let (line, _) = split_line_ending(line);
+    let (Some(start), Some(end)) = (line.find('"'), line.rfind('"')) else {
+        return String::new();
+    };
+    if end <= start {
+        return String::new();
+    }
+
+    let mut res = String::new();
+    let mut chars = line[start + 1..end].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some(c) => res.push(c),
+            None => {}
+        }
+    }
+    res
+}
+
+/// Writes the text as a string literal
+pub(super) fn quote(text: &str) -> String {
+    let mut res = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::document::{Document, DocumentFormat};
+
+    const PO: &str = r#"# Translation of wdym.
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: src/app.rs:44
+msgid "What Do You Mean?"
+msgstr ""
+
+#, c-format
+msgid ""
+"Press <q> to quit\n"
+"and \"enter\" to search"
+msgstr ""
+""
+
+msgid "Book"
+msgstr "本"
+
+msgctxt "menu"
+msgid "%d file"
+msgid_plural "%d files"
+msgstr[0] ""
+msgstr[1] ""
+
+#~ msgid "Old"
+#~ msgstr ""
+"#;
+
+    #[test]
+    fn po_round_trip() {
+        let document = Document::parse(DocumentFormat::Po, PO);
+        assert_eq!(document.render(&[]), PO);
+        assert_eq!(
+            document.texts(),
+            vec![
+                "What Do You Mean?",
+                "Press <q> to quit\nand \"enter\" to search",
+                "%d file",
+                "%d files",
+            ]
+        );
+    }
+
+    #[test]
+    fn po_translation() {
+        let document = Document::parse(DocumentFormat::Po, PO);
+        let translated = document.render(&[
+            Some("どういう意味？".to_string()),
+            Some("<q>で終了\n\"enter\"で検索".to_string()),
+            None,
+            Some("%d 個のファイル".to_string()),
+        ]);
+        assert!(translated.contains("msgid \"What Do You Mean?\"\nmsgstr \"どういう意味？\"\n"));
+        assert!(translated.contains("msgstr \"<q>で終了\\n\\\"enter\\\"で検索\"\n\"\"\n"));
+        assert!(translated.contains("msgstr[0] \"\"\nmsgstr[1] \"%d 個のファイル\"\n"));
+        assert!(translated.contains("msgstr \"本\""));
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        let text = "tab\there \"quoted\" back\\slash\n";
+        assert_eq!(unquote(&format!("msgid {}", quote(text))), text);
+    }
+}
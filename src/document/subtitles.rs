@@ -0,0 +1,76 @@
+//! SubRip (`.srt`) and WebVTT (`.vtt`) subtitles
+//!
+//! Both are made of blocks separated by blank lines. The lines after the timing line of a cue are
+//! text, everything else (indices, timings, headers, `NOTE` and `STYLE` blocks) is kept verbatim.
+//! The text of a cue is translated whole, even over several lines.
+use super::{split_line_ending, Part, Segments};
+
+pub(super) fn parse(input: &str) -> Segments {
+    let mut segments = Segments::default();
+    let mut in_cue_text = false;
+    // The parts of the text of the current cue
+    let mut cue = Vec::new();
+    for line in input.split_inclusive('\n') {
+        let (content, ending) = split_line_ending(line);
+        if content.trim().is_empty() {
+            in_cue_text = false;
+            segments.sentence(&std::mem::take(&mut cue));
+            segments.verbatim(line);
+        } else if in_cue_text {
+            tagged_text(&mut cue, content);
+            cue.push(Part::Markup(ending));
+        } else {
+            in_cue_text = content.contains("-->");
+            segments.verbatim(line);
+        }
+    }
+    segments.sentence(&cue);
+    segments
+}
+
+/// Adds a line of cue text, with its tags like `<i>` and `<v Speaker>` as markup
+fn tagged_text<'a>(cue: &mut Vec<Part<'a>>, line: &'a str) {
+    let mut rest = line;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        cue.push(Part::Text(&rest[..start]));
+        cue.push(Part::Markup(&rest[start..=start + len]));
+        rest = &rest[start + len + 1..];
+    }
+    cue.push(Part::Text(rest));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::document::{Document, DocumentFormat};
+
+    const SRT: &str = "1\r\n00:00:01,000 --> 00:00:04,000\r\nHello there.\r\n<i>General Kenobi!</i>\r\n\r\n2\r\n00:00:05,000 --> 00:00:06,500\r\nYou are a bold one.\r\n";
+
+    const VTT: &str = "WEBVTT - Example\n\nNOTE This is a comment\n\nSTYLE\n::cue { color: yellow }\n\nintro\n00:01.000 --> 00:04.000 line:0\n<v Obi-Wan>Hello there.\n\n00:05.000 --> 00:06.000\n- Run!\n";
+
+    #[test]
+    fn srt_round_trip() {
+        let document = Document::parse(DocumentFormat::Srt, SRT);
+        assert_eq!(
+            document.texts(),
+            vec!["Hello there. {0}General Kenobi!", "You are a bold one."]
+        );
+        assert_eq!(document.render(&[]), SRT);
+
+        // The line break of the cue is put back where the translation has it
+        let translated = document.render(&[Some("やあ。 {0}ケノービ将軍！".to_string()), None]);
+        assert_eq!(
+            translated,
+            "1\r\n00:00:01,000 --> 00:00:04,000\r\nやあ。\r\n<i>ケノービ将軍！</i>\r\n\r\n2\r\n00:00:05,000 --> 00:00:06,500\r\nYou are a bold one.\r\n"
+        );
+    }
+
+    #[test]
+    fn vtt_round_trip() {
+        let document = Document::parse(DocumentFormat::Vtt, VTT);
+        assert_eq!(document.texts(), vec!["Hello there.", "- Run!"]);
+        assert_eq!(document.render(&[]), VTT);
+    }
+}
@@ -0,0 +1,178 @@
+//! How common words are, from frequency lists
+//!
+//! Frequency lists are not shipped with wdym. Save a list of the words of a language, most common
+//! first, in the data directory, see [`crate::data`], as `frequency-<language>.txt` with the ISO
+//! 639-3 code of the language: `frequency-eng.txt`. Lines may go on with the count of the word
+//! after a space or tab, like the lists of <https://github.com/hermitdave/FrequencyWords>.
+//!
+//! Frequency dictionaries in the Yomitan format are imported with the other Yomitan
+//! dictionaries, see [`crate::translators::yomitan`].
+//!
+//! # Examples
+//! ```rust
+//! use wdym::frequency::{Commonness, FrequencyList};
+//!
+//! let list = FrequencyList::parse("the 23135851162\nof 13151942776\nhello 25000\n".as_bytes())
+//!     .unwrap();
+//! assert_eq!(list.rank("Hello"), Some(3));
+//! assert_eq!(Commonness::of(3), Commonness::VeryCommon);
+//! ```
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use clap::ValueEnum;
+use color_eyre::Result;
+use isolang::Language;
+
+use crate::data::{self, MissingDataError};
+
+/// Words ranked by how common they are, 1 being the most common
+#[derive(Debug, Default)]
+pub struct FrequencyList {
+    ranks: HashMap<String, u32>,
+}
+
+/// The frequency lists loaded from the data directory
+static LOADED: LazyLock<Mutex<HashMap<Language, Arc<FrequencyList>>>> =
+    LazyLock::new(Default::default);
+
+impl FrequencyList {
+    /// Reads a word per line, most common first
+    ///
+    /// Anything after a tab, or after a space when there is no tab, is ignored. Empty lines and
+    /// lines starting with `#` are skipped, and words listed twice keep their first rank.
+    pub fn parse(input: impl BufRead) -> Result<Self> {
+        let mut ranks = HashMap::new();
+        let mut rank = 0;
+        for line in input.lines() {
+            let line = line?;
+            let word = match line.split_once('\t') {
+                Some((word, _)) => word,
+                None => line.split_whitespace().next().unwrap_or_default(),
+            }
+            .trim();
+            if word.is_empty() || word.starts_with('#') {
+                continue;
+            }
+            rank += 1;
+            ranks.entry(word.to_lowercase()).or_insert(rank);
+        }
+        Ok(Self { ranks })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(data::open(path.as_ref())?)
+    }
+
+    /// The frequency list of the language in the data directory, loaded on first use
+    pub fn installed(language: Language) -> Result<Arc<Self>> {
+        let mut loaded = LOADED.lock().expect("Lock poisoned");
+        if let Some(list) = loaded.get(&language) {
+            return Ok(Arc::clone(list));
+        }
+        let name = format!("frequency-{}.txt", language.to_639_3());
+        let names = [format!("{}.gz", name), name];
+        let list = match data::find(&names.iter().map(String::as_str).collect::<Vec<&str>>()) {
+            Some(path) => Self::load(path)?,
+            None => Err(MissingDataError {
+                name: format!("The frequency list of {}", language.to_name()),
+                files: names.to_vec(),
+            })?,
+        };
+        let list = Arc::new(list);
+        loaded.insert(language, Arc::clone(&list));
+        Ok(list)
+    }
+
+    /// The rank of the word, ignoring the case
+    pub fn rank(&self, word: &str) -> Option<u32> {
+        self.ranks.get(&word.trim().to_lowercase()).copied()
+    }
+}
+
+/// How common a word is, from its rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Commonness {
+    /// One of the 1000 most common words
+    VeryCommon,
+    /// One of the 10000 most common words
+    Common,
+    /// One of the 30000 most common words
+    Uncommon,
+    Rare,
+}
+
+impl Commonness {
+    pub fn of(rank: u32) -> Self {
+        match rank {
+            0..=1_000 => Commonness::VeryCommon,
+            1_001..=10_000 => Commonness::Common,
+            10_001..=30_000 => Commonness::Uncommon,
+            _ => Commonness::Rare,
+        }
+    }
+}
+
+impl std::fmt::Display for Commonness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Commonness::VeryCommon => "very common",
+            Commonness::Common => "common",
+            Commonness::Uncommon => "uncommon",
+            Commonness::Rare => "rare",
+        }
+        .fmt(f)
+    }
+}
+
+/// How definitions are ordered
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DefinitionOrder {
+    /// As the provider ranks them, by its confidence
+    #[default]
+    Confidence,
+    /// Most common words first, from the frequency lists
+    Frequency,
+}
+
+impl std::fmt::Display for DefinitionOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("No variables should be skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_lists() {
+        let list = FrequencyList::parse(
+            "# words of the subtitles\nyou\t22484400\nI 19975318\n\nthe 17667005\nYou 1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(list.rank("you"), Some(1));
+        assert_eq!(list.rank("i"), Some(2));
+        assert_eq!(list.rank("the"), Some(3));
+        assert_eq!(list.rank("subtitles"), None);
+
+        // Lists without counts, and words with spaces before a tab
+        let list = FrequencyList::parse("の\nに\nice cream\t10\n".as_bytes()).unwrap();
+        assert_eq!(list.rank("に"), Some(2));
+        assert_eq!(list.rank("ice cream"), Some(3));
+    }
+
+    #[test]
+    fn commonness() {
+        assert_eq!(Commonness::of(1), Commonness::VeryCommon);
+        assert_eq!(Commonness::of(1_001), Commonness::Common);
+        assert_eq!(Commonness::of(20_000), Commonness::Uncommon);
+        assert_eq!(Commonness::of(50_000).to_string(), "rare");
+    }
+}
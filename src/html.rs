@@ -0,0 +1,806 @@
+//! Converts the HTML of dictionary entries to styled text
+//!
+//! Only a safe subset of HTML is rendered, the text and its emphasis: bold, italic and
+//! underlined elements and inline styles, links, lists, ruby text and tables. Block elements and
+//! line breaks start new lines, and whitespace is collapsed like browsers do. Scripts, styles,
+//! comments and hidden elements are dropped.
+//!
+//! The lines are converted to ratatui lines for the TUI, or to plain text for the other outputs.
+//!
+//! It is the one renderer of dictionary markup: MDict entries are HTML, and the structured
+//! content of Yomitan glossaries is written as HTML to be rendered here.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::html;
+//!
+//! let lines = html::to_plain_text("<b>cat</b> <i>n.</i><br>a small   animal");
+//! assert_eq!(lines, vec!["cat n.", "a small animal"]);
+//!
+//! let lines = html::to_plain_text("<ol><li><ruby>猫<rp>(</rp><rt>ねこ</rt><rp>)</rp></ruby></li></ol>");
+//! assert_eq!(lines, vec!["1. 猫(ねこ)"]);
+//! ```
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+
+use crate::search::{StyledSpan, TextStyle};
+
+/// A piece of HTML
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Text(String),
+    /// An opening tag, in lower case, with its attributes
+    Start(String, Vec<(String, String)>),
+    End(String),
+}
+
+/// Elements without content or end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose content is never shown
+const HIDDEN_ELEMENTS: &[&str] = &["head", "rp", "script", "style", "template", "title"];
+
+/// Elements on lines of their own
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "tfoot",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// Splits the HTML into text and tags
+fn tokenize(html: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(decode_entities(rest)));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(decode_entities(&rest[..start])));
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = tag_end(rest) else {
+            // A lone `<` is text
+            tokens.push(Token::Text(decode_entities(rest)));
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::End(name.trim().to_lowercase()));
+            continue;
+        }
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        let (name, attributes) = parse_tag(tag);
+        if HIDDEN_ELEMENTS.contains(&name.as_str()) {
+            // Skip to the end tag
+            let end = rest.match_indices("</").find(|(end, _)| {
+                rest.get(end + 2..end + 2 + name.len())
+                    .is_some_and(|end_name| end_name.eq_ignore_ascii_case(&name))
+            });
+            rest = match end {
+                Some((end, _)) => rest[end..]
+                    .find('>')
+                    .map_or("", |close| &rest[end + close + 1..]),
+                None => "",
+            };
+            continue;
+        }
+        tokens.push(Token::Start(name, attributes));
+    }
+    tokens
+}
+
+/// The index of the `>` closing the tag at the start of the text, outside of quotes
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '>') => return Some(index),
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The name and attributes of a tag, without its brackets: `a href="entry://cat"`
+fn parse_tag(tag: &str) -> (String, Vec<(String, String)>) {
+    let tag = tag.trim_end_matches('/');
+    let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+    let name = tag[..name_end].to_lowercase();
+    let mut attributes = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_lowercase();
+        rest = rest[key_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, after) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let value = &value[1..];
+                        let end = value.find(quote).unwrap_or(value.len());
+                        (&value[..end], value.get(end + 1..).unwrap_or_default())
+                    }
+                    _ => {
+                        let end = value
+                            .find(|c: char| c.is_whitespace())
+                            .unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = after.trim_start();
+                decode_entities(value)
+            }
+            None => String::new(),
+        };
+        if !key.is_empty() {
+            attributes.push((key, value));
+        }
+    }
+    (name, attributes)
+}
+
+/// The named character references of Latin-1, of punctuation, arrows and symbols, and of Greek
+/// letters, besides the ones escaping markup
+const NAMED_ENTITIES: &[(&str, char)] = &[
+    ("nbsp", '\u{a0}'),
+    ("iexcl", '¡'),
+    ("cent", '¢'),
+    ("pound", '£'),
+    ("curren", '¤'),
+    ("yen", '¥'),
+    ("brvbar", '¦'),
+    ("sect", '§'),
+    ("uml", '¨'),
+    ("copy", '©'),
+    ("ordf", 'ª'),
+    ("laquo", '«'),
+    ("not", '¬'),
+    ("shy", '\u{ad}'),
+    ("reg", '®'),
+    ("macr", '¯'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("sup2", '²'),
+    ("sup3", '³'),
+    ("acute", '´'),
+    ("micro", 'µ'),
+    ("para", '¶'),
+    ("middot", '·'),
+    ("cedil", '¸'),
+    ("sup1", '¹'),
+    ("ordm", 'º'),
+    ("raquo", '»'),
+    ("frac14", '¼'),
+    ("frac12", '½'),
+    ("frac34", '¾'),
+    ("iquest", '¿'),
+    ("Agrave", 'À'),
+    ("Aacute", 'Á'),
+    ("Acirc", 'Â'),
+    ("Atilde", 'Ã'),
+    ("Auml", 'Ä'),
+    ("Aring", 'Å'),
+    ("AElig", 'Æ'),
+    ("Ccedil", 'Ç'),
+    ("Egrave", 'È'),
+    ("Eacute", 'É'),
+    ("Ecirc", 'Ê'),
+    ("Euml", 'Ë'),
+    ("Igrave", 'Ì'),
+    ("Iacute", 'Í'),
+    ("Icirc", 'Î'),
+    ("Iuml", 'Ï'),
+    ("ETH", 'Ð'),
+    ("Ntilde", 'Ñ'),
+    ("Ograve", 'Ò'),
+    ("Oacute", 'Ó'),
+    ("Ocirc", 'Ô'),
+    ("Otilde", 'Õ'),
+    ("Ouml", 'Ö'),
+    ("times", '×'),
+    ("Oslash", 'Ø'),
+    ("Ugrave", 'Ù'),
+    ("Uacute", 'Ú'),
+    ("Ucirc", 'Û'),
+    ("Uuml", 'Ü'),
+    ("Yacute", 'Ý'),
+    ("THORN", 'Þ'),
+    ("szlig", 'ß'),
+    ("agrave", 'à'),
+    ("aacute", 'á'),
+    ("acirc", 'â'),
+    ("atilde", 'ã'),
+    ("auml", 'ä'),
+    ("aring", 'å'),
+    ("aelig", 'æ'),
+    ("ccedil", 'ç'),
+    ("egrave", 'è'),
+    ("eacute", 'é'),
+    ("ecirc", 'ê'),
+    ("euml", 'ë'),
+    ("igrave", 'ì'),
+    ("iacute", 'í'),
+    ("icirc", 'î'),
+    ("iuml", 'ï'),
+    ("eth", 'ð'),
+    ("ntilde", 'ñ'),
+    ("ograve", 'ò'),
+    ("oacute", 'ó'),
+    ("ocirc", 'ô'),
+    ("otilde", 'õ'),
+    ("ouml", 'ö'),
+    ("divide", '÷'),
+    ("oslash", 'ø'),
+    ("ugrave", 'ù'),
+    ("uacute", 'ú'),
+    ("ucirc", 'û'),
+    ("uuml", 'ü'),
+    ("yacute", 'ý'),
+    ("thorn", 'þ'),
+    ("yuml", 'ÿ'),
+    ("OElig", 'Œ'),
+    ("oelig", 'œ'),
+    ("Scaron", 'Š'),
+    ("scaron", 'š'),
+    ("Yuml", 'Ÿ'),
+    ("fnof", 'ƒ'),
+    ("circ", 'ˆ'),
+    ("tilde", '˜'),
+    ("ensp", '\u{2002}'),
+    ("emsp", '\u{2003}'),
+    ("thinsp", '\u{2009}'),
+    ("zwnj", '\u{200c}'),
+    ("zwj", '\u{200d}'),
+    ("lrm", '\u{200e}'),
+    ("rlm", '\u{200f}'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("sbquo", '‚'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("bdquo", '„'),
+    ("dagger", '†'),
+    ("Dagger", '‡'),
+    ("bull", '•'),
+    ("hellip", '…'),
+    ("permil", '‰'),
+    ("prime", '′'),
+    ("Prime", '″'),
+    ("lsaquo", '‹'),
+    ("rsaquo", '›'),
+    ("oline", '‾'),
+    ("frasl", '⁄'),
+    ("euro", '€'),
+    ("trade", '™'),
+    ("larr", '←'),
+    ("uarr", '↑'),
+    ("rarr", '→'),
+    ("darr", '↓'),
+    ("harr", '↔'),
+    ("lArr", '⇐'),
+    ("uArr", '⇑'),
+    ("rArr", '⇒'),
+    ("dArr", '⇓'),
+    ("hArr", '⇔'),
+    ("minus", '−'),
+    ("lowast", '∗'),
+    ("radic", '√'),
+    ("infin", '∞'),
+    ("ne", '≠'),
+    ("equiv", '≡'),
+    ("le", '≤'),
+    ("ge", '≥'),
+    ("sim", '∼'),
+    ("asymp", '≈'),
+    ("sdot", '⋅'),
+    ("loz", '◊'),
+    ("spades", '♠'),
+    ("clubs", '♣'),
+    ("hearts", '♥'),
+    ("diams", '♦'),
+    ("Alpha", 'Α'),
+    ("Beta", 'Β'),
+    ("Gamma", 'Γ'),
+    ("Delta", 'Δ'),
+    ("Epsilon", 'Ε'),
+    ("Zeta", 'Ζ'),
+    ("Eta", 'Η'),
+    ("Theta", 'Θ'),
+    ("Iota", 'Ι'),
+    ("Kappa", 'Κ'),
+    ("Lambda", 'Λ'),
+    ("Mu", 'Μ'),
+    ("Nu", 'Ν'),
+    ("Xi", 'Ξ'),
+    ("Omicron", 'Ο'),
+    ("Pi", 'Π'),
+    ("Rho", 'Ρ'),
+    ("Sigma", 'Σ'),
+    ("Tau", 'Τ'),
+    ("Upsilon", 'Υ'),
+    ("Phi", 'Φ'),
+    ("Chi", 'Χ'),
+    ("Psi", 'Ψ'),
+    ("Omega", 'Ω'),
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("omicron", 'ο'),
+    ("pi", 'π'),
+    ("rho", 'ρ'),
+    ("sigmaf", 'ς'),
+    ("sigma", 'σ'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("omega", 'ω'),
+    ("thetasym", 'ϑ'),
+    ("upsih", 'ϒ'),
+    ("piv", 'ϖ'),
+];
+
+/// Replaces the character references of the text: `&amp;`, `&eacute;`, `&#233;`, `&#xE9;`
+fn decode_entities(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                name if !name.starts_with('#') => {
+                    NAMED_ENTITIES.iter().find(|(entity, _)| *entity == name)?.1
+                }
+                name => {
+                    let code = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => name.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// An open element
+#[derive(Debug)]
+struct Element {
+    name: String,
+    /// The style of its content
+    style: TextStyle,
+    /// Whether its content is hidden, by `display: none`
+    hidden: bool,
+}
+
+/// A list being rendered
+#[derive(Debug)]
+struct List {
+    ordered: bool,
+    /// The number of items so far
+    items: usize,
+}
+
+/// Builds the lines of the HTML, element by element
+#[derive(Debug)]
+struct Renderer {
+    lines: Vec<Vec<StyledSpan>>,
+    open: Vec<Element>,
+    lists: Vec<List>,
+}
+
+impl Renderer {
+    fn style(&self) -> TextStyle {
+        self.open
+            .last()
+            .map(|element| element.style)
+            .unwrap_or_default()
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.open.last().is_some_and(|element| element.hidden)
+    }
+
+    fn start(&mut self, name: String, attributes: Vec<(String, String)>) {
+        if self.is_hidden() {
+            if !VOID_ELEMENTS.contains(&name.as_str()) {
+                let style = self.style();
+                self.open.push(Element {
+                    name,
+                    style,
+                    hidden: true,
+                });
+            }
+            return;
+        }
+        if name == "br" {
+            self.lines.push(Vec::new());
+        } else if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            break_line(&mut self.lines);
+        }
+        if VOID_ELEMENTS.contains(&name.as_str()) {
+            return;
+        }
+
+        let mut style = self.style();
+        match name.as_str() {
+            "b" | "strong" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => style.bold = true,
+            "i" | "em" | "cite" | "var" => style.italic = true,
+            "u" | "a" => style.underlined = true,
+            "small" | "sub" | "sup" | "rt" => style.dim = true,
+            _ => {}
+        }
+        let css = attributes
+            .iter()
+            .find(|(key, _)| key == "style")
+            .map_or("", |(_, css)| css.as_str());
+        let hidden = apply_css(css, &mut style);
+
+        match name.as_str() {
+            "ul" | "ol" => self.lists.push(List {
+                ordered: name == "ol",
+                items: 0,
+            }),
+            "li" if !hidden => {
+                let depth = self.lists.len().max(1);
+                let marker = match self.lists.last_mut() {
+                    Some(list) if list.ordered => {
+                        list.items += 1;
+                        format!("{}. ", list.items)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.push_raw(&format!("{}{}", "  ".repeat(depth - 1), marker), style);
+            }
+            // Cells after the first of a row are separated
+            "td" | "th" if !hidden && self.lines.last().is_some_and(|line| !line.is_empty()) => {
+                self.push_raw(" | ", TextStyle { dim: true, ..style })
+            }
+            "rt" if !hidden => self.push_raw("(", style),
+            _ => {}
+        }
+        self.open.push(Element {
+            name,
+            style,
+            hidden,
+        });
+    }
+
+    fn end(&mut self, name: &str) {
+        // Closes the element, and the ones left open inside it
+        let Some(index) = self.open.iter().rposition(|element| element.name == name) else {
+            return;
+        };
+        let element = &self.open[index];
+        let (style, hidden) = (element.style, element.hidden);
+        self.open.truncate(index);
+        if hidden {
+            return;
+        }
+        match name {
+            "ul" | "ol" => {
+                self.lists.pop();
+            }
+            "rt" => self.push_raw(")", style),
+            _ => {}
+        }
+        if BLOCK_ELEMENTS.contains(&name) {
+            break_line(&mut self.lines);
+        }
+    }
+
+    /// Appends the text to the last line, collapsing its whitespace
+    fn text(&mut self, text: &str) {
+        if self.is_hidden() {
+            return;
+        }
+        let line = self.lines.last().expect("There is always a line");
+        let ends_with_space = line
+            .last()
+            .is_none_or(|span| span.text.ends_with(char::is_whitespace));
+        let mut collapsed = String::new();
+        for (index, word) in text.split(|c: char| c.is_ascii_whitespace()).enumerate() {
+            if index > 0 && !collapsed.ends_with(' ') && !(collapsed.is_empty() && ends_with_space)
+            {
+                collapsed.push(' ');
+            }
+            collapsed.push_str(word);
+        }
+        self.push_raw(&collapsed, self.style());
+    }
+
+    /// Appends the text to the last line as it is
+    fn push_raw(&mut self, text: &str, style: TextStyle) {
+        if text.is_empty() {
+            return;
+        }
+        let line = self.lines.last_mut().expect("There is always a line");
+        match line.last_mut() {
+            Some(span) if span.style == style => span.text.push_str(text),
+            _ => line.push(StyledSpan {
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+
+    /// The lines, without empty ones and trailing whitespace
+    fn finish(self) -> Vec<Vec<StyledSpan>> {
+        self.lines
+            .into_iter()
+            .filter_map(|mut line| {
+                if let Some(span) = line.last_mut() {
+                    span.text.truncate(span.text.trim_end().len());
+                }
+                line.retain(|span| !span.text.is_empty());
+                (!line.is_empty()).then_some(line)
+            })
+            .collect()
+    }
+}
+
+/// Applies the declarations of a `style` attribute that can be shown in a terminal, and returns
+/// whether the element is hidden
+fn apply_css(css: &str, style: &mut TextStyle) -> bool {
+    let mut hidden = false;
+    for declaration in css.split(';') {
+        let Some((property, value)) = declaration.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_lowercase();
+        match property.trim().to_lowercase().as_str() {
+            "font-weight" => {
+                style.bold = match value.parse::<u32>() {
+                    Ok(weight) => weight >= 600,
+                    Err(_) => matches!(value.as_str(), "bold" | "bolder"),
+                }
+            }
+            "font-style" => style.italic = matches!(value.as_str(), "italic" | "oblique"),
+            "font-size" => {
+                style.dim |= matches!(value.as_str(), "small" | "smaller" | "x-small" | "xx-small")
+            }
+            "text-decoration" | "text-decoration-line" => {
+                style.underlined = value.contains("underline")
+            }
+            "display" => hidden = value == "none",
+            _ => {}
+        }
+    }
+    hidden
+}
+
+/// Converts the HTML to lines of styled text
+pub fn to_styled_lines(html: &str) -> Vec<Vec<StyledSpan>> {
+    let mut renderer = Renderer {
+        lines: vec![Vec::new()],
+        open: Vec::new(),
+        lists: Vec::new(),
+    };
+    for token in tokenize(html) {
+        match token {
+            Token::Text(text) => renderer.text(&text),
+            Token::Start(name, attributes) => renderer.start(name, attributes),
+            Token::End(name) => renderer.end(&name),
+        }
+    }
+    renderer.finish()
+}
+
+/// Converts the HTML to lines of ratatui
+pub fn to_lines(html: &str) -> Vec<Line<'static>> {
+    to_styled_lines(html)
+        .iter()
+        .map(|line| Line::from(to_spans(line)))
+        .collect()
+}
+
+/// Converts a line of styled text to spans of ratatui
+pub fn to_spans(line: &[StyledSpan]) -> Vec<Span<'static>> {
+    line.iter()
+        .map(|span| {
+            let mut modifier = Modifier::empty();
+            modifier.set(Modifier::BOLD, span.style.bold);
+            modifier.set(Modifier::ITALIC, span.style.italic);
+            modifier.set(Modifier::DIM, span.style.dim);
+            modifier.set(Modifier::UNDERLINED, span.style.underlined);
+            Span::styled(span.text.clone(), Style::new().add_modifier(modifier))
+        })
+        .collect()
+}
+
+/// The plain text of styled lines, for output without styles
+pub fn plain_text(line: &[StyledSpan]) -> String {
+    line.iter().map(|span| span.text.as_str()).collect()
+}
+
+/// The plain text of the lines of the HTML
+pub fn to_plain_text(html: &str) -> Vec<String> {
+    to_styled_lines(html)
+        .iter()
+        .map(|line| plain_text(line))
+        .collect()
+}
+
+/// Starts a new line, unless the last one is empty
+fn break_line(lines: &mut Vec<Vec<StyledSpan>>) {
+    if lines.last().is_some_and(|line| !line.is_empty()) {
+        lines.push(Vec::new());
+    }
+}
+
+/// Escapes the text to be written in HTML
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_html() {
+        let html = "<html><head><style>b { color: red }</style></head><body>\n\
+            <div class=\"entry\"><b>cat</b> <i>noun</i></div>\n\
+            <p>a small animal &amp; a <small>pet</small></p><!-- note -->\n\
+            <script>alert('<b>')</script>fin&eacute; &mdash; &Omega;&middot;&bogus;</body></html>";
+        assert_eq!(
+            to_plain_text(html),
+            vec!["cat noun", "a small animal & a pet", "finé — Ω·&bogus;"]
+        );
+
+        let lines = to_styled_lines(html);
+        assert_eq!(lines[0][0].text, "cat");
+        assert!(lines[0][0].style.bold);
+        assert_eq!(lines[0][1].text, " ");
+        assert!(lines[0][2].style.italic);
+        assert!(lines[1].last().unwrap().style.dim);
+    }
+
+    #[test]
+    fn render_elements() {
+        let html = "<ul><li>to eat<ol><li>food</li><li style=\"display: none\">hidden</li>\
+            <li>meals</li></ol></li><li><a href=\"entry://run\">to run</a></li></ul>\
+            <ruby>食<rp>(</rp><rt>た</rt><rp>)</rp></ruby>べる\
+            <table><tr><th>form</th><th>reading</th></tr><tr><td>食べた</td><td>たべた</td></tr>\
+            </table><span style=\"font-weight: 700; font-size: small\">note</span>";
+        assert_eq!(
+            to_plain_text(html),
+            vec![
+                "• to eat",
+                "  1. food",
+                "  2. meals",
+                "• to run",
+                "食(た)べる",
+                "form | reading",
+                "食べた | たべた",
+                "note"
+            ]
+        );
+
+        let lines = to_styled_lines(html);
+        assert!(lines[3][1].style.underlined);
+        assert!(lines[4][1].style.dim);
+        assert!(lines[5][0].style.bold);
+        assert!(lines[5][1].style.dim);
+        assert!(!lines[6][0].style.bold);
+        assert_eq!(
+            lines[7][0].style,
+            TextStyle {
+                bold: true,
+                dim: true,
+                ..Default::default()
+            }
+        );
+
+        let lines = to_lines("<b>cat</b> <u>noun</u>");
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].spans[0].content, "cat");
+        assert!(lines[0].spans[0]
+            .style
+            .add_modifier
+            .contains(Modifier::BOLD));
+        assert!(lines[0].spans[2]
+            .style
+            .add_modifier
+            .contains(Modifier::UNDERLINED));
+    }
+
+    #[test]
+    fn parse_tags() {
+        assert_eq!(
+            parse_tag("a href=\"entry://a&amp;b\" class='x y' hidden"),
+            (
+                "a".to_string(),
+                vec![
+                    ("href".to_string(), "entry://a&b".to_string()),
+                    ("class".to_string(), "x y".to_string()),
+                    ("hidden".to_string(), String::new()),
+                ]
+            )
+        );
+        assert_eq!(
+            decode_entities("&#233;&#x4e00;&lt;&bogus; &"),
+            "é一<&bogus; &"
+        );
+    }
+}
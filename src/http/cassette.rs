@@ -0,0 +1,175 @@
+//! Records responses to a file, and replays them without the network
+//!
+//! A cassette is a JSON array of the interactions, matched by their URL.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use wdym::http::cassette::CassetteTransport;
+//! use wdym::http::{HttpClient, HttpConfig};
+//!
+//! let transport = CassetteTransport::replay("tests/cassettes/google_translate/simple_lookup.json")
+//!     .unwrap();
+//! let client = HttpClient::with_transport(HttpConfig::default(), Arc::new(transport));
+//! ```
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use super::{HttpConfig, ReqwestTransport, Response, Transport};
+
+/// Set this environment variable to record the cassettes again from the network
+pub const RECORD_ENV: &str = "WDYM_RECORD_CASSETTES";
+
+/// A request and its recorded response
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Interaction {
+    url: String,
+    status: u16,
+    content_type: Option<String>,
+    /// The body as text, so binary bodies can't be recorded
+    body: String,
+}
+
+impl Interaction {
+    fn to_response(&self) -> Result<Response> {
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = &self.content_type {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+        }
+        Ok(Response {
+            status: StatusCode::from_u16(self.status)?,
+            url: Url::parse(&self.url)?,
+            headers,
+            body: self.body.clone().into_bytes(),
+        })
+    }
+}
+
+/// A transport answering from a cassette file
+#[derive(Debug)]
+pub struct CassetteTransport {
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+    /// Sends the requests when recording
+    recorder: Option<Arc<dyn Transport>>,
+}
+
+impl CassetteTransport {
+    /// Answers every request from the cassette at `path`
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path.as_ref())?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            interactions: Mutex::new(serde_json::from_reader(std::io::BufReader::new(file))?),
+            recorder: None,
+        })
+    }
+
+    /// Sends every request through `transport`, and saves the responses to a new cassette at
+    /// `path`
+    pub fn record(path: impl AsRef<Path>, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            interactions: Mutex::new(Vec::new()),
+            recorder: Some(transport),
+        }
+    }
+
+    /// Records from the network if `RECORD_ENV` is set, and replays otherwise
+    pub fn replay_or_record(path: impl AsRef<Path>) -> Result<Self> {
+        if std::env::var_os(RECORD_ENV).is_some() {
+            let transport = ReqwestTransport::new(&HttpConfig::default())?;
+            Ok(Self::record(path, Arc::new(transport)))
+        } else {
+            Self::replay(path)
+        }
+    }
+
+    fn save(&self, interactions: &[Interaction]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut json = serde_json::to_string_pretty(interactions)?;
+        json.push('\n');
+        std::fs::write(&self.path, json)?;
+        Ok(())
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn get(&self, url: &Url) -> Result<Response> {
+        let mut interactions = self.interactions.lock().expect("Lock poisoned");
+
+        let Some(recorder) = &self.recorder else {
+            return interactions
+                .iter()
+                .find(|interaction| interaction.url == url.as_str())
+                .ok_or_else(|| CassetteMissError(url.to_string()))?
+                .to_response();
+        };
+
+        let response = recorder.get(url)?;
+        interactions.push(Interaction {
+            url: url.to_string(),
+            status: response.status.as_u16(),
+            content_type: response.content_type().map(str::to_string),
+            body: response.text().into_owned(),
+        });
+        self.save(&interactions)?;
+        Ok(response)
+    }
+}
+
+/// The cassette has no response for the URL
+#[derive(Debug)]
+pub struct CassetteMissError(String);
+
+impl std::error::Error for CassetteMissError {}
+
+impl std::fmt::Display for CassetteMissError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no recorded response for url ({})", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_and_replay() {
+        #[derive(Debug)]
+        struct EchoTransport;
+
+        impl Transport for EchoTransport {
+            fn get(&self, url: &Url) -> Result<Response> {
+                Ok(Response {
+                    status: StatusCode::OK,
+                    url: url.clone(),
+                    headers: HeaderMap::new(),
+                    body: url.query().unwrap_or_default().as_bytes().to_vec(),
+                })
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("wdym-cassette-{}.json", std::process::id()));
+        let url = Url::parse("https://example.com/?q=book").unwrap();
+
+        let recorder = CassetteTransport::record(&path, Arc::new(EchoTransport));
+        assert_eq!(recorder.get(&url).unwrap().text(), "q=book");
+
+        let player = CassetteTransport::replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let response = player.get(&url).unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.text(), "q=book");
+        assert!(player
+            .get(&Url::parse("https://example.com/?q=pen").unwrap())
+            .is_err());
+    }
+}
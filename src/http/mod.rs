@@ -0,0 +1,478 @@
+//! The HTTP client shared by the search providers
+//!
+//! # Examples
+//! ```rust
+//! use std::time::Duration;
+//! use wdym::http::{HttpClient, HttpConfig};
+//!
+//! let client = HttpClient::new(HttpConfig {
+//!     proxy: Some("socks5h://127.0.0.1:1080".to_string()),
+//!     connect_timeout: Duration::from_secs(3),
+//!     ..Default::default()
+//! })
+//! .unwrap();
+//! ```
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use reqwest::blocking::Client;
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+use crate::translators::{RateLimitedError, SearchProvider};
+
+pub mod cassette;
+
+/// A response read to the end
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: StatusCode,
+    /// The URL of the response, after redirects
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// The body, which may be binary, like audio
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Fails with a `HttpStatusError` if the status is 4xx or 5xx
+    pub fn error_for_status(self) -> Result<Self> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            return Err(HttpStatusError {
+                status: self.status,
+                url: self.url,
+            }
+            .into());
+        }
+        Ok(self)
+    }
+
+    /// The body as text, replacing what isn't UTF-8
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    /// Parses the body as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(reqwest::header::CONTENT_TYPE)?
+            .to_str()
+            .ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpStatusError {
+    pub status: StatusCode,
+    pub url: Url,
+}
+
+impl std::error::Error for HttpStatusError {}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP status {} for url ({})", self.status, self.url)
+    }
+}
+
+/// Sends the requests of a `HttpClient`
+///
+/// Implemented by `ReqwestTransport` for the network, and by `cassette::CassetteTransport` to
+/// replay recorded responses in tests.
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    /// Sends a GET request. Failing to get a response at all is an error, a 4xx or 5xx status is
+    /// not.
+    fn get(&self, url: &Url) -> Result<Response>;
+}
+
+/// Sends requests over the network with `reqwest`
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(config: &HttpConfig) -> Result<Self> {
+        let mut builder = Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .user_agent(&config.user_agent);
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+        })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn get(&self, url: &Url) -> Result<Response> {
+        let response = self.client.get(url.clone()).send()?;
+        Ok(Response {
+            status: response.status(),
+            url: response.url().clone(),
+            headers: response.headers().clone(),
+            body: response.bytes()?.to_vec(),
+        })
+    }
+}
+
+/// Options of the HTTP client
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// Time to wait for a connection to the server
+    pub connect_timeout: Duration,
+    /// Time to wait for the whole response, including the connection
+    pub timeout: Duration,
+    /// HTTP, HTTPS or SOCKS proxy, e.g. `socks5h://127.0.0.1:1080`. Without it, the proxy is read
+    /// from the environment variables.
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    /// How many times a request is retried after a 429, a 5xx or a connection error
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every following retry
+    pub retry_delay: Duration,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+            proxy: None,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string(),
+            max_retries: 3,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// How fast requests may be sent, as a token bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// How many requests may be sent at once after being idle
+    burst: u32,
+    /// How many requests may be sent every second in the long run
+    per_second: f64,
+}
+
+impl RateLimit {
+    /// No limit, for the providers looked up offline
+    pub const UNLIMITED: RateLimit = RateLimit::new(u32::MAX, f64::INFINITY);
+
+    /// Panics if no request could ever be sent: without a burst, or a positive rate
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        assert!(burst >= 1, "the burst of a rate limit should be at least 1");
+        assert!(
+            per_second > 0.0,
+            "the rate of a rate limit should be positive"
+        );
+        Self { burst, per_second }
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+}
+
+/// A token bucket, refilled at `per_second` tokens a second up to `burst` tokens
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    /// Tokens left, and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new((limit.burst as f64, Instant::now())),
+        }
+    }
+
+    /// Takes a token, waiting until one is available
+    pub fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().expect("Lock poisoned");
+                let now = Instant::now();
+                let (tokens, last) = *state;
+                let tokens = (tokens
+                    + now.duration_since(last).as_secs_f64() * self.limit.per_second)
+                    .min(self.limit.burst as f64);
+                if tokens >= 1.0 {
+                    *state = (tokens - 1.0, now);
+                    return;
+                }
+                *state = (tokens, now);
+                Duration::from_secs_f64((1.0 - tokens) / self.limit.per_second)
+            };
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// A HTTP client that reuses its connections, and retries failed requests
+///
+/// Requests are rate limited per provider. It is cheap to clone, and the clones share the
+/// connection pool and the rate limits.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    transport: Arc<dyn Transport>,
+    config: HttpConfig,
+    limiters: Arc<Mutex<HashMap<SearchProvider, Arc<RateLimiter>>>>,
+}
+
+impl HttpClient {
+    pub fn new(config: HttpConfig) -> Result<Self> {
+        let transport = ReqwestTransport::new(&config)?;
+        Ok(Self::with_transport(config, Arc::new(transport)))
+    }
+
+    /// Creates a client sending its requests through `transport`
+    ///
+    /// The connection options of `config` are up to the transport, only the retries are used.
+    pub fn with_transport(config: HttpConfig, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
+            config,
+            limiters: Default::default(),
+        }
+    }
+
+    /// The client of the default config, shared by the lookups that aren't given one
+    ///
+    /// Looking up many queries through it, or through clones of it, keeps to the rate limits of
+    /// the providers.
+    pub fn shared() -> &'static HttpClient {
+        &DEFAULT_CLIENT
+    }
+
+    pub fn config(&self) -> &HttpConfig {
+        &self.config
+    }
+
+    /// The rate limiter of the provider, shared by all clones of the client
+    pub fn limiter(&self, provider: SearchProvider) -> Arc<RateLimiter> {
+        let mut limiters = self.limiters.lock().expect("Lock poisoned");
+        Arc::clone(
+            limiters
+                .entry(provider)
+                .or_insert_with(|| Arc::new(RateLimiter::new(provider.rate_limit()))),
+        )
+    }
+
+    /// Sends a GET request to the provider, retrying with exponential backoff
+    ///
+    /// Waits for the `Retry-After` of the response between retries, up to `MAX_RETRY_AFTER`.
+    /// Fails with a `RateLimitedError` if the provider still answers 429 once the retries run
+    /// out. Other responses are returned as is, so the status should be checked by the caller.
+    pub fn get(&self, provider: SearchProvider, url: Url) -> Result<Response> {
+        let limiter = self.limiter(provider);
+        let mut attempt: u32 = 0;
+        loop {
+            limiter.acquire();
+            let res = self.transport.get(&url);
+            let retry_after = match &res {
+                Ok(response) if is_retryable(response.status) => retry_after(response),
+                Err(err) if is_connection_error(err) => None,
+                _ => return res,
+            };
+            if attempt >= self.config.max_retries {
+                let response = res?;
+                if response.status == StatusCode::TOO_MANY_REQUESTS {
+                    return Err(RateLimitedError {
+                        provider,
+                        retry_after,
+                    }
+                    .into());
+                }
+                return Ok(response);
+            }
+
+            thread::sleep(
+                retry_after
+                    .unwrap_or_else(|| backoff(self.config.retry_delay, attempt))
+                    .min(MAX_RETRY_AFTER),
+            );
+            attempt += 1;
+        }
+    }
+}
+
+static DEFAULT_CLIENT: LazyLock<HttpClient> = LazyLock::new(HttpClient::default);
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(HttpConfig::default()).expect("The default config should be valid")
+    }
+}
+
+/// Longest wait before a retry, so that a server can't stall a lookup for hours
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The delay before the retry after `attempt` failures, doubling every time up to
+/// `MAX_RETRY_AFTER`
+fn backoff(retry_delay: Duration, attempt: u32) -> Duration {
+    2u32.checked_pow(attempt)
+        .and_then(|factor| retry_delay.checked_mul(factor))
+        .map_or(MAX_RETRY_AFTER, |delay| delay.min(MAX_RETRY_AFTER))
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn is_connection_error(err: &color_eyre::Report) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|err| err.is_connect() || err.is_timeout())
+}
+
+/// Reads the `Retry-After` header, if it is given in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_proxy() {
+        let config = HttpConfig {
+            proxy: Some("not a proxy".to_string()),
+            ..Default::default()
+        };
+        assert!(HttpClient::new(config).is_err());
+    }
+
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::new(RateLimit::new(2, 20.0));
+        let start = Instant::now();
+        limiter.acquire();
+        limiter.acquire();
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        // The bucket is empty, so the next tokens take 50ms each
+        limiter.acquire();
+        limiter.acquire();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_rate_limit() {
+        RateLimit::new(5, 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_rate_limit() {
+        RateLimit::new(5, f64::NAN);
+    }
+
+    #[test]
+    fn limiters_are_shared_between_clones() {
+        let client = HttpClient::default();
+        let clone = client.clone();
+        assert!(Arc::ptr_eq(
+            &client.limiter(SearchProvider::GoogleTranslate),
+            &clone.limiter(SearchProvider::GoogleTranslate)
+        ));
+    }
+
+    #[test]
+    fn shared_client_keeps_its_limiters() {
+        assert!(Arc::ptr_eq(
+            &HttpClient::shared().limiter(SearchProvider::Jisho),
+            &HttpClient::shared().limiter(SearchProvider::Jisho)
+        ));
+    }
+
+    #[test]
+    fn rate_limited_after_retries() {
+        /// Answers every request with a 429, counting them
+        #[derive(Debug, Default)]
+        struct TooManyRequests(Mutex<u32>);
+
+        impl Transport for TooManyRequests {
+            fn get(&self, url: &Url) -> Result<Response> {
+                *self.0.lock().unwrap() += 1;
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    reqwest::header::RETRY_AFTER,
+                    reqwest::header::HeaderValue::from_static("0"),
+                );
+                Ok(Response {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    url: url.clone(),
+                    headers,
+                    body: Vec::new(),
+                })
+            }
+        }
+
+        let transport = Arc::new(TooManyRequests::default());
+        let client = HttpClient::with_transport(
+            HttpConfig {
+                max_retries: 2,
+                ..Default::default()
+            },
+            transport.clone(),
+        );
+        let err = client
+            .get(
+                SearchProvider::Jisho,
+                Url::parse("https://jisho.org/api/v1/search/words?keyword=cat").unwrap(),
+            )
+            .unwrap_err();
+        let err = err.downcast_ref::<RateLimitedError>().unwrap();
+        assert_eq!(err.provider, SearchProvider::Jisho);
+        assert_eq!(err.retry_after, Some(Duration::ZERO));
+        assert_eq!(*transport.0.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let delay = Duration::from_millis(500);
+        assert_eq!(backoff(delay, 0), delay);
+        assert_eq!(backoff(delay, 3), Duration::from_secs(4));
+        assert_eq!(backoff(delay, 10), MAX_RETRY_AFTER);
+        assert_eq!(backoff(delay, 32), MAX_RETRY_AFTER);
+        assert_eq!(backoff(delay, u32::MAX), MAX_RETRY_AFTER);
+        assert_eq!(backoff(Duration::MAX, 1), MAX_RETRY_AFTER);
+    }
+
+    #[test]
+    fn retryable_status() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+        assert!(!is_retryable(StatusCode::OK));
+    }
+}
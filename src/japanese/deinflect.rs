@@ -0,0 +1,456 @@
+//! Rule based deinflection of Japanese verbs and adjectives
+//!
+//! Modeled after the deinflection rules of Yomichan: every rule replaces an inflected suffix with
+//! the suffix of a less inflected form. The rules chain through the word type of the form they
+//! produce, so 食べられなかった becomes 食べる through past, negative, then potential or passive.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::japanese::deinflect::{deinflect, WordTypes};
+//!
+//! let candidates = deinflect("食べられなかった");
+//! let taberu = candidates
+//!     .iter()
+//!     .find(|candidate| candidate.term == "食べる" && candidate.word_types == WordTypes::V1)
+//!     .unwrap();
+//! assert_eq!(taberu.reasons, vec!["potential or passive", "negative", "past"]);
+//! ```
+use std::sync::LazyLock;
+
+/// Word types a form can conjugate as, as bit flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WordTypes(u8);
+
+impl WordTypes {
+    /// The word type isn't known yet, it can be any
+    pub const ANY: WordTypes = WordTypes(0);
+    /// Ichidan verbs, like 食べる
+    pub const V1: WordTypes = WordTypes(1);
+    /// Godan verbs, like 書く
+    pub const V5: WordTypes = WordTypes(1 << 1);
+    /// する, and the nouns conjugating with it
+    pub const VS: WordTypes = WordTypes(1 << 2);
+    /// 来る
+    pub const VK: WordTypes = WordTypes(1 << 3);
+    /// い-adjectives, like 高い
+    pub const ADJ_I: WordTypes = WordTypes(1 << 4);
+    /// Forms which can't be inflected further, like the past
+    const FINAL: WordTypes = WordTypes(0);
+
+    pub fn intersects(&self, other: WordTypes) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl std::ops::BitOr for WordTypes {
+    type Output = WordTypes;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        WordTypes(self.0 | rhs.0)
+    }
+}
+
+/// A possible less inflected form of the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deinflection {
+    pub term: String,
+    /// What the term has to be for the deinflection to be right, `ANY` for the original text
+    pub word_types: WordTypes,
+    /// The inflections applied to `term` to get the original text, innermost first
+    pub reasons: Vec<&'static str>,
+}
+
+impl Deinflection {
+    /// Whether the term can be a dictionary form of its word types
+    ///
+    /// Ichidan verbs end in る after a kana of the i or e row or a kanji, godan verbs in a kana of
+    /// the u row, and い-adjectives in い: 高かっる can't be the ichidan verb of 高かった.
+    pub fn is_well_formed(&self) -> bool {
+        if self.word_types == WordTypes::ANY {
+            return true;
+        }
+        let mut chars = self.term.chars().rev();
+        let (Some(last), before) = (chars.next(), chars.next()) else {
+            return false;
+        };
+        let before_ru = before.is_some_and(|c| {
+            super::kanji::is_kanji(c)
+                || "いきぎしじちぢにひびぴみりえけげせぜてでねへべぺめれ".contains(c)
+        });
+        (self.word_types.intersects(WordTypes::V1) && last == 'る' && before_ru)
+            || (self.word_types.intersects(WordTypes::V5) && "うくぐすつぬぶむる".contains(last))
+            || (self.word_types.intersects(WordTypes::VS) && self.term.ends_with("する"))
+            || (self.word_types.intersects(WordTypes::VK)
+                && (self.term.ends_with("くる") || self.term.ends_with("来る")))
+            || (self.word_types.intersects(WordTypes::ADJ_I) && last == 'い')
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    inflected: String,
+    base: String,
+    /// What the inflected form conjugates as
+    rules_in: WordTypes,
+    /// What the base form conjugates as
+    rules_out: WordTypes,
+    reason: &'static str,
+}
+
+/// More candidates are most likely nonsense
+const MAX_CANDIDATES: usize = 256;
+
+/// Lists the possible dictionary forms of the text, starting with the text itself
+///
+/// Candidates are in breadth first order, so the ones with fewer inflections come first. Most of
+/// them are not real words, and should be checked against a dictionary.
+pub fn deinflect(text: &str) -> Vec<Deinflection> {
+    let mut candidates = vec![Deinflection {
+        term: text.to_string(),
+        word_types: WordTypes::ANY,
+        reasons: Vec::new(),
+    }];
+
+    let mut i = 0;
+    while i < candidates.len() && candidates.len() < MAX_CANDIDATES {
+        let current = candidates[i].clone();
+        for rule in RULES.iter() {
+            if current.word_types != WordTypes::ANY && !current.word_types.intersects(rule.rules_in)
+            {
+                continue;
+            }
+            let Some(stem) = current.term.strip_suffix(rule.inflected.as_str()) else {
+                continue;
+            };
+            let term = format!("{}{}", stem, rule.base);
+            if term.is_empty()
+                || candidates.iter().any(|candidate| {
+                    candidate.term == term && candidate.word_types == rule.rules_out
+                })
+            {
+                continue;
+            }
+
+            let mut reasons = vec![rule.reason];
+            reasons.extend(&current.reasons);
+            candidates.push(Deinflection {
+                term,
+                word_types: rule.rules_out,
+                reasons,
+            });
+        }
+        i += 1;
+    }
+
+    candidates
+}
+
+/// An inflected suffix, what it conjugates as, and the name of the inflection
+type Form = (&'static str, WordTypes, &'static str);
+
+static RULES: LazyLock<Vec<Rule>> = LazyLock::new(build_rules);
+
+/// Godan endings, with their a, i, e and o rows, and their te and past forms
+const GODAN: &[(&str, &str, &str, &str, &str, &str, &str)] = &[
+    ("う", "わ", "い", "え", "お", "って", "った"),
+    ("く", "か", "き", "け", "こ", "いて", "いた"),
+    ("ぐ", "が", "ぎ", "げ", "ご", "いで", "いだ"),
+    ("す", "さ", "し", "せ", "そ", "して", "した"),
+    ("つ", "た", "ち", "て", "と", "って", "った"),
+    ("ぬ", "な", "に", "ね", "の", "んで", "んだ"),
+    ("ぶ", "ば", "び", "べ", "ぼ", "んで", "んだ"),
+    ("む", "ま", "み", "め", "も", "んで", "んだ"),
+    ("る", "ら", "り", "れ", "ろ", "って", "った"),
+];
+
+fn build_rules() -> Vec<Rule> {
+    use WordTypes as T;
+
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut rule = |inflected: &str, base: &str, rules_in, rules_out, reason| {
+        rules.push(Rule {
+            inflected: inflected.to_string(),
+            base: base.to_string(),
+            rules_in,
+            rules_out,
+            reason,
+        })
+    };
+
+    // Forms built on the te and past forms, shared by all verbs
+    let after_te: &[Form] = &[
+        ("", T::FINAL, "te"),
+        ("いる", T::V1, "progressive or perfect"),
+        ("る", T::V1, "progressive or perfect"),
+        ("しまう", T::V5, "completion"),
+        ("ください", T::FINAL, "request"),
+    ];
+    let after_past: &[(&str, &str)] = &[("", "past"), ("ら", "-tara"), ("り", "-tari")];
+
+    for &(ending, a, i, e, o, te, ta) in GODAN {
+        let v5 = T::V5;
+        rule(&format!("{}ない", a), ending, T::ADJ_I, v5, "negative");
+        rule(&format!("{}ず", a), ending, T::FINAL, v5, "-zu");
+        rule(&format!("{}れる", a), ending, T::V1, v5, "passive");
+        rule(&format!("{}せる", a), ending, T::V1, v5, "causative");
+        rule(i, ending, T::FINAL, v5, "masu stem");
+        rule(&format!("{}ます", i), ending, T::FINAL, v5, "polite");
+        rule(
+            &format!("{}ません", i),
+            ending,
+            T::FINAL,
+            v5,
+            "polite negative",
+        );
+        rule(&format!("{}ました", i), ending, T::FINAL, v5, "polite past");
+        rule(
+            &format!("{}ませんでした", i),
+            ending,
+            T::FINAL,
+            v5,
+            "polite past negative",
+        );
+        rule(
+            &format!("{}ましょう", i),
+            ending,
+            T::FINAL,
+            v5,
+            "polite volitional",
+        );
+        rule(&format!("{}たい", i), ending, T::ADJ_I, v5, "-tai");
+        rule(&format!("{}る", e), ending, T::V1, v5, "potential");
+        rule(e, ending, T::FINAL, v5, "imperative");
+        rule(&format!("{}ば", e), ending, T::FINAL, v5, "-ba");
+        rule(&format!("{}う", o), ending, T::FINAL, v5, "volitional");
+        for &(suffix, rules_in, reason) in after_te {
+            rule(&format!("{}{}", te, suffix), ending, rules_in, v5, reason);
+        }
+        for &(suffix, reason) in after_past {
+            rule(&format!("{}{}", ta, suffix), ending, T::FINAL, v5, reason);
+        }
+    }
+
+    // 行く is the only godan verb in く with a te form in って
+    for iku in ["行く", "いく"] {
+        let stem = iku.trim_end_matches('く');
+        for &(suffix, rules_in, reason) in after_te {
+            rule(
+                &format!("{}って{}", stem, suffix),
+                iku,
+                rules_in,
+                T::V5,
+                reason,
+            );
+        }
+        for &(suffix, reason) in after_past {
+            rule(
+                &format!("{}った{}", stem, suffix),
+                iku,
+                T::FINAL,
+                T::V5,
+                reason,
+            );
+        }
+    }
+
+    // Ichidan verbs conjugate on the stem without る
+    let v1 = T::V1;
+    rule("ない", "る", T::ADJ_I, v1, "negative");
+    rule("ず", "る", T::FINAL, v1, "-zu");
+    rule("られる", "る", T::V1, v1, "potential or passive");
+    rule("させる", "る", T::V1, v1, "causative");
+    rule("ます", "る", T::FINAL, v1, "polite");
+    rule("ません", "る", T::FINAL, v1, "polite negative");
+    rule("ました", "る", T::FINAL, v1, "polite past");
+    rule("ませんでした", "る", T::FINAL, v1, "polite past negative");
+    rule("ましょう", "る", T::FINAL, v1, "polite volitional");
+    rule("たい", "る", T::ADJ_I, v1, "-tai");
+    rule("ろ", "る", T::FINAL, v1, "imperative");
+    rule("よ", "る", T::FINAL, v1, "imperative");
+    rule("れば", "る", T::FINAL, v1, "-ba");
+    rule("よう", "る", T::FINAL, v1, "volitional");
+    for &(suffix, rules_in, reason) in after_te {
+        rule(&format!("て{}", suffix), "る", rules_in, v1, reason);
+    }
+    for &(suffix, reason) in after_past {
+        rule(&format!("た{}", suffix), "る", T::FINAL, v1, reason);
+    }
+
+    // する and 来る are irregular. 来る is written in kana or with its kanji.
+    let irregular: &[(&str, WordTypes, &[Form])] = &[
+        (
+            "する",
+            T::VS,
+            &[
+                ("しない", T::ADJ_I, "negative"),
+                ("せず", T::FINAL, "-zu"),
+                ("される", T::V1, "passive"),
+                ("させる", T::V1, "causative"),
+                ("できる", T::V1, "potential"),
+                ("します", T::FINAL, "polite"),
+                ("しません", T::FINAL, "polite negative"),
+                ("しました", T::FINAL, "polite past"),
+                ("しませんでした", T::FINAL, "polite past negative"),
+                ("しましょう", T::FINAL, "polite volitional"),
+                ("したい", T::ADJ_I, "-tai"),
+                ("しろ", T::FINAL, "imperative"),
+                ("せよ", T::FINAL, "imperative"),
+                ("すれば", T::FINAL, "-ba"),
+                ("しよう", T::FINAL, "volitional"),
+            ],
+        ),
+        (
+            "くる",
+            T::VK,
+            &[
+                ("こない", T::ADJ_I, "negative"),
+                ("こず", T::FINAL, "-zu"),
+                ("こられる", T::V1, "potential or passive"),
+                ("こさせる", T::V1, "causative"),
+                ("きます", T::FINAL, "polite"),
+                ("きません", T::FINAL, "polite negative"),
+                ("きました", T::FINAL, "polite past"),
+                ("きませんでした", T::FINAL, "polite past negative"),
+                ("きましょう", T::FINAL, "polite volitional"),
+                ("きたい", T::ADJ_I, "-tai"),
+                ("こい", T::FINAL, "imperative"),
+                ("くれば", T::FINAL, "-ba"),
+                ("こよう", T::FINAL, "volitional"),
+            ],
+        ),
+    ];
+    for &(base, word_type, forms) in irregular {
+        // The te and past forms of する and 来る are し and き, followed by the usual suffixes
+        let stem = if base == "する" { "し" } else { "き" };
+        let te_forms = after_te
+            .iter()
+            .map(|&(suffix, rules_in, reason)| (format!("{}て{}", stem, suffix), rules_in, reason));
+        let past_forms = after_past
+            .iter()
+            .map(|&(suffix, reason)| (format!("{}た{}", stem, suffix), T::FINAL, reason));
+        let forms: Vec<(String, WordTypes, &str)> = forms
+            .iter()
+            .map(|&(form, rules_in, reason)| (form.to_string(), rules_in, reason))
+            .chain(te_forms)
+            .chain(past_forms)
+            .collect();
+
+        for (form, rules_in, reason) in forms {
+            rule(&form, base, rules_in, word_type, reason);
+            if base == "くる" {
+                // 来ない, 来ます: the kanji replaces the first kana
+                let mut chars = form.chars();
+                chars.next();
+                rule(
+                    &format!("来{}", chars.as_str()),
+                    "来る",
+                    rules_in,
+                    word_type,
+                    reason,
+                );
+            }
+        }
+    }
+
+    // い-adjectives
+    let adj = T::ADJ_I;
+    rule("くない", "い", T::ADJ_I, adj, "negative");
+    rule("かった", "い", T::FINAL, adj, "past");
+    rule("かったら", "い", T::FINAL, adj, "-tara");
+    rule("かったり", "い", T::FINAL, adj, "-tari");
+    rule("く", "い", T::FINAL, adj, "adverb");
+    rule("くて", "い", T::FINAL, adj, "te");
+    rule("ければ", "い", T::FINAL, adj, "-ba");
+    rule("さ", "い", T::FINAL, adj, "noun");
+    rule("そう", "い", T::FINAL, adj, "-sou");
+    rule("すぎる", "い", T::V1, adj, "-sugiru");
+
+    rules
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Finds the candidate with the term and word type
+    fn find(text: &str, term: &str, word_types: WordTypes) -> Option<Vec<&'static str>> {
+        deinflect(text)
+            .into_iter()
+            .find(|candidate| candidate.term == term && candidate.word_types == word_types)
+            .map(|candidate| candidate.reasons)
+    }
+
+    #[test]
+    fn original_comes_first() {
+        let candidates = deinflect("本");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].term, "本");
+        assert!(candidates[0].reasons.is_empty());
+    }
+
+    #[test]
+    fn verbs() {
+        assert_eq!(
+            find("食べられなかった", "食べる", WordTypes::V1).unwrap(),
+            vec!["potential or passive", "negative", "past"]
+        );
+        assert_eq!(
+            find("書きました", "書く", WordTypes::V5).unwrap(),
+            vec!["polite past"]
+        );
+        assert_eq!(
+            find("読んでいます", "読む", WordTypes::V5).unwrap(),
+            vec!["progressive or perfect", "polite"]
+        );
+        assert_eq!(find("行った", "行く", WordTypes::V5).unwrap(), vec!["past"]);
+        assert_eq!(
+            find("泳がせられない", "泳ぐ", WordTypes::V5).unwrap(),
+            vec!["causative", "potential or passive", "negative"]
+        );
+    }
+
+    #[test]
+    fn irregular_verbs() {
+        assert_eq!(
+            find("勉強しなかった", "勉強する", WordTypes::VS).unwrap(),
+            vec!["negative", "past"]
+        );
+        assert_eq!(
+            find("来られます", "来る", WordTypes::VK).unwrap(),
+            vec!["potential or passive", "polite"]
+        );
+        assert_eq!(find("きた", "くる", WordTypes::VK).unwrap(), vec!["past"]);
+    }
+
+    #[test]
+    fn adjectives() {
+        assert_eq!(
+            find("高くなかった", "高い", WordTypes::ADJ_I).unwrap(),
+            vec!["negative", "past"]
+        );
+        assert_eq!(
+            find("食べたくない", "食べる", WordTypes::V1).unwrap(),
+            vec!["-tai", "negative"]
+        );
+    }
+
+    #[test]
+    fn well_formed_terms() {
+        let candidates = deinflect("高くなかった");
+        let well_formed: Vec<&str> = candidates
+            .iter()
+            .filter(|candidate| candidate.is_well_formed())
+            .map(|candidate| candidate.term.as_str())
+            .collect();
+        assert!(well_formed.contains(&"高い"));
+        assert!(well_formed.contains(&"高くなかった"));
+        assert!(!well_formed.contains(&"高くなかっる"));
+        assert!(!well_formed.contains(&"高くる"));
+    }
+
+    #[test]
+    fn final_forms_do_not_chain() {
+        // A past form can't be made negative
+        assert!(find("食べたない", "食べる", WordTypes::V1).is_none());
+    }
+}
@@ -0,0 +1,327 @@
+//! Aligns the reading of Japanese text to its kanji, to write it as furigana
+//!
+//! The kana of the text anchor the reading, and the kana between them are the reading of the
+//! kanji runs: 食べ物 read たべもの is 食(た)べ物(もの).
+//!
+//! # Examples
+//! ```rust
+//! use wdym::japanese::furigana::{align, inline, ruby};
+//!
+//! let segments = align("食べ物", "たべもの").unwrap();
+//! assert_eq!(inline(&segments), "食[た]べ物[もの]");
+//!
+//! let segments = align("計算", "けいさん").unwrap();
+//! assert_eq!(ruby(&segments), ("けいさん".to_string(), "  計算  ".to_string()));
+//! ```
+use super::{kana, kanji};
+
+/// A run of the text, with the reading of its kanji
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub text: String,
+    /// Only set on kanji runs
+    pub reading: Option<String>,
+}
+
+/// What a character is, for splitting the text into runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Kanji,
+    Kana,
+    /// Punctuation and spaces, which aren't read
+    Silent,
+    /// Anything else, read as written
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    match c {
+        _ if kanji::is_kanji(c) => CharClass::Kanji,
+        _ if kana::is_kana(c.encode_utf8(&mut [0; 4])) => CharClass::Kana,
+        _ if c.is_whitespace() || (!c.is_alphanumeric() && c != '\'') => CharClass::Silent,
+        _ => CharClass::Other,
+    }
+}
+
+/// Longest text aligned, which bounds the table of the alignments tried
+const MAX_TEXT_LEN: usize = 256;
+
+/// Aligns the reading to the kanji of the text
+///
+/// The reading is in kana, and its whitespace is ignored. Returns `None` if the text has no
+/// kanji, or if the reading doesn't fit the kana of the text.
+pub fn align(text: &str, reading: &str) -> Option<Vec<Segment>> {
+    if text.chars().count() > MAX_TEXT_LEN {
+        return None;
+    }
+    let mut runs: Vec<(CharClass, String)> = Vec::new();
+    for c in text.chars() {
+        let class = char_class(c);
+        match runs.last_mut() {
+            Some((last, run)) if *last == class => run.push(c),
+            _ => runs.push((class, c.to_string())),
+        }
+    }
+    if !runs.iter().any(|(class, _)| *class == CharClass::Kanji) {
+        return None;
+    }
+
+    let reading: Vec<char> = kana::to_hiragana(reading)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let mut aligner = Aligner {
+        runs: &runs,
+        reading: &reading,
+        failed: vec![false; (runs.len() + 1) * (reading.len() + 1)],
+        readings: Vec::new(),
+    };
+    if !aligner.align(0, 0) {
+        return None;
+    }
+    let readings = aligner.readings;
+
+    let mut readings = readings.into_iter();
+    Some(
+        runs.into_iter()
+            .map(|(class, text)| Segment {
+                text,
+                reading: (class == CharClass::Kanji)
+                    .then(|| readings.next())
+                    .flatten(),
+            })
+            .collect(),
+    )
+}
+
+/// Matches the runs against the reading, remembering the positions that can't be aligned
+///
+/// Each run and position of the reading is only tried once, so the alignment takes polynomial
+/// time even when the kanji runs can be read in many ways.
+struct Aligner<'a> {
+    runs: &'a [(CharClass, String)],
+    reading: &'a [char],
+    /// Whether the runs from a run can't be aligned to the reading from a position, indexed by
+    /// `run * (reading.len() + 1) + position`
+    failed: Vec<bool>,
+    /// The reading of every kanji run aligned so far
+    readings: Vec<String>,
+}
+
+impl Aligner<'_> {
+    /// Aligns the runs from `run` to the reading from `position`, pushing the reading of every
+    /// kanji run
+    fn align(&mut self, run: usize, position: usize) -> bool {
+        let index = run * (self.reading.len() + 1) + position;
+        if self.failed[index] {
+            return false;
+        }
+        let aligned = self.align_run(run, position);
+        if !aligned {
+            self.failed[index] = true;
+        }
+        aligned
+    }
+
+    fn align_run(&mut self, run: usize, position: usize) -> bool {
+        let reading = &self.reading[position..];
+        let Some((class, text)) = self.runs.get(run) else {
+            return reading.is_empty();
+        };
+        match class {
+            CharClass::Silent => {
+                // The reading may or may not keep the punctuation
+                let written: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+                (reading.starts_with(&written) && self.align(run + 1, position + written.len()))
+                    || self.align(run + 1, position)
+            }
+            CharClass::Kana | CharClass::Other => {
+                let written: Vec<char> = kana::to_hiragana(text).chars().collect();
+                written.len() <= reading.len()
+                    && written
+                        .iter()
+                        .zip(reading)
+                        .all(|(written, read)| same_sound(*written, *read))
+                    && self.align(run + 1, position + written.len())
+            }
+            CharClass::Kanji => {
+                // Every kanji is read with at least one kana, and only kana
+                let kana_len = reading
+                    .iter()
+                    .take_while(|c| kana::is_kana(c.encode_utf8(&mut [0; 4])))
+                    .count();
+                for len in text.chars().count()..=kana_len {
+                    self.readings.push(reading[..len].iter().collect());
+                    if self.align(run + 1, position + len) {
+                        return true;
+                    }
+                    self.readings.pop();
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Whether the kana of the text is read as the kana of the reading
+///
+/// Particles are read differently than they are written, and the prolonged sound mark lengthens
+/// the vowel before it.
+fn same_sound(written: char, read: char) -> bool {
+    written == read
+        || written.to_lowercase().eq(read.to_lowercase())
+        || matches!((written, read), ('は', 'わ') | ('へ', 'え') | ('を', 'お'))
+        || (written == 'ー' && "あいうえお".contains(read))
+}
+
+/// Aligns the romaji reading of the text, like the transliteration of a translator
+pub fn align_romaji(text: &str, romaji: &str) -> Option<Vec<Segment>> {
+    let romaji: String = romaji
+        .chars()
+        .filter(|c| !c.is_ascii_punctuation() || *c == '\'' || *c == '-')
+        .collect();
+    align(text, &kana::romaji_to_hiragana(&romaji)?)
+}
+
+/// Writes the readings in brackets after their kanji: `計算[けいさん]`
+pub fn inline(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match &segment.reading {
+            Some(reading) => format!("{}[{}]", segment.text, reading),
+            None => segment.text.clone(),
+        })
+        .collect()
+}
+
+/// Writes the readings on a line above the text, centered on their kanji
+///
+/// Returns the line of readings and the line of text, padded with spaces to keep them aligned
+/// in a terminal, where kana and kanji are two columns wide.
+pub fn ruby(segments: &[Segment]) -> (String, String) {
+    let mut readings = String::new();
+    let mut text = String::new();
+    for segment in segments {
+        let Some(reading) = &segment.reading else {
+            readings.push_str(&" ".repeat(width(&segment.text)));
+            text.push_str(&segment.text);
+            continue;
+        };
+        let (text_width, reading_width) = (width(&segment.text), width(reading));
+        let cell = text_width.max(reading_width);
+        let pad = |line: &mut String, content: &str, content_width: usize| {
+            let before = (cell - content_width) / 2;
+            line.push_str(&" ".repeat(before));
+            line.push_str(content);
+            line.push_str(&" ".repeat(cell - content_width - before));
+        };
+        pad(&mut readings, reading, reading_width);
+        pad(&mut text, &segment.text, text_width);
+    }
+    (readings.trim_end().to_string(), text)
+}
+
+/// Columns taken by the text in a terminal
+pub fn width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match char_class(c) {
+            CharClass::Kanji | CharClass::Kana => 2,
+            // Full width punctuation, like 。 and 「
+            _ if ('\u{3000}'..='\u{303f}').contains(&c)
+                || ('\u{ff01}'..='\u{ff60}').contains(&c) =>
+            {
+                2
+            }
+            _ => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segment(text: &str, reading: Option<&str>) -> Segment {
+        Segment {
+            text: text.to_string(),
+            reading: reading.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn align_words() {
+        assert_eq!(
+            align("計算", "けいさん").unwrap(),
+            vec![segment("計算", Some("けいさん"))]
+        );
+        assert_eq!(
+            align("食べられなかった", "たべられなかった").unwrap(),
+            vec![segment("食", Some("た")), segment("べられなかった", None)]
+        );
+        assert_eq!(
+            align("お見舞い", "おみまい").unwrap(),
+            vec![
+                segment("お", None),
+                segment("見舞", Some("みま")),
+                segment("い", None)
+            ]
+        );
+        // Katakana in the text, hiragana in the reading
+        assert_eq!(
+            align("ノート型", "のーとがた").unwrap(),
+            vec![segment("ノート", None), segment("型", Some("がた"))]
+        );
+        assert_eq!(align("たべる", "たべる"), None);
+        assert_eq!(align("食べる", "のむ"), None);
+        // Kanji are only read with kana
+        assert_eq!(align("漢字", "かんじ、x"), None);
+        assert_eq!(
+            align("漢字x", "かんじx").map(|segments| inline(&segments)),
+            Some("漢字[かんじ]x".to_string())
+        );
+    }
+
+    #[test]
+    fn align_sentences() {
+        assert_eq!(
+            inline(&align_romaji("私は学生です。", "Watashi wa gakusei desu.").unwrap()),
+            "私[わたし]は学生[がくせい]です。"
+        );
+        assert_eq!(
+            inline(&align_romaji("東京へ行く", "Tōkyō e iku").unwrap()),
+            "東京[とうきょう]へ行[い]く"
+        );
+        assert_eq!(
+            inline(&align_romaji("コーヒーを飲む", "kōhī o nomu").unwrap()),
+            "コーヒーを飲[の]む"
+        );
+        assert_eq!(align_romaji("Typerは便利", "Typer wa benri"), None);
+    }
+
+    #[test]
+    fn align_long_text() {
+        // Many kanji runs that can be read in many ways, which can't be aligned in the end
+        let text = "日の".repeat(40);
+        let reading = "ひの".repeat(40) + "か";
+        let start = std::time::Instant::now();
+        assert_eq!(align(&text, &reading), None);
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        let segments = align(&text, &"ひの".repeat(40)).unwrap();
+        assert_eq!(segments.len(), 80);
+        assert_eq!(segments[0], segment("日", Some("ひ")));
+    }
+
+    #[test]
+    fn ruby_lines() {
+        let segments = align("計算する", "けいさんする").unwrap();
+        let (readings, text) = ruby(&segments);
+        assert_eq!(readings, "けいさん");
+        assert_eq!(text, "  計算  する");
+        assert_eq!(width(&readings), 8);
+
+        // A reading narrower than its kanji is centered
+        let segments = vec![segment("明日", Some("あす"))];
+        assert_eq!(ruby(&segments), ("あす".to_string(), "明日".to_string()));
+    }
+}
@@ -0,0 +1,490 @@
+//! Conversion between hiragana, katakana and romaji, without a provider
+//!
+//! Romaji is read in the Hepburn, Kunrei and Nihon-shiki systems, as well as the usual input
+//! method spellings like `xtsu` for っ, and written in Hepburn or Kunrei.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::japanese::kana::{romaji_to_hiragana, to_katakana, to_romaji, Romanization};
+//!
+//! assert_eq!(romaji_to_hiragana("taberu").as_deref(), Some("たべる"));
+//! assert_eq!(romaji_to_hiragana("book"), None);
+//! assert_eq!(to_katakana("ちーず"), "チーズ");
+//! assert_eq!(to_romaji("しんぶん", Romanization::Hepburn), "shinbun");
+//! assert_eq!(to_romaji("しんぶん", Romanization::Kunrei), "sinbun");
+//! ```
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+
+/// How kana is written in the latin alphabet
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Romanization {
+    /// Modified Hepburn: shi, tsu, ja
+    #[default]
+    Hepburn,
+    /// Kunrei-shiki: si, tu, zya
+    Kunrei,
+}
+
+impl std::fmt::Display for Romanization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("No variables should be skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// Hiragana, in Hepburn and in Kunrei
+const KANA: &[(&str, &str, &str)] = &[
+    ("あ", "a", "a"),
+    ("い", "i", "i"),
+    ("う", "u", "u"),
+    ("え", "e", "e"),
+    ("お", "o", "o"),
+    ("か", "ka", "ka"),
+    ("き", "ki", "ki"),
+    ("く", "ku", "ku"),
+    ("け", "ke", "ke"),
+    ("こ", "ko", "ko"),
+    ("が", "ga", "ga"),
+    ("ぎ", "gi", "gi"),
+    ("ぐ", "gu", "gu"),
+    ("げ", "ge", "ge"),
+    ("ご", "go", "go"),
+    ("さ", "sa", "sa"),
+    ("し", "shi", "si"),
+    ("す", "su", "su"),
+    ("せ", "se", "se"),
+    ("そ", "so", "so"),
+    ("ざ", "za", "za"),
+    ("じ", "ji", "zi"),
+    ("ず", "zu", "zu"),
+    ("ぜ", "ze", "ze"),
+    ("ぞ", "zo", "zo"),
+    ("た", "ta", "ta"),
+    ("ち", "chi", "ti"),
+    ("つ", "tsu", "tu"),
+    ("て", "te", "te"),
+    ("と", "to", "to"),
+    ("だ", "da", "da"),
+    ("ぢ", "ji", "zi"),
+    ("づ", "zu", "zu"),
+    ("で", "de", "de"),
+    ("ど", "do", "do"),
+    ("な", "na", "na"),
+    ("に", "ni", "ni"),
+    ("ぬ", "nu", "nu"),
+    ("ね", "ne", "ne"),
+    ("の", "no", "no"),
+    ("は", "ha", "ha"),
+    ("ひ", "hi", "hi"),
+    ("ふ", "fu", "hu"),
+    ("へ", "he", "he"),
+    ("ほ", "ho", "ho"),
+    ("ば", "ba", "ba"),
+    ("び", "bi", "bi"),
+    ("ぶ", "bu", "bu"),
+    ("べ", "be", "be"),
+    ("ぼ", "bo", "bo"),
+    ("ぱ", "pa", "pa"),
+    ("ぴ", "pi", "pi"),
+    ("ぷ", "pu", "pu"),
+    ("ぺ", "pe", "pe"),
+    ("ぽ", "po", "po"),
+    ("ま", "ma", "ma"),
+    ("み", "mi", "mi"),
+    ("む", "mu", "mu"),
+    ("め", "me", "me"),
+    ("も", "mo", "mo"),
+    ("や", "ya", "ya"),
+    ("ゆ", "yu", "yu"),
+    ("よ", "yo", "yo"),
+    ("ら", "ra", "ra"),
+    ("り", "ri", "ri"),
+    ("る", "ru", "ru"),
+    ("れ", "re", "re"),
+    ("ろ", "ro", "ro"),
+    ("わ", "wa", "wa"),
+    ("ゐ", "i", "i"),
+    ("ゑ", "e", "e"),
+    ("を", "o", "o"),
+    ("ん", "n", "n"),
+    ("ゔ", "vu", "vu"),
+    // Palatalized syllables
+    ("きゃ", "kya", "kya"),
+    ("きゅ", "kyu", "kyu"),
+    ("きょ", "kyo", "kyo"),
+    ("ぎゃ", "gya", "gya"),
+    ("ぎゅ", "gyu", "gyu"),
+    ("ぎょ", "gyo", "gyo"),
+    ("しゃ", "sha", "sya"),
+    ("しゅ", "shu", "syu"),
+    ("しょ", "sho", "syo"),
+    ("じゃ", "ja", "zya"),
+    ("じゅ", "ju", "zyu"),
+    ("じょ", "jo", "zyo"),
+    ("ちゃ", "cha", "tya"),
+    ("ちゅ", "chu", "tyu"),
+    ("ちょ", "cho", "tyo"),
+    ("ぢゃ", "ja", "zya"),
+    ("ぢゅ", "ju", "zyu"),
+    ("ぢょ", "jo", "zyo"),
+    ("にゃ", "nya", "nya"),
+    ("にゅ", "nyu", "nyu"),
+    ("にょ", "nyo", "nyo"),
+    ("ひゃ", "hya", "hya"),
+    ("ひゅ", "hyu", "hyu"),
+    ("ひょ", "hyo", "hyo"),
+    ("びゃ", "bya", "bya"),
+    ("びゅ", "byu", "byu"),
+    ("びょ", "byo", "byo"),
+    ("ぴゃ", "pya", "pya"),
+    ("ぴゅ", "pyu", "pyu"),
+    ("ぴょ", "pyo", "pyo"),
+    ("みゃ", "mya", "mya"),
+    ("みゅ", "myu", "myu"),
+    ("みょ", "myo", "myo"),
+    ("りゃ", "rya", "rya"),
+    ("りゅ", "ryu", "ryu"),
+    ("りょ", "ryo", "ryo"),
+    // Syllables of loanwords, which Kunrei doesn't have
+    ("しぇ", "she", "sye"),
+    ("じぇ", "je", "zye"),
+    ("ちぇ", "che", "tye"),
+    ("てぃ", "ti", "ti"),
+    ("でぃ", "di", "di"),
+    ("とぅ", "tu", "tu"),
+    ("どぅ", "du", "du"),
+    ("ふぁ", "fa", "fa"),
+    ("ふぃ", "fi", "fi"),
+    ("ふぇ", "fe", "fe"),
+    ("ふぉ", "fo", "fo"),
+    ("ゔぁ", "va", "va"),
+    ("ゔぃ", "vi", "vi"),
+    ("ゔぇ", "ve", "ve"),
+    ("ゔぉ", "vo", "vo"),
+    ("うぃ", "wi", "wi"),
+    ("うぇ", "we", "we"),
+    ("うぉ", "wo", "wo"),
+    ("つぁ", "tsa", "tsa"),
+    ("つぃ", "tsi", "tsi"),
+    ("つぇ", "tse", "tse"),
+    ("つぉ", "tso", "tso"),
+    ("いぇ", "ye", "ye"),
+    // Small kana on their own
+    ("ぁ", "a", "a"),
+    ("ぃ", "i", "i"),
+    ("ぅ", "u", "u"),
+    ("ぇ", "e", "e"),
+    ("ぉ", "o", "o"),
+    ("ゃ", "ya", "ya"),
+    ("ゅ", "yu", "yu"),
+    ("ょ", "yo", "yo"),
+    ("ゎ", "wa", "wa"),
+];
+
+/// Input spellings of neither system, read before the table
+const ROMAJI_INPUT: &[(&str, &str)] = &[
+    ("wo", "を"),
+    ("di", "ぢ"),
+    ("du", "づ"),
+    ("dya", "ぢゃ"),
+    ("dyu", "ぢゅ"),
+    ("dyo", "ぢょ"),
+    ("jya", "じゃ"),
+    ("jyu", "じゅ"),
+    ("jyo", "じょ"),
+    ("cya", "ちゃ"),
+    ("cyu", "ちゅ"),
+    ("cyo", "ちょ"),
+    ("thi", "てぃ"),
+    ("dhi", "でぃ"),
+    ("twu", "とぅ"),
+    ("xtu", "っ"),
+    ("xtsu", "っ"),
+    ("ltu", "っ"),
+    ("ltsu", "っ"),
+    ("-", "ー"),
+];
+
+/// Romaji read as hiragana
+static ROMAJI: LazyLock<HashMap<String, &'static str>> = LazyLock::new(|| {
+    let mut romaji = HashMap::new();
+    for &(input, kana) in ROMAJI_INPUT {
+        romaji.entry(input.to_string()).or_insert(kana);
+    }
+    for &(kana, hepburn, kunrei) in KANA {
+        if kana == "ん" {
+            // Read depending on the next letter
+            continue;
+        }
+        let small = ["ぁ", "ぃ", "ぅ", "ぇ", "ぉ", "ゃ", "ゅ", "ょ", "ゎ"].contains(&kana);
+        for spelling in [hepburn, kunrei] {
+            if small {
+                romaji.entry(format!("x{}", spelling)).or_insert(kana);
+                romaji.entry(format!("l{}", spelling)).or_insert(kana);
+            } else {
+                romaji.entry(spelling.to_string()).or_insert(kana);
+            }
+        }
+    }
+    romaji
+});
+
+/// Hiragana written in romaji, longest first so syllables win over their first kana
+static KANA_ROMAJI: LazyLock<Vec<(&'static str, &'static str, &'static str)>> =
+    LazyLock::new(|| {
+        let mut kana = KANA.to_vec();
+        kana.sort_by_key(|(kana, _, _)| std::cmp::Reverse(kana.chars().count()));
+        kana
+    });
+
+const LONGEST_ROMAJI: usize = 4;
+
+/// Converts katakana to hiragana, keeping other characters
+pub fn to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' | 'ヽ' | 'ヾ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Converts hiragana to katakana, keeping other characters
+pub fn to_katakana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Whether the text is only kana, ignoring the prolonged sound mark
+pub fn is_kana(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| matches!(c, 'ぁ'..='ゖ' | 'ゝ' | 'ゞ' | 'ァ'..='ヺ' | 'ー' | 'ヽ' | 'ヾ'))
+}
+
+/// Reads romaji as hiragana
+///
+/// Other characters are kept, and long vowels with a macron or a circumflex are written as two
+/// kana: `Tōkyō` is とうきょう. Returns `None` if some letters don't spell kana, which is what
+/// happens for most English words.
+pub fn romaji_to_hiragana(text: &str) -> Option<String> {
+    let text = expand_long_vowels(&text.to_lowercase());
+    let chars: Vec<char> = text.chars().collect();
+    let is_vowel = |c: Option<&char>| c.is_some_and(|c| "aiueoy".contains(*c));
+
+    let mut res = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == 'n' && !is_vowel(chars.get(i + 1)) {
+            // n', nn and n before a consonant are ん, unless nn is followed by a vowel: konnichiha
+            i += match chars.get(i + 1) {
+                Some('\'') => 2,
+                Some('n') if !is_vowel(chars.get(i + 2)) => 2,
+                _ => 1,
+            };
+            res.push('ん');
+            continue;
+        }
+        if c.is_ascii_alphabetic() && !"aiueon".contains(c) {
+            // Double consonants are っ, and so is the t of tch: matcha
+            let next = chars.get(i + 1);
+            if next == Some(&c) || (c == 't' && next == Some(&'c')) {
+                res.push('っ');
+                i += 1;
+                continue;
+            }
+        }
+
+        let matched = (1..=LONGEST_ROMAJI.min(chars.len() - i))
+            .rev()
+            .find_map(|len| {
+                let spelling: String = chars[i..i + len].iter().collect();
+                ROMAJI.get(spelling.as_str()).map(|kana| (len, *kana))
+            });
+        match matched {
+            Some((len, kana)) => {
+                res.push_str(kana);
+                i += len;
+            }
+            None if c.is_ascii_alphabetic() || c == '\'' => return None,
+            None => {
+                res.push(c);
+                i += 1;
+            }
+        }
+    }
+    Some(res)
+}
+
+/// Reads romaji as katakana, like `romaji_to_hiragana`
+pub fn romaji_to_katakana(text: &str) -> Option<String> {
+    romaji_to_hiragana(text).map(|hiragana| to_katakana(&hiragana))
+}
+
+/// Writes kana in romaji, keeping other characters
+///
+/// Long vowels written with kana are kept as written, as the romaji can't tell おう in 東京 from
+/// the one in 思う, and the prolonged sound mark ー is written with a macron in Hepburn, or a
+/// circumflex in Kunrei.
+pub fn to_romaji(text: &str, system: Romanization) -> String {
+    let hiragana = to_hiragana(text);
+    let mut res = String::new();
+    let mut rest = hiragana.as_str();
+    // Set by っ, doubles the consonant of the next syllable
+    let mut sokuon = false;
+
+    while let Some(c) = rest.chars().next() {
+        if c == 'っ' {
+            sokuon = true;
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if c == 'ー' {
+            if let Some(vowel) = res.pop() {
+                res.push(long_vowel(vowel, system));
+            }
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let Some(&(kana, hepburn, kunrei)) = KANA_ROMAJI
+            .iter()
+            .find(|(kana, _, _)| rest.starts_with(kana))
+        else {
+            if sokuon {
+                // A trailing っ is a glottal stop
+                res.push('\'');
+                sokuon = false;
+            }
+            res.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        let romaji = match system {
+            Romanization::Hepburn => hepburn,
+            Romanization::Kunrei => kunrei,
+        };
+
+        if sokuon {
+            match romaji.chars().next() {
+                // Hepburn writes っち as tchi
+                Some('c') if system == Romanization::Hepburn => res.push('t'),
+                Some(first) if !"aiueon".contains(first) => res.push(first),
+                _ => res.push('\''),
+            }
+            sokuon = false;
+        }
+        if kana == "ん"
+            && rest[kana.len()..].starts_with(['あ', 'い', 'う', 'え', 'お', 'や', 'ゆ', 'よ'])
+        {
+            // Tells んあ from な: kin'en
+            res.push_str("n'");
+        } else {
+            res.push_str(romaji);
+        }
+        rest = &rest[kana.len()..];
+    }
+    if sokuon {
+        res.push('\'');
+    }
+    res
+}
+
+/// Writes both vowels of ああ as one character with a macron or circumflex
+fn expand_long_vowels(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ā' | 'â' => "aa".to_string(),
+            'ī' | 'î' => "ii".to_string(),
+            'ū' | 'û' => "uu".to_string(),
+            'ē' | 'ê' => "ee".to_string(),
+            // おう is much more common than おお
+            'ō' | 'ô' => "ou".to_string(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+fn long_vowel(vowel: char, system: Romanization) -> char {
+    let long = match system {
+        Romanization::Hepburn => ['ā', 'ī', 'ū', 'ē', 'ō'],
+        Romanization::Kunrei => ['â', 'î', 'û', 'ê', 'ô'],
+    };
+    match "aiueo".find(vowel) {
+        Some(index) => long[index],
+        None => vowel,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kana_conversion() {
+        assert_eq!(to_katakana("こんぴゅーたー"), "コンピューター");
+        assert_eq!(to_hiragana("コンピューター"), "こんぴゅーたー");
+        assert_eq!(to_hiragana("漢字とカナ"), "漢字とかな");
+        assert!(is_kana("たべる"));
+        assert!(is_kana("コーヒー"));
+        assert!(!is_kana("食べる"));
+    }
+
+    #[test]
+    fn romaji_to_kana() {
+        assert_eq!(romaji_to_hiragana("taberu").unwrap(), "たべる");
+        assert_eq!(romaji_to_hiragana("konnichiha").unwrap(), "こんにちは");
+        assert_eq!(romaji_to_hiragana("kin'en").unwrap(), "きんえん");
+        assert_eq!(romaji_to_hiragana("shinbun").unwrap(), "しんぶん");
+        assert_eq!(romaji_to_hiragana("sinbun").unwrap(), "しんぶん");
+        assert_eq!(romaji_to_hiragana("kitte").unwrap(), "きって");
+        assert_eq!(romaji_to_hiragana("matcha").unwrap(), "まっちゃ");
+        assert_eq!(romaji_to_hiragana("zyuusyo").unwrap(), "じゅうしょ");
+        assert_eq!(romaji_to_hiragana("Tōkyō").unwrap(), "とうきょう");
+        assert_eq!(romaji_to_hiragana("hon").unwrap(), "ほん");
+        assert_eq!(romaji_to_katakana("ko-hi-").unwrap(), "コーヒー");
+        assert_eq!(romaji_to_hiragana("xtsu").unwrap(), "っ");
+        assert_eq!(romaji_to_hiragana("Book"), None);
+        assert_eq!(romaji_to_hiragana("strength"), None);
+    }
+
+    #[test]
+    fn kana_to_romaji() {
+        let hepburn = |text| to_romaji(text, Romanization::Hepburn);
+        let kunrei = |text| to_romaji(text, Romanization::Kunrei);
+
+        assert_eq!(hepburn("ちゃんと"), "chanto");
+        assert_eq!(kunrei("ちゃんと"), "tyanto");
+        assert_eq!(hepburn("まっちゃ"), "matcha");
+        assert_eq!(kunrei("まっちゃ"), "mattya");
+        assert_eq!(hepburn("きんえん"), "kin'en");
+        assert_eq!(hepburn("ふじさん"), "fujisan");
+        assert_eq!(kunrei("ふじさん"), "huzisan");
+        assert_eq!(hepburn("コーヒー"), "kōhī");
+        assert_eq!(kunrei("コーヒー"), "kôhî");
+        assert_eq!(hepburn("あっ"), "a'");
+        assert_eq!(hepburn("食べる"), "食beru");
+    }
+
+    #[test]
+    fn round_trip() {
+        for text in ["しゅくだい", "がっこう", "ぜんいん", "ちょっと", "りょこう"]
+        {
+            for system in [Romanization::Hepburn, Romanization::Kunrei] {
+                let romaji = to_romaji(text, system);
+                assert_eq!(romaji_to_hiragana(&romaji).unwrap(), text, "{}", romaji);
+            }
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Information about kanji, from the KANJIDIC2 dictionary and the imported Yomitan kanji
+//! dictionaries
+//!
+//! KANJIDIC2 is not shipped with wdym. Download `kanjidic2.xml.gz` from
+//! <https://www.edrdg.org/wiki/index.php/KANJIDIC_Project>, and put it in the data directory, see
+//! [`crate::data`]. The kanji dictionaries of Yomitan, imported with
+//! `wdym import-yomitan <archive>`, are used for the kanji KANJIDIC2 doesn't have, and for their
+//! frequencies.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::japanese::kanji::Kanjidic;
+//!
+//! let kanjidic = Kanjidic::parse(
+//!     r#"<kanjidic2><character><literal>本</literal>
+//!     <radical><rad_value rad_type="classical">75</rad_value></radical>
+//!     <misc><grade>1</grade><stroke_count>5</stroke_count><jlpt>4</jlpt></misc>
+//!     <reading_meaning><rmgroup>
+//!     <reading r_type="ja_on">ホン</reading><reading r_type="ja_kun">もと</reading>
+//!     <meaning>book</meaning><meaning m_lang="fr">livre</meaning>
+//!     </rmgroup></reading_meaning></character></kanjidic2>"#
+//!         .as_bytes(),
+//! )
+//! .unwrap();
+//! let info = kanjidic.get('本').unwrap();
+//! assert_eq!(info.on_readings, vec!["ホン"]);
+//! assert_eq!(info.meanings, vec!["book"]);
+//! assert_eq!(info.stroke_count, Some(5));
+//! ```
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+use quick_xml::events::Event;
+
+use crate::data::{self, MissingDataError};
+use crate::translators::yomitan::Yomitan;
+
+/// What KANJIDIC2 tells about a kanji
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KanjiInfo {
+    pub literal: char,
+    /// Readings borrowed from Chinese, in katakana
+    pub on_readings: Vec<String>,
+    /// Native readings, in hiragana. The okurigana follows a dot: `はか.る`
+    pub kun_readings: Vec<String>,
+    /// Meanings in English
+    pub meanings: Vec<String>,
+    pub stroke_count: Option<u8>,
+    /// Level of the former JLPT, from 4 (easiest) to 1. The current levels aren't in KANJIDIC2.
+    pub jlpt: Option<u8>,
+    /// School grade it's taught in: 1 to 6, 8 for the rest of the jōyō kanji, 9 and 10 for the
+    /// jinmeiyō kanji
+    pub grade: Option<u8>,
+    /// Number of its classical (Kangxi) radical
+    pub radical: Option<u8>,
+    /// Rank among the kanji by how common they are, 1 being the most common
+    pub frequency: Option<u32>,
+}
+
+/// The kanji of KANJIDIC2
+#[derive(Debug, Default)]
+pub struct Kanjidic {
+    kanji: HashMap<char, KanjiInfo>,
+}
+
+/// Names of the KANJIDIC2 file in the data directory
+const KANJIDIC_FILES: &[&str] = &["kanjidic2.xml", "kanjidic2.xml.gz"];
+
+/// The dictionary loaded from the data directory, shared by `kanji_info`
+static DEFAULT: Mutex<Option<Arc<Kanjidic>>> = Mutex::new(None);
+
+impl Kanjidic {
+    /// Reads the KANJIDIC2 XML
+    pub fn parse(input: impl BufRead) -> Result<Self> {
+        let mut reader = quick_xml::Reader::from_reader(input);
+        reader.config_mut().trim_text(true);
+
+        let mut kanji = HashMap::new();
+        let mut current = KanjiInfo::default();
+        // The element the text is in, and its type attribute
+        let mut element: Vec<u8> = Vec::new();
+        let mut kind: Option<String> = None;
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(start) => {
+                    element = start.name().as_ref().to_vec();
+                    let attribute: &[u8] = match element.as_slice() {
+                        b"rad_value" => b"rad_type",
+                        b"reading" => b"r_type",
+                        b"meaning" => b"m_lang",
+                        _ => b"",
+                    };
+                    kind = match start.try_get_attribute(attribute)? {
+                        Some(attribute) => Some(attribute.unescape_value()?.into_owned()),
+                        None => None,
+                    };
+                    if element == b"character" {
+                        current = KanjiInfo::default();
+                    }
+                }
+                Event::Text(text) => {
+                    let text = text.unescape()?;
+                    let number = || text.parse::<u8>().ok();
+                    match (element.as_slice(), kind.as_deref()) {
+                        (b"literal", _) => {
+                            current.literal = text.chars().next().unwrap_or_default();
+                        }
+                        (b"rad_value", Some("classical")) => current.radical = number(),
+                        (b"grade", _) => current.grade = number(),
+                        // The first stroke count is the right one, the others are common mistakes
+                        (b"stroke_count", _) if current.stroke_count.is_none() => {
+                            current.stroke_count = number();
+                        }
+                        (b"jlpt", _) => current.jlpt = number(),
+                        (b"freq", _) => current.frequency = text.parse().ok(),
+                        (b"reading", Some("ja_on")) => current.on_readings.push(text.into_owned()),
+                        (b"reading", Some("ja_kun")) => {
+                            current.kun_readings.push(text.into_owned())
+                        }
+                        // Meanings without a language are in English
+                        (b"meaning", None) => current.meanings.push(text.into_owned()),
+                        _ => {}
+                    }
+                }
+                Event::End(end) => {
+                    if end.name().as_ref() == b"character" && current.literal != char::default() {
+                        let info = std::mem::take(&mut current);
+                        kanji.insert(info.literal, info);
+                    }
+                    element.clear();
+                    kind = None;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        Ok(Self { kanji })
+    }
+
+    /// Reads a KANJIDIC2 file, which may be compressed with gzip
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(data::open(path.as_ref())?)
+    }
+
+    /// The dictionary in the data directory, loaded on first use
+    pub fn installed() -> Result<Arc<Self>> {
+        let mut default = DEFAULT.lock().expect("Lock poisoned");
+        if let Some(kanjidic) = default.as_ref() {
+            return Ok(Arc::clone(kanjidic));
+        }
+        let path = data::find(KANJIDIC_FILES).ok_or_else(|| MissingDataError {
+            name: "KANJIDIC2".to_string(),
+            files: KANJIDIC_FILES.iter().map(|file| file.to_string()).collect(),
+        })?;
+        let kanjidic = Arc::new(Self::load(path)?);
+        *default = Some(Arc::clone(&kanjidic));
+        Ok(kanjidic)
+    }
+
+    pub fn get(&self, literal: char) -> Option<&KanjiInfo> {
+        self.kanji.get(&literal)
+    }
+
+    /// The kanji of the text known to the dictionary, in order and without repeats
+    pub fn lookup_text(&self, text: &str) -> Vec<&KanjiInfo> {
+        let mut res: Vec<&KanjiInfo> = Vec::new();
+        for info in text
+            .chars()
+            .filter(|c| is_kanji(*c))
+            .filter_map(|c| self.get(c))
+        {
+            if !res.iter().any(|known| known.literal == info.literal) {
+                res.push(info);
+            }
+        }
+        res
+    }
+}
+
+/// Looks up the kanji in the installed KANJIDIC2 and Yomitan kanji dictionaries
+///
+/// Fails if neither is installed, and returns `None` if none has the character.
+pub fn kanji_info(literal: char) -> Result<Option<KanjiInfo>> {
+    Ok(lookup_kanji(&literal.to_string())?.pop())
+}
+
+/// The kanji of the text in the installed KANJIDIC2 and Yomitan kanji dictionaries, in order and
+/// without repeats
+///
+/// KANJIDIC2 comes first, completed with the frequencies of Yomitan. Fails if neither is
+/// installed.
+pub fn lookup_kanji(text: &str) -> Result<Vec<KanjiInfo>> {
+    let yomitan = Yomitan::installed()
+        .ok()
+        .filter(|store| store.has_kanji().unwrap_or(false));
+    let kanjidic = match Kanjidic::installed() {
+        Ok(kanjidic) => Some(kanjidic),
+        Err(_) if yomitan.is_some() => None,
+        Err(err) => return Err(err),
+    };
+    let mut res: Vec<KanjiInfo> = Vec::new();
+    for literal in text.chars().filter(|c| is_kanji(*c)) {
+        if res.iter().any(|known| known.literal == literal) {
+            continue;
+        }
+        let from_kanjidic = kanjidic
+            .as_ref()
+            .and_then(|kanjidic| kanjidic.get(literal).cloned());
+        let from_yomitan = yomitan
+            .as_ref()
+            .and_then(|store| store.kanji(literal).ok().flatten());
+        let info = match (from_kanjidic, from_yomitan) {
+            (Some(mut info), Some(other)) => {
+                info.frequency = info.frequency.or(other.frequency);
+                Some(info)
+            }
+            (info, other) => info.or(other),
+        };
+        res.extend(info);
+    }
+    Ok(res)
+}
+
+/// Whether the character is a kanji, or a mark repeating or abbreviating one
+pub fn is_kanji(c: char) -> bool {
+    matches!(c,
+        '々' | '〆' | 'ヶ'
+        | '\u{3400}'..='\u{4dbf}' // CJK extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK unified ideographs
+        | '\u{f900}'..='\u{faff}' // CJK compatibility ideographs
+        | '\u{20000}'..='\u{2ffff}') // CJK extensions B and later
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KANJIDIC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE kanjidic2 [
+<!ELEMENT kanjidic2 (header,character*)>
+]>
+<kanjidic2>
+<header>
+<file_version>4</file_version>
+<database_version>2024-001</database_version>
+</header>
+<character>
+<literal>計</literal>
+<codepoint>
+<cp_value cp_type="ucs">8a08</cp_value>
+</codepoint>
+<radical>
+<rad_value rad_type="classical">149</rad_value>
+<rad_value rad_type="nelson_c">149</rad_value>
+</radical>
+<misc>
+<grade>2</grade>
+<stroke_count>9</stroke_count>
+<stroke_count>8</stroke_count>
+<freq>106</freq>
+<jlpt>3</jlpt>
+</misc>
+<reading_meaning>
+<rmgroup>
+<reading r_type="pinyin">ji4</reading>
+<reading r_type="ja_on">ケイ</reading>
+<reading r_type="ja_kun">はか.る</reading>
+<reading r_type="ja_kun">はか.らう</reading>
+<meaning>plot</meaning>
+<meaning>plan</meaning>
+<meaning>scheme</meaning>
+<meaning>measure</meaning>
+<meaning m_lang="fr">mesurer</meaning>
+</rmgroup>
+<nanori>かず</nanori>
+</reading_meaning>
+</character>
+<character>
+<literal>算</literal>
+<radical>
+<rad_value rad_type="classical">118</rad_value>
+</radical>
+<misc>
+<grade>2</grade>
+<stroke_count>14</stroke_count>
+<jlpt>2</jlpt>
+</misc>
+<reading_meaning>
+<rmgroup>
+<reading r_type="ja_on">サン</reading>
+<reading r_type="ja_kun">そろ</reading>
+<meaning>calculate</meaning>
+<meaning>divining</meaning>
+<meaning>number</meaning>
+<meaning>abacus</meaning>
+<meaning>probability</meaning>
+</rmgroup>
+</reading_meaning>
+</character>
+</kanjidic2>
+"#;
+
+    #[test]
+    fn parse_kanjidic() {
+        let kanjidic = Kanjidic::parse(KANJIDIC.as_bytes()).unwrap();
+        assert_eq!(
+            kanjidic.get('計').unwrap(),
+            &KanjiInfo {
+                literal: '計',
+                on_readings: vec!["ケイ".to_string()],
+                kun_readings: vec!["はか.る".to_string(), "はか.らう".to_string()],
+                meanings: vec![
+                    "plot".to_string(),
+                    "plan".to_string(),
+                    "scheme".to_string(),
+                    "measure".to_string()
+                ],
+                stroke_count: Some(9),
+                jlpt: Some(3),
+                grade: Some(2),
+                radical: Some(149),
+                frequency: Some(106),
+            }
+        );
+        assert!(kanjidic.get('本').is_none());
+    }
+
+    #[test]
+    fn kanji_of_text() {
+        let kanjidic = Kanjidic::parse(KANJIDIC.as_bytes()).unwrap();
+        let literals: Vec<char> = kanjidic
+            .lookup_text("計算する、計算機")
+            .iter()
+            .map(|info| info.literal)
+            .collect();
+        assert_eq!(literals, vec!['計', '算']);
+    }
+
+    #[test]
+    fn compressed_file() {
+        use std::io::Write;
+
+        let path =
+            std::env::temp_dir().join(format!("wdym-kanjidic-{}.xml.gz", std::process::id()));
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&path).unwrap(),
+            Default::default(),
+        );
+        encoder.write_all(KANJIDIC.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let kanjidic = Kanjidic::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(kanjidic.get('算').unwrap().stroke_count, Some(14));
+    }
+}
@@ -0,0 +1,7 @@
+//! Tools for looking up Japanese text
+pub mod deinflect;
+pub mod furigana;
+pub mod kana;
+pub mod kanji;
+pub mod pitch;
+pub mod segment;
@@ -0,0 +1,228 @@
+//! Pitch accent of Japanese words, from a local accent dictionary
+//!
+//! The dictionary is a tab separated file of words, their readings and their accents, like the
+//! `accents.txt` of [Kanjium](https://github.com/mifunetoshiro/kanjium). An accent is the mora
+//! after which the pitch drops, 0 if it doesn't drop: 計算 (けいさん) is 0, 言葉 (ことば) is 3.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::japanese::pitch::{morae, pattern, AccentDictionary};
+//!
+//! let accents = AccentDictionary::parse("言葉\tことば\t3\n".as_bytes()).unwrap();
+//! assert_eq!(accents.get("言葉", Some("ことば")), Some(&[3][..]));
+//! assert_eq!(morae("きょう"), vec!["きょ", "う"]);
+//! // Low, then high until the drop, and the particle after the word is low
+//! assert_eq!(pattern(3, 3), vec![false, true, true, false]);
+//! ```
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+
+use super::furigana;
+use crate::data::{self, MissingDataError};
+
+/// Words with their accents, by spelling and reading
+#[derive(Debug, Default)]
+pub struct AccentDictionary {
+    accents: HashMap<(String, String), Vec<u8>>,
+}
+
+/// Names of the accent dictionary in the data directory
+const ACCENT_FILES: &[&str] = &["accents.txt", "accents.tsv"];
+
+/// The dictionary loaded from the data directory
+static DEFAULT: Mutex<Option<Arc<AccentDictionary>>> = Mutex::new(None);
+
+impl AccentDictionary {
+    /// Reads the lines of `word`, `reading` and comma separated `accents`
+    ///
+    /// The reading is empty for words written in kana only. Lines without an accent are skipped.
+    pub fn parse(input: impl BufRead) -> Result<Self> {
+        let mut accents = HashMap::new();
+        for line in input.lines() {
+            let line = line?;
+            let mut fields = line.split('\t');
+            let (Some(word), Some(reading), Some(accent)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let reading = match reading {
+                "" => word,
+                reading => reading,
+            };
+            // Accents may be annotated with the part of speech they apply to: (名)0
+            let downsteps: Vec<u8> = accent
+                .split(',')
+                .filter_map(|accent| {
+                    accent
+                        .trim_start_matches(|c: char| !c.is_ascii_digit())
+                        .parse()
+                        .ok()
+                })
+                .collect();
+            if !downsteps.is_empty() {
+                accents.insert((word.to_string(), reading.to_string()), downsteps);
+            }
+        }
+        Ok(Self { accents })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(data::open(path.as_ref())?)
+    }
+
+    /// The dictionary in the data directory, loaded on first use
+    pub fn installed() -> Result<Arc<Self>> {
+        let mut default = DEFAULT.lock().expect("Lock poisoned");
+        if let Some(accents) = default.as_ref() {
+            return Ok(Arc::clone(accents));
+        }
+        let path = data::find(ACCENT_FILES).ok_or_else(|| MissingDataError {
+            name: "The pitch accent dictionary".to_string(),
+            files: ACCENT_FILES.iter().map(|file| file.to_string()).collect(),
+        })?;
+        let accents = Arc::new(Self::load(path)?);
+        *default = Some(Arc::clone(&accents));
+        Ok(accents)
+    }
+
+    /// The accents of the word, most common first
+    ///
+    /// Without a reading, the word has to be written in kana.
+    pub fn get(&self, word: &str, reading: Option<&str>) -> Option<&[u8]> {
+        let key = (word.to_string(), reading.unwrap_or(word).to_string());
+        self.accents.get(&key).map(Vec::as_slice)
+    }
+}
+
+/// Splits kana into morae: the small ゃ, ゅ, ょ and vowels belong to the kana before them
+pub fn morae(kana: &str) -> Vec<String> {
+    let mut morae: Vec<String> = Vec::new();
+    for c in kana.chars() {
+        let small = "ゃゅょぁぃぅぇぉゎャュョァィゥェォヮ".contains(c);
+        match morae.last_mut() {
+            Some(mora) if small => mora.push(c),
+            _ => morae.push(c.to_string()),
+        }
+    }
+    morae
+}
+
+/// Whether each mora is high, followed by the pitch of the particle after the word
+pub fn pattern(mora_count: usize, downstep: u8) -> Vec<bool> {
+    let downstep = downstep as usize;
+    (0..=mora_count)
+        .map(|mora| match downstep {
+            // Heiban: low, then high, and the particle stays high
+            0 => mora != 0,
+            // Atamadaka: only the first mora is high
+            1 => mora == 0,
+            // Nakadaka and odaka: rises after the first mora, and drops after the downstep
+            _ => mora != 0 && mora < downstep,
+        })
+        .collect()
+}
+
+/// Draws the pitch of every mora of the reading, to write on the line above it in a terminal
+///
+/// High morae are overlined with `‾`, and low morae underlined with `_`.
+pub fn graph(reading: &str, downstep: u8) -> String {
+    let morae = morae(reading);
+    let pattern = pattern(morae.len(), downstep);
+    morae
+        .iter()
+        .zip(pattern)
+        .map(|(mora, high)| match high {
+            true => "‾".repeat(furigana::width(mora)),
+            false => "_".repeat(furigana::width(mora)),
+        })
+        .collect()
+}
+
+/// Writes the reading with an overline on its high morae, for HTML outputs like Anki cards
+pub fn html(reading: &str, downstep: u8) -> String {
+    let morae = morae(reading);
+    let pattern = pattern(morae.len(), downstep);
+    let mut res = String::new();
+    let mut high = false;
+    for (mora, is_high) in morae.iter().zip(&pattern) {
+        if *is_high != high {
+            res.push_str(match is_high {
+                true => "<span style=\"text-decoration: overline\">",
+                false => "</span>",
+            });
+            high = *is_high;
+        }
+        res.push_str(mora);
+    }
+    if high {
+        res.push_str("</span>");
+    }
+    // The drop after the last mora only shows on the particle
+    if downstep as usize == morae.len() {
+        res.push('ꜜ');
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_accents() {
+        let accents = AccentDictionary::parse(
+            "計算\tけいさん\t0\n箸\tはし\t1\n橋\tはし\t2\nあれ\t\t0\n今日\tきょう\t1,0\n新聞\tしんぶん\t(名)0\nbroken line\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(accents.get("計算", Some("けいさん")), Some(&[0][..]));
+        assert_eq!(accents.get("橋", Some("はし")), Some(&[2][..]));
+        assert_eq!(accents.get("あれ", None), Some(&[0][..]));
+        assert_eq!(accents.get("今日", Some("きょう")), Some(&[1, 0][..]));
+        assert_eq!(accents.get("新聞", Some("しんぶん")), Some(&[0][..]));
+        assert_eq!(accents.get("計算", None), None);
+    }
+
+    #[test]
+    fn pitch_patterns() {
+        assert_eq!(morae("しゅっぱつ"), vec!["しゅ", "っ", "ぱ", "つ"]);
+        // 箸: atamadaka
+        assert_eq!(pattern(2, 1), vec![true, false, false]);
+        // 計算: heiban
+        assert_eq!(pattern(4, 0), vec![false, true, true, true, true]);
+        // 橋: odaka
+        assert_eq!(pattern(2, 2), vec![false, true, false]);
+        // 雪崩: nakadaka
+        assert_eq!(pattern(3, 2), vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn pitch_graph() {
+        assert_eq!(graph("けいさん", 0), "__‾‾‾‾‾‾");
+        assert_eq!(graph("きょう", 1), "‾‾‾‾__");
+        assert_eq!(
+            furigana::width(&graph("きょう", 1)),
+            furigana::width("きょう")
+        );
+    }
+
+    #[test]
+    fn pitch_html() {
+        assert_eq!(
+            html("けいさん", 0),
+            "け<span style=\"text-decoration: overline\">いさん</span>"
+        );
+        assert_eq!(
+            html("はし", 1),
+            "<span style=\"text-decoration: overline\">は</span>し"
+        );
+        assert_eq!(
+            html("はし", 2),
+            "は<span style=\"text-decoration: overline\">し</span>ꜜ"
+        );
+    }
+}
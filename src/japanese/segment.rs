@@ -0,0 +1,269 @@
+//! Splits Japanese sentences into words
+//!
+//! With a dictionary, the longest words of the dictionary are taken, conjugated or not. Without
+//! one, words are guessed from the scripts: kanji with the kana that follow them, runs of
+//! katakana, and the particles between them. Conjugated words stay whole, to be deinflected when
+//! they are looked up.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::japanese::segment::{longest_match, words};
+//!
+//! assert_eq!(words("東京に行きます"), vec!["東京", "に", "行きます"]);
+//!
+//! let dictionary = ["東京", "に", "行く"];
+//! let is_word = |text: &str| dictionary.contains(&text);
+//! assert_eq!(longest_match("東京に行きます", 8, is_word), vec!["東京", "に", "行きます"]);
+//! ```
+use super::deinflect::deinflect;
+use super::{kana, kanji};
+
+/// Particles and copulas, which are words of their own when they are a whole run of kana
+const PARTICLES: &[&str] = &[
+    "でしょう",
+    "でした",
+    "です",
+    "では",
+    "には",
+    "とは",
+    "から",
+    "まで",
+    "より",
+    "ので",
+    "のに",
+    "だ",
+    "は",
+    "が",
+    "を",
+    "に",
+    "へ",
+    "と",
+    "で",
+    "も",
+    "の",
+    "や",
+    "か",
+    "ね",
+    "よ",
+];
+
+/// Particles split from the end of a run of kana, longest first
+const TRAILING_PARTICLES: &[&str] = &[
+    "でしょう",
+    "でした",
+    "です",
+    "では",
+    "には",
+    "から",
+    "まで",
+    "ので",
+    "は",
+    "が",
+    "を",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Kanji,
+    Hiragana,
+    /// Katakana, letters and digits, which make words of their own
+    Other,
+    /// Whitespace and punctuation, between words
+    Silent,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        _ if kanji::is_kanji(c) => Script::Kanji,
+        'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => Script::Hiragana,
+        // The prolonged sound mark is part of the katakana words it's in
+        _ if kana::is_kana(c.encode_utf8(&mut [0; 4])) => Script::Other,
+        _ if c.is_alphanumeric() => Script::Other,
+        _ => Script::Silent,
+    }
+}
+
+/// Splits the sentence into the longest words of the dictionary, of at most `max_len` characters
+///
+/// Conjugated words are found through their dictionary forms. Characters that start no word are
+/// words on their own, except runs of katakana, letters and digits, which are kept together.
+/// Whitespace and punctuation split the text, and are dropped.
+pub fn longest_match(text: &str, max_len: usize, is_word: impl Fn(&str) -> bool) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let first = script(chars[start]);
+        if first == Script::Silent {
+            start += 1;
+            continue;
+        }
+        let end = chars[start..]
+            .iter()
+            .position(|&c| script(c) == Script::Silent)
+            .map_or(chars.len(), |len| start + len);
+        let longest = (1..=max_len.min(end - start)).rev().find(|len| {
+            let word: String = chars[start..start + len].iter().collect();
+            deinflect(&word)
+                .iter()
+                .any(|candidate| candidate.is_well_formed() && is_word(&candidate.term))
+        });
+        let len = match longest {
+            Some(len) => len,
+            None if first == Script::Other => chars[start..end]
+                .iter()
+                .take_while(|&&c| script(c) == Script::Other)
+                .count(),
+            None => 1,
+        };
+        words.push(chars[start..start + len].iter().collect());
+        start += len;
+    }
+    words
+}
+
+/// Splits the sentence into words guessed from the scripts, without a dictionary
+pub fn words(text: &str) -> Vec<String> {
+    let mut runs: Vec<(Script, String)> = Vec::new();
+    for c in text.chars() {
+        let script = script(c);
+        match runs.last_mut() {
+            Some((last, run)) if *last == script => run.push(c),
+            _ => runs.push((script, c.to_string())),
+        }
+    }
+
+    let mut words: Vec<String> = Vec::new();
+    // Whether the last word ends with kanji, and takes the kana that follow as okurigana
+    let mut after_kanji = false;
+    for (script, run) in runs {
+        match script {
+            Script::Silent => after_kanji = false,
+            Script::Kanji => {
+                words.push(run);
+                after_kanji = true;
+            }
+            Script::Other => {
+                words.push(run);
+                after_kanji = false;
+            }
+            Script::Hiragana if PARTICLES.contains(&run.as_str()) => {
+                words.push(run);
+                after_kanji = false;
+            }
+            Script::Hiragana => {
+                let (word, particles) = split_particles(&run, after_kanji);
+                match words.last_mut() {
+                    Some(last) if after_kanji => last.push_str(word),
+                    _ if !word.is_empty() => words.push(word.to_string()),
+                    _ => {}
+                }
+                words.extend(particles.into_iter().map(str::to_string));
+                after_kanji = false;
+            }
+        }
+    }
+    words
+}
+
+/// Splits the particles ending the kana, when what's left is likely a word
+///
+/// The particle may follow a の making a noun of the plain form before it: 食べるのは.
+fn split_particles(run: &str, after_kanji: bool) -> (&str, Vec<&str>) {
+    let (word, Some(particle)) = split_particle(run, after_kanji) else {
+        return (run, Vec::new());
+    };
+    match word.strip_suffix('の') {
+        Some(plain)
+            if plain.ends_with(|c| "うくぐすつぬぶむるいた".contains(c))
+                && (after_kanji || plain.chars().count() >= 2) =>
+        {
+            (plain, vec!["の", particle])
+        }
+        _ => (word, vec![particle]),
+    }
+}
+
+/// Splits the particle ending the kana, when what's left is likely a word
+fn split_particle(run: &str, after_kanji: bool) -> (&str, Option<&str>) {
+    TRAILING_PARTICLES
+        .iter()
+        .find_map(|particle| {
+            let word = run.strip_suffix(particle)?;
+            // Okurigana may be a single kana, but kana words are longer: はは is a word
+            let is_word = match after_kanji {
+                true => true,
+                false => word.chars().count() >= 2,
+            };
+            is_word.then_some((word, Some(*particle)))
+        })
+        .unwrap_or((run, None))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_sentences() {
+        assert_eq!(words("私は学生です。"), vec!["私", "は", "学生", "です"]);
+        assert_eq!(
+            words("日本語を勉強しています"),
+            vec!["日本語", "を", "勉強しています"]
+        );
+        assert_eq!(words("これはペンです"), vec!["これ", "は", "ペン", "です"]);
+        assert_eq!(
+            words("コーヒーが好きです"),
+            vec!["コーヒー", "が", "好き", "です"]
+        );
+        assert_eq!(
+            words("食べるのは楽しい"),
+            vec!["食べる", "の", "は", "楽しい"]
+        );
+        assert_eq!(words("ものは"), vec!["もの", "は"]);
+        // Words stay whole
+        assert_eq!(words("食べられなかった"), vec!["食べられなかった"]);
+        assert_eq!(words("はは"), vec!["はは"]);
+    }
+
+    #[test]
+    fn split_with_dictionary() {
+        let dictionary = [
+            "食べる",
+            "の",
+            "は",
+            "楽しい",
+            "私",
+            "学生",
+            "です",
+            "日本語",
+            "を",
+            "勉強",
+            "する",
+            "勉強する",
+            "いる",
+            "ペン",
+            "コーヒー",
+        ];
+        let is_word = |text: &str| dictionary.contains(&text);
+        assert_eq!(
+            longest_match("食べるのは楽しい", 8, is_word),
+            vec!["食べる", "の", "は", "楽しい"]
+        );
+        assert_eq!(
+            longest_match("私は学生です。", 8, is_word),
+            vec!["私", "は", "学生", "です"]
+        );
+        // Conjugated words are found through their dictionary forms
+        assert_eq!(
+            longest_match("日本語を勉強しました", 8, is_word),
+            vec!["日本語", "を", "勉強しました"]
+        );
+        // Unknown characters are words on their own, but katakana runs stay together
+        assert_eq!(
+            longest_match("猫とボールペン", 8, is_word),
+            vec!["猫", "と", "ボールペン"]
+        );
+        assert_eq!(longest_match("", 8, is_word), Vec::<String>::new());
+    }
+}
@@ -1,3 +1,14 @@
 pub mod app;
+pub mod audio;
+pub mod batch;
+pub mod chinese;
+pub mod clipboard;
+pub mod data;
+pub mod document;
+pub mod frequency;
+pub mod html;
+pub mod http;
+pub mod japanese;
 pub mod search;
+pub mod tatoeba;
 pub mod translators;
@@ -1,27 +1,170 @@
-use clap::Parser;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
 use color_eyre::eyre::Result;
+use ratatui::crossterm::event::DisableMouseCapture;
+use ratatui::crossterm::execute;
 
 use wdym::app::App;
+use wdym::audio::{AudioConfig, Speaker, DEFAULT_PLAYER};
+use wdym::batch::{lookup_batch, BatchConfig, BatchFormat};
+use wdym::clipboard::{self, ClipboardConfig, Selection};
+use wdym::document::{Document, DocumentFormat};
+use wdym::frequency::DefinitionOrder;
+use wdym::http::{HttpClient, HttpConfig};
+use wdym::japanese::kana::Romanization;
 use wdym::search::parse_lang;
 use wdym::search::SearchConfig;
+use wdym::translators::yomitan::Yomitan;
 use wdym::translators::SearchProvider;
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, subcommand_negates_reqs = true)]
 struct CliArgs {
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The query to look up. In batch mode, the file to read queries from, or stdin if omitted.
+    #[arg(required_unless_present_any = ["list_languages", "batch", "watch_clipboard"])]
+    input: Option<String>,
 
-    /// Optional name of source language
-    #[arg(short, long)]
+    /// Optional name of source language. Accepts regional variants like `zh-TW` or `pt_BR`.
+    #[arg(short, long, global = true)]
     source_lang: Option<String>,
 
     /// Name of target language. Only required for some search providers.
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     dest_lang: Option<String>,
 
     /// What search provider to use.
-    #[arg(short, long, default_value_t = SearchProvider::GoogleTranslate)]
+    #[arg(short, long, global = true, default_value_t = SearchProvider::GoogleTranslate)]
     provider: SearchProvider,
+
+    /// How readings are written in the latin alphabet. Romaji queries are read in either system,
+    /// and converted to kana when the source language is Japanese.
+    #[arg(long, global = true, default_value_t = Romanization::Hepburn)]
+    romanization: Romanization,
+
+    /// How definitions are ordered. Frequencies come from the frequency lists in the data
+    /// directory.
+    #[arg(long, global = true, default_value_t = DefinitionOrder::Confidence)]
+    sort: DefinitionOrder,
+
+    /// List the languages supported by the search provider, and exit
+    #[arg(long)]
+    list_languages: bool,
+
+    /// Look up every line of the input, and print the results instead of starting the TUI
+    #[arg(long)]
+    batch: bool,
+
+    /// Output format of batch mode
+    #[arg(long, default_value_t = BatchFormat::Tsv, requires = "batch")]
+    format: BatchFormat,
+
+    /// How many lookups batch mode and `translate-file` run at the same time
+    #[arg(long, global = true, default_value_t = 4)]
+    concurrency: usize,
+
+    /// Minimum milliseconds between two lookups in batch mode and `translate-file`
+    #[arg(long, global = true)]
+    interval: Option<u64>,
+
+    /// Proxy to send the requests through, e.g. `socks5h://127.0.0.1:1080`
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    /// Seconds to wait for the whole response of a request, connection included
+    #[arg(long, global = true, default_value_t = 30)]
+    timeout: u64,
+
+    /// How many times to retry a failed request
+    #[arg(long, global = true, default_value_t = 3)]
+    retries: u32,
+
+    /// Command synthesizing speech instead of Google Translate, like
+    /// `espeak-ng -v {lang} -w {output} -- {text}`. Without `{text}`, the text is written to its
+    /// input, and without `{output}`, the audio is read from its output. Put `--` before
+    /// `{text}`, or text starting with `-` is refused.
+    #[arg(long)]
+    tts_command: Option<String>,
+
+    /// Command playing the audio of <p> and <t>, given as `{file}` or as the last argument
+    #[arg(long, default_value = DEFAULT_PLAYER)]
+    player: String,
+
+    /// Look up the text copied to the clipboard while the TUI runs
+    #[arg(long, conflicts_with = "batch")]
+    watch_clipboard: bool,
+
+    /// Which clipboard to watch
+    #[arg(long, default_value_t = Selection::Clipboard, requires = "watch_clipboard")]
+    selection: Selection,
+
+    /// Command printing the content of the clipboard, instead of `wl-paste`, `xclip` or `xsel`
+    #[arg(long, requires = "watch_clipboard")]
+    clipboard_command: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Translate the text of a Markdown, subtitle (SRT, VTT) or gettext PO file, keeping its
+    /// structure
+    TranslateFile {
+        /// The file to translate
+        file: PathBuf,
+
+        /// Where to write the translated file, instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Format of the file, guessed from its extension if omitted
+        #[arg(long)]
+        file_format: Option<DocumentFormat>,
+    },
+    /// Import Yomichan or Yomitan dictionaries, to look them up with `--provider yomitan`
+    ImportYomitan {
+        /// The ZIP archives of the dictionaries
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+    },
+}
+
+impl CliArgs {
+    fn batch_config(&self) -> BatchConfig {
+        BatchConfig {
+            concurrency: self.concurrency,
+            interval: self.interval.map(Duration::from_millis),
+        }
+    }
+
+    fn clipboard_config(&self) -> ClipboardConfig {
+        ClipboardConfig {
+            selection: self.selection,
+            command: self.clipboard_command.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn audio_config(&self) -> AudioConfig {
+        AudioConfig {
+            tts_command: self.tts_command.clone(),
+            player: self.player.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn http_client(&self) -> Result<HttpClient> {
+        HttpClient::new(HttpConfig {
+            timeout: Duration::from_secs(self.timeout),
+            proxy: self.proxy.clone(),
+            max_retries: self.retries,
+            ..Default::default()
+        })
+    }
 }
 
 impl TryInto<SearchConfig> for CliArgs {
@@ -29,7 +172,7 @@ impl TryInto<SearchConfig> for CliArgs {
 
     fn try_into(self) -> Result<SearchConfig, Self::Error> {
         let res: SearchConfig = SearchConfig {
-            query: self.input,
+            query: self.input.unwrap_or_default(),
             source_language: match self.source_lang {
                 Some(lang) => Some(parse_lang(&lang)?),
                 None => None,
@@ -39,6 +182,8 @@ impl TryInto<SearchConfig> for CliArgs {
                 None => None,
             },
             provider: self.provider,
+            romanization: self.romanization,
+            order: self.sort,
         };
 
         Ok(res)
@@ -49,15 +194,125 @@ impl TryInto<SearchConfig> for CliArgs {
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let search_config: SearchConfig = CliArgs::parse().try_into()?;
+    let mut args = CliArgs::parse();
+    if let Some(command) = args.command.take() {
+        return match command {
+            Command::TranslateFile {
+                file,
+                output,
+                file_format,
+            } => translate_file(args, file, output, file_format),
+            Command::ImportYomitan { archives } => import_yomitan(archives),
+        };
+    }
+
+    if args.list_languages {
+        for lang in args.provider.supported_languages() {
+            println!("{}\t{}", lang.to_639_3(), lang.to_name());
+        }
+        return Ok(());
+    }
+
+    if args.batch {
+        return run_batch(args);
+    }
+
+    let client = args.http_client()?;
+    let speaker = Speaker::new(client.clone(), args.audio_config());
+    // Fails before the TUI starts if the clipboard can't be read
+    let copies = if args.watch_clipboard {
+        Some(clipboard::watch(args.clipboard_config())?)
+    } else {
+        None
+    };
+    let search_config: SearchConfig = args.try_into()?;
 
     let mut terminal = ratatui::init();
-    let mut app = App::new(search_config);
+    // The app captures the mouse while the words of a sentence can be clicked, which is released
+    // before the terminal is restored
+    let restore_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = execute!(std::io::stdout(), DisableMouseCapture);
+        restore_hook(info);
+    }));
+    let mut app = App::new(search_config, client, speaker);
+    if let Some(copies) = copies {
+        app.watch_clipboard(copies);
+    }
     let result = app.run(&mut terminal);
 
+    let mouse_released = execute!(std::io::stdout(), DisableMouseCapture);
     ratatui::restore();
 
-    result
+    result?;
+    Ok(mouse_released?)
+}
+
+/// Looks up every non-empty line of the input, and prints one result per line
+fn run_batch(args: CliArgs) -> Result<()> {
+    let input: Box<dyn BufRead> = match &args.input {
+        Some(path) if path != "-" => Box::new(std::io::BufReader::new(std::fs::File::open(path)?)),
+        _ => Box::new(std::io::stdin().lock()),
+    };
+    let queries: Vec<String> = input
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .collect::<Result<_, _>>()?;
+
+    let format = args.format;
+    let batch_config = args.batch_config();
+    let client = args.http_client()?;
+    let search_config: SearchConfig = args.try_into()?;
+
+    let mut stdout = std::io::stdout().lock();
+    lookup_batch(&client, queries, &search_config, &batch_config, |entry| {
+        writeln!(stdout, "{}", format.format(&entry))?;
+        Ok(())
+    })
+}
+
+/// Translates the text of a structured file, and writes it to `output` or stdout
+fn translate_file(
+    args: CliArgs,
+    file: PathBuf,
+    output: Option<PathBuf>,
+    file_format: Option<DocumentFormat>,
+) -> Result<()> {
+    let format = file_format
+        .or_else(|| DocumentFormat::from_path(&file))
+        .ok_or_else(|| {
+            color_eyre::eyre::eyre!(
+                "can't guess the format of {}, use --file-format",
+                file.display()
+            )
+        })?;
+    let document = Document::parse(format, &std::fs::read_to_string(&file)?);
+
+    let batch_config = args.batch_config();
+    let client = args.http_client()?;
+    let search_config: SearchConfig = args.try_into()?;
+    let (translated, failures) = document.translate(&client, &search_config, &batch_config);
+
+    for failure in &failures {
+        if let Err(err) = &failure.result {
+            eprintln!("failed to translate {:?}: {}", failure.query, err);
+        }
+    }
+    match output {
+        Some(path) => std::fs::write(path, translated)?,
+        None => std::io::stdout().lock().write_all(translated.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+fn import_yomitan(archives: Vec<PathBuf>) -> Result<()> {
+    let store = Yomitan::create()?;
+    for archive in archives {
+        let summary = store.import(std::io::BufReader::new(std::fs::File::open(&archive)?))?;
+        println!("Imported {}", summary);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -67,23 +322,76 @@ mod test {
     #[should_panic]
     fn invalid_cli_args_source() {
         let args = CliArgs {
-            input: "book".to_owned(),
+            command: None,
+            input: Some("book".to_owned()),
             source_lang: Some("invalid language for test".to_string()),
             dest_lang: None,
             provider: SearchProvider::GoogleTranslate,
+            romanization: Romanization::Hepburn,
+            sort: DefinitionOrder::Confidence,
+            list_languages: false,
+            batch: false,
+            format: BatchFormat::Tsv,
+            concurrency: 4,
+            interval: None,
+            proxy: None,
+            timeout: 30,
+            retries: 3,
+            tts_command: None,
+            player: DEFAULT_PLAYER.to_string(),
+            watch_clipboard: false,
+            selection: Selection::Clipboard,
+            clipboard_command: None,
         };
 
         let _search_conf: SearchConfig = args.try_into().unwrap();
     }
 
+    #[test]
+    fn translate_file_subcommand() {
+        let args = CliArgs::try_parse_from([
+            "wdym",
+            "translate-file",
+            "in.srt",
+            "-d",
+            "ja",
+            "-o",
+            "out.srt",
+        ])
+        .unwrap();
+
+        assert_eq!(args.dest_lang.as_deref(), Some("ja"));
+        assert!(matches!(
+            args.command,
+            Some(Command::TranslateFile { ref file, output: Some(ref output), file_format: None })
+                if file.to_str() == Some("in.srt") && output.to_str() == Some("out.srt")
+        ));
+    }
+
     #[test]
     #[should_panic]
     fn invalid_cli_args_dest() {
         let args = CliArgs {
-            input: "book".to_owned(),
+            command: None,
+            input: Some("book".to_owned()),
             source_lang: None,
             dest_lang: Some("invalid language for test".to_string()),
             provider: SearchProvider::GoogleTranslate,
+            romanization: Romanization::Hepburn,
+            sort: DefinitionOrder::Confidence,
+            list_languages: false,
+            batch: false,
+            format: BatchFormat::Tsv,
+            concurrency: 4,
+            interval: None,
+            proxy: None,
+            timeout: 30,
+            retries: 3,
+            tts_command: None,
+            player: DEFAULT_PLAYER.to_string(),
+            watch_clipboard: false,
+            selection: Selection::Clipboard,
+            clipboard_command: None,
         };
 
         let _search_conf: SearchConfig = args.try_into().unwrap();
@@ -2,16 +2,91 @@ use std::error::Error;
 
 use isolang::Language;
 
+use crate::frequency::{DefinitionOrder, FrequencyList};
+use crate::http::HttpClient;
+use crate::japanese::pitch::AccentDictionary;
+use crate::japanese::{
+    self,
+    kana::{self, Romanization},
+};
+use crate::tatoeba::Tatoeba;
 use crate::translators;
+use crate::translators::yomitan::Yomitan;
 use crate::translators::SearchProvider;
 use color_eyre::Result;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct SearchConfig {
     pub query: String,
-    pub source_language: Option<Language>,
-    pub target_language: Option<Language>,
+    pub source_language: Option<LanguageTag>,
+    pub target_language: Option<LanguageTag>,
     pub provider: SearchProvider,
+    /// How readings are written in the latin alphabet
+    pub romanization: Romanization,
+    /// How the definitions are ordered
+    pub order: DefinitionOrder,
+}
+
+/// A language with an optional script and region, as in a BCP 47 tag.
+///
+/// `zh-Hant-TW` is `Language::Zho` written in the `Hant` script, used in the `TW` region.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LanguageTag {
+    pub language: Language,
+    /// ISO 15924 script code, in title case: `Hant`, `Latn`
+    pub script: Option<String>,
+    /// ISO 3166-1 alpha-2 or UN M.49 region code, in upper case: `TW`, `419`
+    pub region: Option<String>,
+}
+
+impl LanguageTag {
+    /// Creates a tag of a language, without a script or region
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            script: None,
+            region: None,
+        }
+    }
+
+    /// Human readable name of the tag, e.g. `Chinese (Hant, TW)`
+    pub fn name(&self) -> String {
+        let mut name = self.language.to_name().to_string();
+        let subtags: Vec<&str> = [self.script.as_deref(), self.region.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        if !subtags.is_empty() {
+            name.push_str(&format!(" ({})", subtags.join(", ")));
+        }
+        name
+    }
+}
+
+impl From<Language> for LanguageTag {
+    fn from(value: Language) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Formats the tag in BCP 47 form, preferring the ISO 639-1 code of the language
+impl std::fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.language
+                .to_639_1()
+                .unwrap_or_else(|| self.language.to_639_3())
+        )?;
+        if let Some(script) = &self.script {
+            write!(f, "-{}", script)?;
+        }
+        if let Some(region) = &self.region {
+            write!(f, "-{}", region)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -20,13 +95,89 @@ pub struct Translation {
     pub(crate) translated: Option<String>,
 }
 
+/// A word of a dictionary, with its reading in kana
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub(crate) text: String,
+    pub(crate) reading: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Definition {
+    /// The dictionary word defined, when the provider is a dictionary
+    pub(crate) headword: Option<Word>,
+    /// Pitch accents of the headword, most common first, from the local accent dictionary
+    pub(crate) pitch_accents: Option<Vec<u8>>,
+    /// Other ways to write the headword, like its variant kanji
+    pub(crate) other_spellings: Option<Vec<String>>,
     pub(crate) meaning: String,
+    /// The meaning as lines of styled text, when the dictionary formats it. `meaning` is its
+    /// plain text.
+    pub(crate) rich_meaning: Option<Vec<Vec<StyledSpan>>>,
     pub(crate) pos: String, // Part of speech, noun verb etc.
     pub(crate) reverse_translation: Option<Vec<String>>,
     pub(crate) confidence: Option<f32>,
-    pub(crate) examples: Option<Vec<String>>,
+    /// Rank of the word in the frequency lists, 1 being the most common
+    pub(crate) frequency: Option<u32>,
+    pub(crate) examples: Option<Vec<Example>>,
+    /// Words related to this meaning, by kind of relation
+    pub(crate) relations: Option<Vec<Relation>>,
+}
+
+/// Emphasis of styled text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextStyle {
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    /// Less important text, like notes and readings
+    pub(crate) dim: bool,
+    /// Links, and underlined text
+    pub(crate) underlined: bool,
+}
+
+/// A run of text in one style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledSpan {
+    pub(crate) text: String,
+    pub(crate) style: TextStyle,
+}
+
+/// A sentence using the word, with its translation if it is bilingual
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    pub(crate) text: String,
+    pub(crate) translation: Option<String>,
+}
+
+/// How words are related to the meaning they are listed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationKind {
+    /// More general words: `canine` for `dog`
+    Hypernym,
+    /// More specific words: `puppy` for `dog`
+    Hyponym,
+    Antonym,
+    /// Words derived from the same root: `runner` for `run`
+    Derived,
+}
+
+impl std::fmt::Display for RelationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelationKind::Hypernym => "broader",
+            RelationKind::Hyponym => "narrower",
+            RelationKind::Antonym => "opposite",
+            RelationKind::Derived => "related forms",
+        }
+        .fmt(f)
+    }
+}
+
+/// Words related to a meaning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub(crate) kind: RelationKind,
+    pub(crate) words: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -35,27 +186,351 @@ pub struct Literation {
     pub(crate) translated: Option<String>,
 }
 
+/// A language the query may be written in, as detected by the provider
+#[derive(Debug)]
+pub struct LanguageCandidate {
+    pub(crate) lang: LanguageTag,
+    pub(crate) confidence: Option<f32>,
+}
+
+/// Other ways to translate a segment of the query
+#[derive(Debug)]
+pub struct Alternative {
+    pub(crate) orig: String,
+    pub(crate) alternatives: Vec<String>,
+}
+
+/// A group of words meaning the same as the query
+#[derive(Debug)]
+pub struct Synonym {
+    pub(crate) pos: String,
+    pub(crate) words: Vec<String>,
+}
+
+/// How the query is inflected from the dictionary form found for it
+#[derive(Debug)]
+pub struct Inflection {
+    pub(crate) base_form: String,
+    /// The inflections applied to the base form, innermost first
+    pub(crate) reasons: Vec<String>,
+}
+
+/// A word of a sentence query, looked up on its own
+#[derive(Debug)]
+pub struct Token {
+    pub(crate) text: String,
+    /// The dictionary word found for it
+    pub(crate) headword: Option<Word>,
+    /// Whether it was looked up, as the words past `MAX_TOKENS`, or `MAX_TOKEN_REQUESTS` for the
+    /// online providers, aren't
+    pub(crate) looked_up: bool,
+}
+
 #[derive(Debug)]
 pub struct SearchResult {
     pub provider: SearchProvider,
     pub translations: Option<Vec<Translation>>,
     pub definitions: Option<Vec<Definition>>,
     pub src_lang: Option<String>,
+    /// How sure the provider is about the detected `src_lang`
+    pub src_lang_confidence: Option<f32>,
+    /// Possible source languages, most likely first
+    pub src_lang_candidates: Option<Vec<LanguageCandidate>>,
     pub literation: Option<Literation>,
+    /// Corrected spelling of the query, if it looks misspelled
+    pub spelling_suggestion: Option<String>,
+    pub alternatives: Option<Vec<Alternative>>,
+    pub synonyms: Option<Vec<Synonym>>,
+    pub related_words: Option<Vec<String>>,
+    pub inflection: Option<Inflection>,
+    /// The words of a sentence query, when the dictionary doesn't have the whole sentence. Their
+    /// definitions are the definitions of the result.
+    pub tokens: Option<Vec<Token>>,
+    /// Problems that didn't fail the lookup, like dictionary files that couldn't be read
+    pub warnings: Option<Vec<String>>,
 }
 
+/// Looks up the query with `HttpClient::shared`, so that repeated lookups keep to the rate
+/// limits of the providers
 pub fn lookup(query: &SearchConfig) -> Result<SearchResult> {
-    let res: SearchResult = match query.provider {
-        SearchProvider::GoogleTranslate => {
-            translators::google_translate::lookup_google_translate(query)?.into()
+    lookup_with_client(HttpClient::shared(), query)
+}
+
+/// Looks up the query, reusing the connections of `client`
+///
+/// A query in romaji is converted to kana first when the source language is Japanese.
+pub fn lookup_with_client(client: &HttpClient, query: &SearchConfig) -> Result<SearchResult> {
+    let kana_query;
+    let query = match romaji_query(query) {
+        Some(kana) => {
+            kana_query = SearchConfig {
+                query: kana,
+                ..query.clone()
+            };
+            &kana_query
         }
-        _ => todo!("This provider is not implemented"),
+        None => query,
     };
+    let mut res = lookup_provider(client, query)?;
+    if !has_query(&res, &query.query) {
+        if let Some(words) = segment_query(query).filter(|words| words.len() > 1) {
+            lookup_tokens(client, query, &mut res, words);
+        }
+    }
+    // The accent dictionary is optional, the results are shown without accents if it is missing
+    if let Ok(accents) = AccentDictionary::installed() {
+        add_pitch_accents(&mut res, &accents);
+    }
+    // So are the example sentences
+    let source = query
+        .source_language
+        .clone()
+        .or_else(|| parse_lang(res.src_lang.as_deref()?).ok())
+        .map(|tag| tag.language);
+    let target = query.target_language.as_ref().map(|tag| tag.language);
+    if let (Some(source), Some(target)) = (source, target) {
+        if let Ok(tatoeba) = Tatoeba::installed(source, target) {
+            add_examples(&mut res, &query.query, &tatoeba);
+        }
+    }
+    // And the frequency lists
+    add_frequencies(&mut res, source, target);
+    // The words of a sentence stay in the order of the sentence
+    if query.order == DefinitionOrder::Frequency && res.tokens.is_none() {
+        if let Some(definitions) = &mut res.definitions {
+            definitions.sort_by_key(|definition| definition.frequency.unwrap_or(u32::MAX));
+        }
+    }
 
     Ok(res)
 }
 
-/// Parses a language string and return a Language Enum
+/// Looks up a word of a sentence, without the requests for the dictionary forms of jisho: the
+/// sentence has many words, and the token found is what matters
+fn lookup_token(client: &HttpClient, query: &SearchConfig) -> Result<SearchResult> {
+    match query.provider {
+        SearchProvider::Jisho => {
+            Ok(translators::jisho::lookup_jisho_with_candidates(client, query, 0)?.into())
+        }
+        _ => lookup_provider(client, query),
+    }
+}
+
+fn lookup_provider(client: &HttpClient, query: &SearchConfig) -> Result<SearchResult> {
+    Ok(match query.provider {
+        SearchProvider::GoogleTranslate => {
+            translators::google_translate::lookup_google_translate_with_client(client, query)?
+                .into()
+        }
+        SearchProvider::Jisho => {
+            translators::jisho::lookup_jisho_with_client(client, query)?.into()
+        }
+        SearchProvider::Cedict => translators::cedict::lookup_cedict(query)?.into(),
+        SearchProvider::Wordnet => translators::wordnet::lookup_wordnet(query)?.into(),
+        SearchProvider::Mdict => translators::mdict::lookup_mdict(query)?.into(),
+        SearchProvider::Yomitan => translators::yomitan::lookup_yomitan(query)?.into(),
+    })
+}
+
+/// Most words of a sentence looked up one by one, the others are looked up when opened
+const MAX_TOKENS: usize = 16;
+
+/// Most words of a sentence looked up by the online providers, one request each
+///
+/// With the lookup of the sentence and of its dictionary forms, a query sends at most
+/// `1 + MAX_CANDIDATE_LOOKUPS + MAX_TOKEN_REQUESTS` requests: 14 seconds at the rate of jisho.
+const MAX_TOKEN_REQUESTS: usize = 8;
+
+/// Longest Japanese word looked for in the dictionaries, in characters
+const MAX_WORD_LEN: usize = 12;
+
+/// Whether the dictionary found the word of the query, or the word it is inflected from
+fn has_query(result: &SearchResult, query: &str) -> bool {
+    let query = query.trim();
+    result.inflection.is_some()
+        || result
+            .definitions
+            .iter()
+            .flatten()
+            .filter_map(|definition| definition.headword.as_ref())
+            .any(|word| word.text == query || word.reading.as_deref() == Some(query))
+}
+
+/// Splits a Chinese or Japanese query into words, for the dictionary providers
+fn segment_query(query: &SearchConfig) -> Option<Vec<String>> {
+    let is_cjk = query
+        .query
+        .chars()
+        .any(|c| japanese::kanji::is_kanji(c) || kana::is_kana(c.encode_utf8(&mut [0; 4])));
+    if !is_cjk {
+        return None;
+    }
+    match query.provider {
+        SearchProvider::GoogleTranslate | SearchProvider::Wordnet | SearchProvider::Mdict => None,
+        SearchProvider::Jisho | SearchProvider::Yomitan => Some(segment_japanese(&query.query)),
+        SearchProvider::Cedict => Some(
+            translators::cedict::Cedict::installed()
+                .ok()?
+                .segment(&query.query),
+        ),
+    }
+}
+
+/// Splits a Japanese sentence over the imported Yomitan dictionaries and frequency lists, or
+/// guesses the words from the scripts when none is imported
+fn segment_japanese(text: &str) -> Vec<String> {
+    let store = Yomitan::installed()
+        .ok()
+        .filter(|store| store.dictionaries().is_ok_and(|titles| !titles.is_empty()));
+    match store {
+        Some(store) => japanese::segment::longest_match(text, MAX_WORD_LEN, |word| {
+            store.contains(word).unwrap_or(false)
+        }),
+        None => japanese::segment::words(text),
+    }
+}
+
+/// Looks up every word of the sentence, and keeps the definitions of the word found for each
+///
+/// A word that fails to be looked up is shown as not found, without failing the others.
+fn lookup_tokens(
+    client: &HttpClient,
+    query: &SearchConfig,
+    result: &mut SearchResult,
+    words: Vec<String>,
+) {
+    let max_tokens = match query.provider {
+        SearchProvider::GoogleTranslate | SearchProvider::Jisho => MAX_TOKEN_REQUESTS,
+        SearchProvider::Cedict
+        | SearchProvider::Wordnet
+        | SearchProvider::Mdict
+        | SearchProvider::Yomitan => MAX_TOKENS,
+    };
+    let mut tokens = Vec::new();
+    let mut definitions = Vec::new();
+    for (index, text) in words.into_iter().enumerate() {
+        let looked_up = index < max_tokens;
+        let found = match looked_up {
+            true => lookup_token(
+                client,
+                &SearchConfig {
+                    query: text.clone(),
+                    ..query.clone()
+                },
+            )
+            .ok()
+            .and_then(|res| res.definitions),
+            false => None,
+        };
+        let mut found = found.unwrap_or_default();
+        // The word spelled or read like the token, or else the first one, like a dictionary
+        // form of a conjugated token
+        let headword = found
+            .iter()
+            .filter_map(|definition| definition.headword.as_ref())
+            .find(|word| word.text == text || word.reading.as_ref() == Some(&text))
+            .or_else(|| {
+                found
+                    .iter()
+                    .find_map(|definition| definition.headword.as_ref())
+            })
+            .cloned();
+        if headword.is_some() {
+            found.retain(|definition| definition.headword == headword);
+            definitions.append(&mut found);
+        }
+        tokens.push(Token {
+            text,
+            headword,
+            looked_up,
+        });
+    }
+    result.definitions = (!definitions.is_empty()).then_some(definitions);
+    result.tokens = Some(tokens);
+}
+
+/// Most example sentences listed under a definition
+const MAX_EXAMPLES: usize = 3;
+
+/// Fills the examples of the definitions without any, from the sentence pairs
+///
+/// The examples use the headword, or the query for translators. The meanings given by
+/// translators are single words, and are looked for in the translations of the examples, to
+/// tell the meanings apart.
+fn add_examples(result: &mut SearchResult, query: &str, tatoeba: &Tatoeba) {
+    for definition in result.definitions.iter_mut().flatten() {
+        if definition.examples.is_some() {
+            continue;
+        }
+        let (word, translated) = match &definition.headword {
+            Some(headword) => (headword.text.as_str(), None),
+            None => (query, Some(definition.meaning.as_str())),
+        };
+        let examples: Vec<Example> = tatoeba
+            .examples(word, translated, MAX_EXAMPLES)
+            .into_iter()
+            .map(|pair| Example {
+                text: pair.text.clone(),
+                translation: Some(pair.translation.clone()),
+            })
+            .collect();
+        definition.examples = (!examples.is_empty()).then_some(examples);
+    }
+}
+
+/// Sets the frequency ranks of the definitions
+///
+/// Headwords are looked up in the imported Yomitan frequency dictionaries, then in the list of
+/// the source language. The meanings given by translators are words of the target language.
+fn add_frequencies(result: &mut SearchResult, source: Option<Language>, target: Option<Language>) {
+    let list = |language: Option<Language>| FrequencyList::installed(language?).ok();
+    let (source_list, target_list) = (list(source), list(target));
+    let yomitan = Yomitan::installed().ok();
+    for definition in result.definitions.iter_mut().flatten() {
+        definition.frequency = match &definition.headword {
+            Some(headword) => yomitan
+                .as_ref()
+                .and_then(|yomitan| {
+                    yomitan
+                        .frequency(&headword.text, headword.reading.as_deref())
+                        .ok()?
+                })
+                .or_else(|| source_list.as_ref()?.rank(&headword.text)),
+            None => target_list
+                .as_ref()
+                .and_then(|list| list.rank(&definition.meaning)),
+        };
+    }
+}
+
+/// Sets the pitch accents of the headwords found in the accent dictionary
+fn add_pitch_accents(result: &mut SearchResult, accents: &AccentDictionary) {
+    for definition in result.definitions.iter_mut().flatten() {
+        let Some(headword) = &definition.headword else {
+            continue;
+        };
+        definition.pitch_accents = accents
+            .get(&headword.text, headword.reading.as_deref())
+            .map(<[u8]>::to_vec);
+    }
+}
+
+/// The query in kana, if it is a Japanese query written in romaji
+fn romaji_query(config: &SearchConfig) -> Option<String> {
+    let is_japanese = config
+        .source_language
+        .as_ref()
+        .is_some_and(|lang| lang.language == Language::Jpn);
+    if !is_japanese || !config.query.chars().any(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    kana::romaji_to_hiragana(&config.query)
+}
+
+/// Parses a language string and return a `LanguageTag`
+///
+/// Accepts language names, ISO 639 codes, BCP 47 tags (`zh-Hant-TW`) and Unix style locales
+/// (`pt_BR.utf8`). The script and region are kept when given.
 ///
 /// Reference: [Wikipedia page](https://en.wikipedia.org/wiki/List_of_ISO_639_language_codes)
 ///
@@ -63,18 +538,52 @@ pub fn lookup(query: &SearchConfig) -> Result<SearchResult> {
 /// ```rust
 /// use wdym::search::parse_lang;
 /// use isolang::Language;
-/// assert_eq!(parse_lang("en").unwrap(), Language::Eng);
+/// assert_eq!(parse_lang("en").unwrap().language, Language::Eng);
+/// assert_eq!(parse_lang("zh-TW").unwrap().region.as_deref(), Some("TW"));
 /// ```
-pub fn parse_lang(lang: &str) -> Result<Language> {
+pub fn parse_lang(lang: &str) -> Result<LanguageTag> {
     let lang = lang.to_lowercase();
-    let res = lang
-        .parse::<Language>() // Parses ISO 639-1, 639-3 English names and autonyms
-        .or_else(|_| {
-            Language::from_locale(&lang) // Parses Unix style locale: `zh_CN.utf8`
-                .ok_or(LanguageParseError(lang.to_string()))
-        });
+    // Parses ISO 639-1, 639-3 English names and autonyms
+    if let Ok(language) = lang.parse::<Language>() {
+        return Ok(language.into());
+    }
 
-    Ok(res?)
+    Ok(parse_lang_tag(&lang).ok_or(LanguageParseError(lang.to_string()))?)
+}
+
+/// Parses BCP 47 tags and Unix style locales: `zh-Hant-TW`, `zh_CN.utf8`
+fn parse_lang_tag(lang: &str) -> Option<LanguageTag> {
+    // Drop the encoding and modifier of Unix locales
+    let lang = lang.split(['.', '@']).next()?;
+    let mut subtags = lang.split(['-', '_']);
+
+    let primary = subtags.next()?;
+    let language = match primary.len() {
+        2 => Language::from_639_1(primary),
+        3 => Language::from_639_3(primary),
+        _ => None,
+    }?;
+
+    let mut tag = LanguageTag::new(language);
+    for subtag in subtags {
+        let is_alpha = subtag.chars().all(|c| c.is_ascii_alphabetic());
+        let is_digit = subtag.chars().all(|c| c.is_ascii_digit());
+        match subtag.len() {
+            4 if is_alpha && tag.script.is_none() && tag.region.is_none() => {
+                let (first, rest) = subtag.split_at(1);
+                tag.script = Some(first.to_uppercase() + rest);
+            }
+            2 if is_alpha && tag.region.is_none() => {
+                tag.region = Some(subtag.to_uppercase());
+            }
+            3 if is_digit && tag.region.is_none() => {
+                tag.region = Some(subtag.to_string());
+            }
+            _ => return None,
+        }
+    }
+
+    Some(tag)
 }
 
 #[derive(Debug)]
@@ -95,31 +604,160 @@ mod test {
     fn test_parsing_lang() {
         // Unix-style locale
         let res = parse_lang("zh_CN.utf8").unwrap();
-        assert_eq!(res, Language::Zho);
+        assert_eq!(res.language, Language::Zho);
+        assert_eq!(res.region.as_deref(), Some("CN"));
 
-        // Non-standard locale
-        let res = parse_lang("zh_TW").unwrap(); // The implementation of isolang currently only
-                                                // reads the `zh` part, ignoring the country code.
-        assert_eq!(res, Language::Zho);
+        // Unix-style locale with a different region
+        let res = parse_lang("zh_TW").unwrap();
+        assert_eq!(res.language, Language::Zho);
+        assert_eq!(res.region.as_deref(), Some("TW"));
 
         // ISO 639-1
         let res = parse_lang("ja").unwrap();
-        assert_eq!(res, Language::Jpn);
+        assert_eq!(res, Language::Jpn.into());
 
         // ISO 639-3
         let res = parse_lang("jpn").unwrap();
-        assert_eq!(res, Language::Jpn);
+        assert_eq!(res, Language::Jpn.into());
 
         // Lowercase English name
         let res = parse_lang("german").unwrap();
-        assert_eq!(res, Language::Deu);
+        assert_eq!(res, Language::Deu.into());
 
         // Mixed case English name
         let res = parse_lang("HinDi").unwrap();
-        assert_eq!(res, Language::Hin);
+        assert_eq!(res, Language::Hin.into());
 
         // Autonym
         let res = parse_lang("עברית").unwrap();
-        assert_eq!(res, Language::Heb);
+        assert_eq!(res, Language::Heb.into());
+    }
+
+    #[test]
+    fn test_parsing_lang_tag() {
+        let res = parse_lang("zh-Hant-TW").unwrap();
+        assert_eq!(res.language, Language::Zho);
+        assert_eq!(res.script.as_deref(), Some("Hant"));
+        assert_eq!(res.region.as_deref(), Some("TW"));
+        assert_eq!(res.to_string(), "zh-Hant-TW");
+
+        let res = parse_lang("pt-br").unwrap();
+        assert_eq!(res.to_string(), "pt-BR");
+        assert_eq!(res.name(), "Portuguese (BR)");
+
+        let res = parse_lang("es-419").unwrap();
+        assert_eq!(res.region.as_deref(), Some("419"));
+
+        assert!(parse_lang("zh-TW-CN").is_err());
+        assert!(parse_lang("xx-TW").is_err());
+    }
+
+    #[test]
+    fn romaji_queries() {
+        let config = |query: &str, source_language: Option<Language>| SearchConfig {
+            query: query.to_string(),
+            source_language: source_language.map(LanguageTag::from),
+            ..Default::default()
+        };
+        assert_eq!(
+            romaji_query(&config("taberu", Some(Language::Jpn))).as_deref(),
+            Some("たべる")
+        );
+        assert_eq!(romaji_query(&config("taberu", None)), None);
+        assert_eq!(romaji_query(&config("book", Some(Language::Jpn))), None);
+        assert_eq!(romaji_query(&config("たべる", Some(Language::Jpn))), None);
+    }
+
+    #[test]
+    fn sentence_request_budget() {
+        #[derive(Debug, Default)]
+        struct EmptyJisho(std::sync::atomic::AtomicUsize);
+
+        impl crate::http::Transport for EmptyJisho {
+            fn get(&self, url: &reqwest::Url) -> Result<crate::http::Response> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(crate::http::Response {
+                    status: reqwest::StatusCode::OK,
+                    url: url.clone(),
+                    headers: Default::default(),
+                    body: br#"{"meta": {"status": 200}, "data": []}"#.to_vec(),
+                })
+            }
+        }
+
+        let transport = std::sync::Arc::new(EmptyJisho::default());
+        let client = HttpClient::with_transport(Default::default(), transport.clone());
+        // Conjugated words, which have dictionary forms to try
+        let config = SearchConfig {
+            query: "猫が食べた。犬が見た。鳥が飛んだ。魚が泳いだ。虫が鳴いた。".to_string(),
+            provider: SearchProvider::Jisho,
+            ..Default::default()
+        };
+        let result = lookup_with_client(&client, &config).unwrap();
+
+        let tokens = result.tokens.unwrap();
+        assert!(tokens.len() > MAX_TOKEN_REQUESTS);
+        assert!(tokens[..MAX_TOKEN_REQUESTS]
+            .iter()
+            .all(|token| token.looked_up));
+        assert!(!tokens[MAX_TOKEN_REQUESTS].looked_up);
+        let requests = transport.0.load(std::sync::atomic::Ordering::SeqCst);
+        assert!(requests <= 1 + translators::jisho::MAX_CANDIDATE_LOOKUPS + MAX_TOKEN_REQUESTS);
+    }
+
+    #[test]
+    fn sentence_queries() {
+        // A synthetic fixture until recorded, see `tests/cassettes/README.md`
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/search/sentence_lookup.json"
+        );
+        let client = HttpClient::with_transport(
+            Default::default(),
+            std::sync::Arc::new(
+                crate::http::cassette::CassetteTransport::replay_or_record(path).unwrap(),
+            ),
+        );
+        let config = SearchConfig {
+            query: "私は学生".to_string(),
+            provider: SearchProvider::Jisho,
+            ..Default::default()
+        };
+        let result = lookup_with_client(&client, &config).unwrap();
+
+        let tokens = result.tokens.unwrap();
+        assert!(tokens.iter().all(|token| token.looked_up));
+        let words: Vec<(&str, Option<&str>)> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.text.as_str(),
+                    token.headword.as_ref().map(|word| word.text.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                ("私", Some("私")),
+                ("は", Some("は")),
+                ("学生", Some("学生"))
+            ]
+        );
+        // Only the definitions of the word found for each token
+        let meanings: Vec<String> = result
+            .definitions
+            .unwrap()
+            .into_iter()
+            .map(|definition| definition.meaning)
+            .collect();
+        assert_eq!(
+            meanings,
+            vec![
+                "I; me",
+                "indicates sentence topic",
+                "student (esp. a university student)"
+            ]
+        );
     }
 }
@@ -0,0 +1,263 @@
+//! Example sentences from Tatoeba, to show how words are used
+//!
+//! Tatoeba is not shipped with wdym. Download the sentence pairs of two languages from
+//! <https://tatoeba.org/downloads>, and save them in the data directory, see [`crate::data`], as
+//! `tatoeba-<source>-<target>.tsv`, with the ISO 639-3 codes of the languages:
+//! `tatoeba-eng-jpn.tsv`. The pairs of the other direction are used too, so `tatoeba-jpn-eng.tsv`
+//! gives English examples translated to Japanese as well.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::tatoeba::Tatoeba;
+//!
+//! let pairs = "1\tI have a dog.\t2\t犬を飼っています。\n\
+//!     3\tThe dog is sleeping on the sofa.\t4\t犬はソファーで寝ている。\n";
+//! let tatoeba = Tatoeba::parse(pairs.as_bytes(), false).unwrap();
+//! let examples = tatoeba.examples("dog", None, 1);
+//! assert_eq!(examples[0].text, "I have a dog.");
+//! ```
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use color_eyre::Result;
+use isolang::Language;
+
+use crate::data::{self, MissingDataError};
+use crate::japanese::{kana, kanji};
+
+/// A sentence with its translation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentencePair {
+    pub text: String,
+    pub translation: String,
+}
+
+/// Sentence pairs of two languages
+#[derive(Debug, Default)]
+pub struct Tatoeba {
+    pairs: Vec<SentencePair>,
+    /// The sentence and translation of every pair in lowercase, to match words ignoring the case
+    lowercase: Vec<(String, String)>,
+    /// The pairs whose sentence has the key, in order, see [`index_keys`]
+    index: HashMap<String, Vec<usize>>,
+}
+
+/// Source and target language of sentence pairs
+type LanguagePair = (Language, Language);
+
+/// The sentence pairs loaded from the data directory
+static LOADED: LazyLock<Mutex<HashMap<LanguagePair, Arc<Tatoeba>>>> =
+    LazyLock::new(Default::default);
+
+impl Tatoeba {
+    /// Reads the lines of `id`, `sentence`, `translation id` and `translation`
+    ///
+    /// When `reversed`, the translations are read as the sentences, and the other way around.
+    pub fn parse(input: impl BufRead, reversed: bool) -> Result<Self> {
+        let mut pairs = Vec::new();
+        for line in input.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [_, text, _, translation] = fields[..] else {
+                continue;
+            };
+            let (text, translation) = match reversed {
+                true => (translation, text),
+                false => (text, translation),
+            };
+            pairs.push(SentencePair {
+                text: text.to_string(),
+                translation: translation.to_string(),
+            });
+        }
+        Ok(Self::new(pairs))
+    }
+
+    fn new(pairs: Vec<SentencePair>) -> Self {
+        let lowercase = pairs
+            .iter()
+            .map(|pair| (pair.text.to_lowercase(), pair.translation.to_lowercase()))
+            .collect();
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (id, pair) in pairs.iter().enumerate() {
+            for key in index_keys(&pair.text, false) {
+                let ids = index.entry(key).or_default();
+                if ids.last() != Some(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        Self {
+            pairs,
+            lowercase,
+            index,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>, reversed: bool) -> Result<Self> {
+        Self::parse(data::open(path.as_ref())?, reversed)
+    }
+
+    /// The pairs of sentences in `source` translated to `target` in the data directory, loaded on
+    /// first use
+    pub fn installed(source: Language, target: Language) -> Result<Arc<Self>> {
+        let mut loaded = LOADED.lock().expect("Lock poisoned");
+        if let Some(tatoeba) = loaded.get(&(source, target)) {
+            return Ok(Arc::clone(tatoeba));
+        }
+        let names = |source: Language, target: Language| {
+            let name = format!("tatoeba-{}-{}.tsv", source.to_639_3(), target.to_639_3());
+            [format!("{}.gz", name), name]
+        };
+        let (forward, backward) = (names(source, target), names(target, source));
+        let find = |names: &[String; 2]| {
+            data::find(&names.iter().map(String::as_str).collect::<Vec<&str>>())
+        };
+        let tatoeba = match (find(&forward), find(&backward)) {
+            (Some(path), _) => Self::load(path, false)?,
+            (None, Some(path)) => Self::load(path, true)?,
+            (None, None) => Err(MissingDataError {
+                name: format!(
+                    "Tatoeba sentences in {} and {}",
+                    source.to_name(),
+                    target.to_name()
+                ),
+                files: forward.into_iter().chain(backward).collect(),
+            })?,
+        };
+        let tatoeba = Arc::new(tatoeba);
+        loaded.insert((source, target), Arc::clone(&tatoeba));
+        Ok(tatoeba)
+    }
+
+    /// Finds the shortest sentences using the word, at most `limit` of them
+    ///
+    /// With a `translated` word, only the sentences whose translation uses it are kept, to find
+    /// examples of one meaning of the word.
+    pub fn examples(
+        &self,
+        word: &str,
+        translated: Option<&str>,
+        limit: usize,
+    ) -> Vec<&SentencePair> {
+        let word = word.trim().to_lowercase();
+        let translated = translated.map(|translated| translated.trim().to_lowercase());
+        // The pairs with the least common key of the word, which are checked for the whole word
+        let candidates = index_keys(&word, is_cjk(&word))
+            .map(|key| self.index.get(&key).map_or(&[][..], Vec::as_slice))
+            .min_by_key(|ids| ids.len())
+            .unwrap_or_default();
+        let mut examples: Vec<&SentencePair> = candidates
+            .iter()
+            .filter(|&&id| {
+                let (text, translation) = &self.lowercase[id];
+                contains_word(text, &word)
+                    && translated
+                        .as_ref()
+                        .is_none_or(|translated| contains_word(translation, translated))
+            })
+            .map(|&id| &self.pairs[id])
+            .collect();
+        examples.sort_by_key(|pair| pair.text.chars().count());
+        examples.truncate(limit);
+        examples
+    }
+}
+
+fn is_cjk_char(c: char) -> bool {
+    kanji::is_kanji(c) || kana::is_kana(c.encode_utf8(&mut [0; 4]))
+}
+
+fn is_cjk(text: &str) -> bool {
+    text.chars().any(is_cjk_char)
+}
+
+/// The keys of the index in the text: its words in lowercase, and each of its kanji and kana
+///
+/// With `cjk_only`, only the kanji and kana are listed, as a word with them is found anywhere.
+fn index_keys(text: &str, cjk_only: bool) -> impl Iterator<Item = String> + '_ {
+    text.split(move |c: char| !c.is_alphanumeric() || is_cjk_char(c))
+        .filter(move |word| !cjk_only && !word.is_empty())
+        .map(str::to_lowercase)
+        .chain(text.chars().filter(|&c| is_cjk_char(c)).map(String::from))
+}
+
+/// Whether the word is in the sentence, both in lowercase
+///
+/// Words with kanji or kana are found anywhere, as Chinese and Japanese are written without
+/// spaces. Other words have to be whole: `cat` isn't found in `category`.
+fn contains_word(sentence: &str, word: &str) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+    if is_cjk(word) {
+        return sentence.contains(word);
+    }
+
+    sentence.match_indices(word).any(|(start, _)| {
+        let before = sentence[..start].chars().next_back();
+        let after = sentence[start + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PAIRS: &str = "\
+1\tI have a dog.\t2\t犬を飼っています。
+3\tMy dog doesn't like cats, and my cat doesn't like dogs.\t4\t私の犬は猫が嫌いで、私の猫は犬が嫌いだ。
+5\tThis is a category.\t6\tこれはカテゴリーです。
+7\tThe cat is on the table.\t8\t猫はテーブルの上にいます。
+9\tbroken line
+";
+
+    #[test]
+    fn find_examples() {
+        let tatoeba = Tatoeba::parse(PAIRS.as_bytes(), false).unwrap();
+        let texts = |examples: Vec<&SentencePair>| -> Vec<String> {
+            examples.iter().map(|pair| pair.text.clone()).collect()
+        };
+        // Shortest first, and whole words only
+        assert_eq!(
+            texts(tatoeba.examples("Cat", None, 5)),
+            vec![
+                "The cat is on the table.",
+                "My dog doesn't like cats, and my cat doesn't like dogs."
+            ]
+        );
+        assert_eq!(
+            texts(tatoeba.examples("dog", None, 1)),
+            vec!["I have a dog."]
+        );
+        // Only the sentences translated with the word
+        assert_eq!(
+            texts(tatoeba.examples("cat", Some("テーブル"), 5)),
+            vec!["The cat is on the table."]
+        );
+        // Phrases, and words not in any sentence
+        assert_eq!(
+            texts(tatoeba.examples("ON THE table", None, 5)),
+            vec!["The cat is on the table."]
+        );
+        assert!(tatoeba.examples("bird", None, 5).is_empty());
+        assert!(tatoeba.examples(" ", None, 5).is_empty());
+    }
+
+    #[test]
+    fn reversed_pairs() {
+        let tatoeba = Tatoeba::parse(PAIRS.as_bytes(), true).unwrap();
+        let examples = tatoeba.examples("犬", None, 5);
+        assert_eq!(examples.len(), 2);
+        assert_eq!(examples[0].text, "犬を飼っています。");
+        assert_eq!(examples[0].translation, "I have a dog.");
+        assert_eq!(
+            tatoeba.examples("猫が嫌い", None, 5)[0].text,
+            "私の犬は猫が嫌いで、私の猫は犬が嫌いだ。"
+        );
+        assert!(tatoeba.examples("犬が好き", None, 5).is_empty());
+    }
+}
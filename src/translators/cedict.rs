@@ -0,0 +1,474 @@
+//! The CC-CEDICT Chinese-English dictionary, looked up offline
+//!
+//! CC-CEDICT is not shipped with wdym. Download `cedict_1_0_ts_utf-8_mdbg.txt.gz` from
+//! <https://www.mdbg.net/chinese/dictionary?page=cedict>, and put it in the data directory, see
+//! [`crate::data`].
+//!
+//! Words are found by their traditional or simplified spelling, by their pinyin with or without
+//! tones, or by their English meanings. Traditional spellings come first when the source or
+//! target language is traditional Chinese, like `zh-TW` or `zh-Hant`.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::search;
+//! use wdym::translators::cedict::Cedict;
+//! use wdym::translators::SearchProvider;
+//!
+//! let cedict = Cedict::parse("電腦 电脑 [dian4 nao3] /computer/CL:臺|台[tai2]/\n".as_bytes())
+//!     .unwrap();
+//! let search_options = search::SearchConfig {
+//!     query: "diannao".to_string(),
+//!     provider: SearchProvider::Cedict,
+//!     ..Default::default()
+//! };
+//! let result = cedict.search(&search_options);
+//! assert!(result.to_string().starts_with("电脑（電腦） [diàn nǎo]"));
+//! let result: search::SearchResult = result.into();
+//! assert_eq!(&result.src_lang.unwrap(), "zh");
+//! ```
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+use isolang::Language;
+
+use crate::chinese::{pinyin, segment};
+use crate::data::{self, MissingDataError};
+use crate::search;
+
+use super::SearchProvider;
+
+/// A word of the dictionary
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    traditional: String,
+    simplified: String,
+    /// Syllables with tone numbers, separated by spaces: `dian4 nao3`
+    pinyin: String,
+    definitions: Vec<String>,
+}
+
+impl Entry {
+    /// Reads a line like `電腦 电脑 [dian4 nao3] /computer/`
+    fn parse(line: &str) -> Option<Self> {
+        let (words, rest) = line.split_once(" [")?;
+        let (traditional, simplified) = words.split_once(' ')?;
+        let (pinyin, definitions) = rest.split_once("] /")?;
+        Some(Self {
+            traditional: traditional.to_string(),
+            simplified: simplified.to_string(),
+            pinyin: pinyin.to_string(),
+            definitions: definitions
+                .trim_end()
+                .trim_end_matches('/')
+                .split('/')
+                .filter(|definition| !definition.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+
+    /// The spelling to show first, and the other one if it is different
+    fn spellings(&self, traditional_first: bool) -> (&str, Option<&str>) {
+        let (first, second) = match traditional_first {
+            true => (&self.traditional, &self.simplified),
+            false => (&self.simplified, &self.traditional),
+        };
+        (first, (first != second).then_some(second.as_str()))
+    }
+}
+
+/// Keys of the index, to tell the ways to find a word apart
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Chinese(String),
+    /// Pinyin with tone numbers, without spaces
+    Pinyin(String),
+    /// Pinyin without tones or spaces
+    Toneless(String),
+    English(String),
+}
+
+impl Key {
+    fn pinyin(text: &str) -> Self {
+        Key::Pinyin(
+            text.to_lowercase()
+                .replace("u:", "v")
+                .replace('ü', "v")
+                .replace([' ', '\''], ""),
+        )
+    }
+
+    fn toneless(text: &str) -> Self {
+        Key::Toneless(
+            pinyin::strip_tones(text)
+                .replace('ü', "v")
+                .replace([' ', '\''], ""),
+        )
+    }
+
+    /// Meanings are found without their notes in brackets, and verbs without their `to`
+    fn english(text: &str) -> Self {
+        let mut meaning = String::new();
+        let mut depth = 0;
+        for c in text.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if depth == 0 => meaning.push(c),
+                _ => {}
+            }
+        }
+        let meaning = meaning.trim().to_lowercase();
+        Key::English(
+            meaning
+                .strip_prefix("to ")
+                .unwrap_or(&meaning)
+                .trim()
+                .to_string(),
+        )
+    }
+}
+
+/// The words of CC-CEDICT, indexed by spelling, pinyin and meaning
+#[derive(Debug, Default)]
+pub struct Cedict {
+    entries: Vec<Entry>,
+    index: HashMap<Key, Vec<usize>>,
+    /// Characters of the longest word
+    max_word_len: usize,
+}
+
+/// Names of the CC-CEDICT file in the data directory
+const CEDICT_FILES: &[&str] = &["cedict_ts.u8", "cedict_1_0_ts_utf-8_mdbg.txt.gz"];
+
+/// The dictionary loaded from the data directory
+static DEFAULT: Mutex<Option<Arc<Cedict>>> = Mutex::new(None);
+
+/// Most entries returned for one query
+const MAX_ENTRIES: usize = 20;
+
+impl Cedict {
+    /// Reads the CC-CEDICT text format, skipping comments and malformed lines
+    pub fn parse(input: impl BufRead) -> Result<Self> {
+        let mut cedict = Self::default();
+        for line in input.lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some(entry) = Entry::parse(&line) else {
+                continue;
+            };
+            let index = cedict.entries.len();
+            let mut keys = vec![
+                Key::Chinese(entry.traditional.clone()),
+                Key::Chinese(entry.simplified.clone()),
+                Key::pinyin(&entry.pinyin),
+                Key::toneless(&entry.pinyin),
+            ];
+            keys.extend(entry.definitions.iter().map(|def| Key::english(def)));
+            for key in keys {
+                let entries = cedict.index.entry(key).or_default();
+                if entries.last() != Some(&index) {
+                    entries.push(index);
+                }
+            }
+            cedict.max_word_len = cedict.max_word_len.max(entry.traditional.chars().count());
+            cedict.entries.push(entry);
+        }
+        Ok(cedict)
+    }
+
+    /// Reads a CC-CEDICT file, which may be compressed with gzip
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(data::open(path.as_ref())?)
+    }
+
+    /// The dictionary in the data directory, loaded on first use
+    pub fn installed() -> Result<Arc<Self>> {
+        let mut default = DEFAULT.lock().expect("Lock poisoned");
+        if let Some(cedict) = default.as_ref() {
+            return Ok(Arc::clone(cedict));
+        }
+        let path = data::find(CEDICT_FILES).ok_or_else(|| MissingDataError {
+            name: "CC-CEDICT".to_string(),
+            files: CEDICT_FILES.iter().map(|file| file.to_string()).collect(),
+        })?;
+        let cedict = Arc::new(Self::load(path)?);
+        *default = Some(Arc::clone(&cedict));
+        Ok(cedict)
+    }
+
+    /// Whether the text is a word of the dictionary, in either script
+    pub fn contains(&self, word: &str) -> bool {
+        self.index.contains_key(&Key::Chinese(word.to_string()))
+    }
+
+    /// Splits the sentence into the longest words of the dictionary
+    pub fn segment(&self, text: &str) -> Vec<String> {
+        segment::longest_match(text, self.max_word_len, |word| self.contains(word))
+    }
+
+    /// Finds the words spelled, read or meaning like the query
+    pub fn search(&self, search_options: &search::SearchConfig) -> SearchResult {
+        let query = search_options.query.trim();
+        let is_chinese = query.chars().any(crate::japanese::kanji::is_kanji);
+        let mut keys = Vec::new();
+        if is_chinese {
+            keys.push(Key::Chinese(query.to_string()));
+        } else {
+            if pinyin::is_pinyin(query) {
+                keys.push(match query.chars().any(|c| c.is_ascii_digit()) {
+                    true => Key::pinyin(query),
+                    false => Key::toneless(query),
+                });
+            }
+            keys.push(Key::english(query));
+        }
+
+        let mut found: Vec<usize> = Vec::new();
+        let mut is_pinyin = false;
+        for key in &keys {
+            for index in self.index.get(key).into_iter().flatten() {
+                is_pinyin |= matches!(key, Key::Pinyin(_) | Key::Toneless(_));
+                if !found.contains(index) {
+                    found.push(*index);
+                }
+            }
+        }
+        SearchResult {
+            entries: found
+                .into_iter()
+                .take(MAX_ENTRIES)
+                .map(|index| self.entries[index].clone())
+                .collect(),
+            is_chinese: is_chinese || is_pinyin,
+            traditional_first: prefers_traditional(search_options),
+        }
+    }
+}
+
+/// Whether the source or target language is written in traditional characters
+fn prefers_traditional(search_options: &search::SearchConfig) -> bool {
+    [
+        &search_options.source_language,
+        &search_options.target_language,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|lang| {
+        lang.language == Language::Zho
+            && (lang.script.as_deref() == Some("Hant")
+                || matches!(lang.region.as_deref(), Some("TW" | "HK" | "MO")))
+    })
+}
+
+/// The search result of CC-CEDICT
+///
+/// Can be converted to `search::SearchResult`, using `from` or `into`
+pub struct SearchResult {
+    entries: Vec<Entry>,
+    /// Whether the query is in Chinese characters or in pinyin
+    is_chinese: bool,
+    traditional_first: bool,
+}
+
+impl From<SearchResult> for search::SearchResult {
+    fn from(value: SearchResult) -> Self {
+        let definitions: Vec<search::Definition> = value
+            .entries
+            .iter()
+            .flat_map(|entry| {
+                let (text, other) = entry.spellings(value.traditional_first);
+                let headword = search::Word {
+                    text: text.to_string(),
+                    reading: Some(pinyin::to_tone_marks(&entry.pinyin)),
+                };
+                entry
+                    .definitions
+                    .iter()
+                    .map(move |definition| search::Definition {
+                        headword: Some(headword.clone()),
+                        pitch_accents: None,
+                        // Links the traditional and simplified spellings of the word
+                        other_spellings: other.map(|other| vec![other.to_string()]),
+                        meaning: definition.clone(),
+                        rich_meaning: None,
+                        pos: String::new(),
+                        reverse_translation: None,
+                        confidence: None,
+                        frequency: None,
+                        examples: None,
+                        relations: None,
+                    })
+            })
+            .collect();
+        let reading = value
+            .entries
+            .first()
+            .map(|entry| pinyin::to_tone_marks(&entry.pinyin));
+        let src_lang = match (value.is_chinese, value.traditional_first) {
+            (true, true) => "zh-TW",
+            (true, false) => "zh",
+            (false, _) => "en",
+        };
+
+        search::SearchResult {
+            provider: SearchProvider::Cedict,
+            translations: None,
+            definitions: (!definitions.is_empty()).then_some(definitions),
+            src_lang: Some(src_lang.to_string()),
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: reading.map(|reading| match value.is_chinese {
+                true => search::Literation {
+                    orig: Some(reading),
+                    translated: None,
+                },
+                false => search::Literation {
+                    orig: None,
+                    translated: Some(reading),
+                },
+            }),
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: None,
+            tokens: None,
+            warnings: None,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            let (text, other) = entry.spellings(self.traditional_first);
+            write!(f, "{}", text)?;
+            if let Some(other) = other {
+                write!(f, "（{}）", other)?;
+            }
+            writeln!(f, " [{}]", pinyin::to_tone_marks(&entry.pinyin))?;
+            for definition in &entry.definitions {
+                writeln!(f, "\t{}", definition)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Looks up the query in the installed CC-CEDICT
+pub fn lookup_cedict(search_options: &search::SearchConfig) -> Result<SearchResult> {
+    Ok(Cedict::installed()?.search(search_options))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::{parse_lang, SearchConfig};
+
+    const CEDICT: &str = "\
+# CC-CEDICT
+# Community maintained free Chinese-English dictionary.
+電腦 电脑 [dian4 nao3] /computer/CL:臺|台[tai2]/
+學生 学生 [xue2 sheng5] /student/schoolchild/
+吃 吃 [chi1] /to eat/to consume/
+吃飯 吃饭 [chi1 fan4] /to have a meal/to eat (lit. to eat rice)/
+女兒 女儿 [nu:3 er2] /daughter/
+malformed line
+";
+
+    fn search(query: &str, lang: Option<&str>) -> String {
+        let cedict = Cedict::parse(CEDICT.as_bytes()).unwrap();
+        cedict
+            .search(&SearchConfig {
+                query: query.to_string(),
+                source_language: lang.map(|lang| parse_lang(lang).unwrap()),
+                provider: SearchProvider::Cedict,
+                ..Default::default()
+            })
+            .to_string()
+    }
+
+    #[test]
+    fn parse_cedict() {
+        let cedict = Cedict::parse(CEDICT.as_bytes()).unwrap();
+        assert_eq!(cedict.entries.len(), 5);
+        assert_eq!(
+            cedict.entries[0],
+            Entry {
+                traditional: "電腦".to_string(),
+                simplified: "电脑".to_string(),
+                pinyin: "dian4 nao3".to_string(),
+                definitions: vec!["computer".to_string(), "CL:臺|台[tai2]".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn lookup_by_spelling() {
+        let expected = "电脑（電腦） [diàn nǎo]\n\tcomputer\n\tCL:臺|台[tai2]\n";
+        assert_eq!(search("電腦", None), expected);
+        assert_eq!(search("电脑", None), expected);
+        // Traditional first for Taiwan
+        assert_eq!(
+            search("电脑", Some("zh_TW")),
+            "電腦（电脑） [diàn nǎo]\n\tcomputer\n\tCL:臺|台[tai2]\n"
+        );
+        assert_eq!(search("吃", None), "吃 [chī]\n\tto eat\n\tto consume\n");
+    }
+
+    #[test]
+    fn lookup_by_pinyin() {
+        assert_eq!(
+            search("xue2sheng5", None),
+            "学生（學生） [xué sheng]\n\tstudent\n\tschoolchild\n"
+        );
+        assert_eq!(search("xue sheng", None), search("xue2sheng5", None));
+        assert_eq!(search("xuéshēng", None), search("xue2sheng5", None));
+        assert_eq!(search("xue1sheng1", None), "");
+        assert_eq!(search("nü er", None), "女儿（女兒） [nǚ ér]\n\tdaughter\n");
+        assert_eq!(search("nv3er2", None), search("nü er", None));
+    }
+
+    #[test]
+    fn segment_sentences() {
+        let cedict = Cedict::parse(CEDICT.as_bytes()).unwrap();
+        assert_eq!(cedict.segment("學生吃飯。"), vec!["學生", "吃飯"]);
+        assert_eq!(cedict.segment("女儿吃电脑"), vec!["女儿", "吃", "电脑"]);
+    }
+
+    #[test]
+    fn lookup_by_meaning() {
+        // Both words mean to eat, and are listed in the order of the dictionary
+        let res = search("eat", None);
+        assert!(res.starts_with("吃 [chī]"));
+        assert!(res.contains("吃饭（吃飯） [chī fàn]"));
+        assert_eq!(
+            search("Daughter", None),
+            "女儿（女兒） [nǚ ér]\n\tdaughter\n"
+        );
+
+        let cedict = Cedict::parse(CEDICT.as_bytes()).unwrap();
+        let result: search::SearchResult = cedict
+            .search(&SearchConfig {
+                query: "computer".to_string(),
+                ..Default::default()
+            })
+            .into();
+        assert_eq!(result.src_lang.as_deref(), Some("en"));
+        assert_eq!(
+            result.literation.unwrap().translated.as_deref(),
+            Some("diàn nǎo")
+        );
+        let definitions = result.definitions.unwrap();
+        assert_eq!(
+            definitions[0].other_spellings,
+            Some(vec!["電腦".to_string()])
+        );
+    }
+}
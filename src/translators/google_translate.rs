@@ -2,24 +2,41 @@
 //!
 //! # Examples
 //! ```rust
-//! use wdym::translators::google_translate::lookup_google_translate;
+//! use wdym::http::HttpClient;
+//! use wdym::translators::google_translate::lookup_google_translate_with_client;
 //! use wdym::search;
 //! use wdym::translators::SearchProvider;
 //!
+//! let client = HttpClient::default();
+//! # let client = wdym::http::HttpClient::with_transport(
+//! #     Default::default(),
+//! #     std::sync::Arc::new(
+//! #         wdym::http::cassette::CassetteTransport::replay_or_record(concat!(
+//! #             env!("CARGO_MANIFEST_DIR"),
+//! #             "/tests/cassettes/google_translate/book.json"
+//! #         ))
+//! #         .unwrap(),
+//! #     ),
+//! # );
 //! let search_options  = search::SearchConfig {
 //!            query: "Book".to_string(),
-//!            source_language: Some(isolang::Language::Eng),
-//!            target_language: Some(isolang::Language::Jpn),
+//!            source_language: Some(isolang::Language::Eng.into()),
+//!            target_language: Some(isolang::Language::Jpn.into()),
 //!            provider: SearchProvider::GoogleTranslate,
+//!            ..Default::default()
 //! };
-//! let result: search::SearchResult = lookup_google_translate(&search_options).unwrap().into();
+//! let result: search::SearchResult = lookup_google_translate_with_client(&client, &search_options)
+//!     .unwrap()
+//!     .into();
 //! assert_eq!(&result.src_lang.unwrap(), "en");
 //! ```
+use crate::http::{HttpClient, RateLimit, Response};
 use crate::search;
 
-use super::TranslateError;
+use super::{RateLimitedError, SearchProvider, TranslateError};
 
 use color_eyre::Result;
+use isolang::Language;
 use serde::Deserialize;
 
 #[allow(dead_code)]
@@ -29,8 +46,58 @@ struct HttpResponse {
     sentences: Option<Vec<Option<HttpResponseSentence>>>,
     src: String,
     confidence: Option<f32>,
-    spell: Option<serde_json::Value>,
-    ld_result: Option<serde_json::Value>,
+    spell: Option<HttpResponseSpell>,
+    ld_result: Option<HttpResponseLdResult>,
+    alternative_translations: Option<Vec<HttpResponseAlternative>>,
+    synsets: Option<Vec<HttpResponseSynset>>,
+    related_words: Option<HttpResponseRelatedWords>,
+}
+
+/// Spelling correction of the query, empty if the query is spelled correctly
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct HttpResponseSpell {
+    spell_res: Option<String>,
+    spell_html_res: Option<String>,
+}
+
+/// Result of the source language detection
+#[derive(Deserialize, Debug)]
+struct HttpResponseLdResult {
+    srclangs: Option<Vec<String>>,
+    srclangs_confidences: Option<Vec<f32>>,
+    extended_srclangs: Option<Vec<String>>,
+}
+
+/// Alternate translations of a segment of the query
+#[derive(Deserialize, Debug)]
+struct HttpResponseAlternative {
+    src_phrase: Option<String>,
+    alternative: Option<Vec<HttpResponseAlternativeWord>>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct HttpResponseAlternativeWord {
+    word_postproc: String,
+    score: Option<f32>,
+}
+
+/// Synonyms of the query, grouped by part of speech
+#[derive(Deserialize, Debug)]
+struct HttpResponseSynset {
+    pos: String,
+    entry: Vec<HttpResponseSynsetEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HttpResponseSynsetEntry {
+    synonym: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HttpResponseRelatedWords {
+    word: Vec<String>,
 }
 
 /// A sentence may have some of the following.
@@ -84,6 +151,43 @@ pub struct SearchResult {
     src_translit: Option<String>,
     translit: Option<String>,
     src_lang: String,
+    src_lang_confidence: Option<f32>,
+    src_lang_candidates: Option<Vec<(String, Option<f32>)>>,
+    spelling_suggestion: Option<String>,
+    alternatives: Option<Vec<(String, Vec<String>)>>,
+    synonyms: Option<Vec<(String, Vec<String>)>>,
+    related_words: Option<Vec<String>>,
+}
+
+impl SearchResult {
+    /// Appends the result of the next chunk of the query
+    fn append(&mut self, next: SearchResult) {
+        fn append_vec<T>(this: &mut Option<Vec<T>>, next: Option<Vec<T>>) {
+            match (this.as_mut(), next) {
+                (Some(this), Some(mut next)) => this.append(&mut next),
+                (None, next) => *this = next,
+                _ => {}
+            }
+        }
+        fn append_text(this: &mut Option<String>, next: Option<String>) {
+            match (this.as_mut(), next) {
+                (Some(this), Some(next)) => {
+                    this.push(' ');
+                    this.push_str(&next);
+                }
+                (None, next) => *this = next,
+                _ => {}
+            }
+        }
+
+        append_vec(&mut self.dicts, next.dicts);
+        append_vec(&mut self.sentence_translation, next.sentence_translation);
+        append_text(&mut self.src_translit, next.src_translit);
+        append_text(&mut self.translit, next.translit);
+        append_vec(&mut self.alternatives, next.alternatives);
+        append_vec(&mut self.synonyms, next.synonyms);
+        append_vec(&mut self.related_words, next.related_words);
+    }
 }
 
 impl From<&(String, String)> for crate::search::Translation {
@@ -105,18 +209,26 @@ impl From<SearchResult> for crate::search::SearchResult {
                     let pos: String = dict_pos.pos;
                     for entry in dict_pos.entry {
                         res.push(crate::search::Definition {
+                            headword: None,
+                            pitch_accents: None,
+                            other_spellings: None,
                             meaning: entry.word,
+                            rich_meaning: None,
                             pos: pos.clone(),
                             reverse_translation: Some(entry.reverse_translation),
                             confidence: entry.score,
+                            frequency: None,
                             examples: None,
+                            relations: None,
                         });
                     }
                 }
-                res.sort_by(|a, b| {
-                    b.confidence
-                        .unwrap_or(0.0)
-                        .total_cmp(&a.confidence.unwrap_or(0.0))
+                // Entries without a score go last, in the order they were given
+                res.sort_by(|a, b| match (a.confidence, b.confidence) {
+                    (Some(a), Some(b)) => b.total_cmp(&a),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
                 });
                 Some(res)
             }
@@ -130,7 +242,23 @@ impl From<SearchResult> for crate::search::SearchResult {
                     .map(|sentence_pair| sentence_pair.into())
                     .collect()
             }),
-            src_lang: Some(value.src_lang),
+            src_lang: Some(
+                parse_language_code(&value.src_lang)
+                    .map(|lang| lang.to_string())
+                    .unwrap_or(value.src_lang),
+            ),
+            src_lang_confidence: value.src_lang_confidence,
+            src_lang_candidates: value.src_lang_candidates.map(|candidates| {
+                candidates
+                    .into_iter()
+                    .filter_map(|(code, confidence)| {
+                        Some(crate::search::LanguageCandidate {
+                            lang: parse_language_code(&code)?,
+                            confidence,
+                        })
+                    })
+                    .collect()
+            }),
             literation: if value.src_translit.is_some() && value.translit.is_some() {
                 Some(crate::search::Literation {
                     orig: value.src_translit,
@@ -140,6 +268,23 @@ impl From<SearchResult> for crate::search::SearchResult {
                 None
             },
             definitions,
+            spelling_suggestion: value.spelling_suggestion,
+            alternatives: value.alternatives.map(|alternatives| {
+                alternatives
+                    .into_iter()
+                    .map(|(orig, alternatives)| crate::search::Alternative { orig, alternatives })
+                    .collect()
+            }),
+            synonyms: value.synonyms.map(|synonyms| {
+                synonyms
+                    .into_iter()
+                    .map(|(pos, words)| crate::search::Synonym { pos, words })
+                    .collect()
+            }),
+            related_words: value.related_words,
+            inflection: None,
+            tokens: None,
+            warnings: None,
         }
     }
 }
@@ -182,6 +327,61 @@ impl TryFrom<HttpResponse> for SearchResult {
             src_translit,
             translit,
             src_lang: value.src,
+            src_lang_confidence: value.confidence,
+            src_lang_candidates: value.ld_result.map(|ld_result| {
+                let mut candidates: Vec<(String, Option<f32>)> = ld_result
+                    .srclangs
+                    .unwrap_or_default()
+                    .into_iter()
+                    .zip(
+                        ld_result
+                            .srclangs_confidences
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(Some)
+                            .chain(std::iter::repeat(None)),
+                    )
+                    .collect();
+                // Extended candidates are ranked lower, and come without a confidence
+                for lang in ld_result.extended_srclangs.unwrap_or_default() {
+                    if !candidates.iter().any(|(candidate, _)| *candidate == lang) {
+                        candidates.push((lang, None));
+                    }
+                }
+                candidates
+            }),
+            spelling_suggestion: value
+                .spell
+                .and_then(|spell| spell.spell_res)
+                .filter(|suggestion| !suggestion.is_empty()),
+            alternatives: value.alternative_translations.map(|alternatives| {
+                alternatives
+                    .into_iter()
+                    .filter_map(|alternative| {
+                        Some((
+                            alternative.src_phrase?,
+                            alternative
+                                .alternative?
+                                .into_iter()
+                                .map(|word| word.word_postproc)
+                                .collect(),
+                        ))
+                    })
+                    .collect()
+            }),
+            synonyms: value.synsets.map(|synsets| {
+                synsets
+                    .into_iter()
+                    .flat_map(|synset| {
+                        let pos = synset.pos;
+                        synset
+                            .entry
+                            .into_iter()
+                            .map(move |entry| (pos.clone(), entry.synonym))
+                    })
+                    .collect()
+            }),
+            related_words: value.related_words.map(|related| related.word),
         };
 
         Ok(res)
@@ -190,7 +390,25 @@ impl TryFrom<HttpResponse> for SearchResult {
 
 impl std::fmt::Display for SearchResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "src lang: {}", self.src_lang)?;
+        match self.src_lang_confidence {
+            Some(confidence) => writeln!(f, "src lang: {} ({:.3})", self.src_lang, confidence)?,
+            None => writeln!(f, "src lang: {}", self.src_lang)?,
+        }
+
+        if let Some(candidates) = &self.src_lang_candidates {
+            write!(f, "src lang candidates:")?;
+            for (lang, confidence) in candidates {
+                match confidence {
+                    Some(confidence) => write!(f, " {} ({:.3})", lang, confidence)?,
+                    None => write!(f, " {}", lang)?,
+                }
+            }
+            writeln!(f)?;
+        }
+
+        if let Some(suggestion) = &self.spelling_suggestion {
+            writeln!(f, "did you mean: {}", suggestion)?;
+        }
 
         if let Some(translations) = &self.sentence_translation {
             writeln!(f, "translations:")?;
@@ -220,6 +438,25 @@ impl std::fmt::Display for SearchResult {
             }
         }
 
+        if let Some(alternatives) = &self.alternatives {
+            writeln!(f, "alternatives:")?;
+            for (orig, alternatives) in alternatives {
+                writeln!(f, "\t{}: {}", orig, alternatives.join(", "))?;
+            }
+        }
+
+        if let Some(synonyms) = &self.synonyms {
+            writeln!(f, "synonyms:")?;
+            for (pos, words) in synonyms {
+                writeln!(f, "\t({}) {}", pos, words.join(", "))?;
+            }
+        }
+
+        if let Some(related_words) = &self.related_words {
+            writeln!(f, "related words:")?;
+            writeln!(f, "\t{}", related_words.join(", "))?;
+        }
+
         if let Some(src_translit) = &self.src_translit {
             writeln!(f, "src_translit:")?;
             pretty_format_section(f, src_translit)?;
@@ -257,9 +494,280 @@ fn pretty_format_section(
     Ok(())
 }
 
+/// Languages supported by google translate, and the codes it uses for them
+///
+/// Most of the codes are ISO 639-1, but google uses legacy codes for some languages (`iw`,
+/// `jw`), and ISO 639-3 or script-suffixed codes for languages without one (`haw`, `mni-Mtei`).
+/// Each code appears once, so that it parses back to a single language; languages google
+/// translates under the code of another are in [`LANGUAGE_ALIASES`].
+const LANGUAGE_CODES: &[(Language, &str)] = &[
+    (Language::Afr, "af"),
+    (Language::Aka, "ak"),
+    (Language::Amh, "am"),
+    (Language::Ara, "ar"),
+    (Language::Asm, "as"),
+    (Language::Aym, "ay"),
+    (Language::Aze, "az"),
+    (Language::Bam, "bm"),
+    (Language::Bel, "be"),
+    (Language::Ben, "bn"),
+    (Language::Bho, "bho"),
+    (Language::Bos, "bs"),
+    (Language::Bul, "bg"),
+    (Language::Cat, "ca"),
+    (Language::Ceb, "ceb"),
+    (Language::Ces, "cs"),
+    (Language::Ckb, "ckb"),
+    (Language::Cos, "co"),
+    (Language::Cym, "cy"),
+    (Language::Dan, "da"),
+    (Language::Deu, "de"),
+    (Language::Div, "dv"),
+    (Language::Doi, "doi"),
+    (Language::Ell, "el"),
+    (Language::Eng, "en"),
+    (Language::Epo, "eo"),
+    (Language::Est, "et"),
+    (Language::Eus, "eu"),
+    (Language::Ewe, "ee"),
+    (Language::Fas, "fa"),
+    (Language::Fin, "fi"),
+    (Language::Fra, "fr"),
+    (Language::Fry, "fy"),
+    (Language::Gla, "gd"),
+    (Language::Gle, "ga"),
+    (Language::Glg, "gl"),
+    (Language::Gom, "gom"),
+    (Language::Grn, "gn"),
+    (Language::Guj, "gu"),
+    (Language::Hat, "ht"),
+    (Language::Hau, "ha"),
+    (Language::Haw, "haw"),
+    (Language::Heb, "iw"),
+    (Language::Hin, "hi"),
+    (Language::Hmn, "hmn"),
+    (Language::Hrv, "hr"),
+    (Language::Hun, "hu"),
+    (Language::Hye, "hy"),
+    (Language::Ibo, "ig"),
+    (Language::Ilo, "ilo"),
+    (Language::Ind, "id"),
+    (Language::Isl, "is"),
+    (Language::Ita, "it"),
+    (Language::Jav, "jw"),
+    (Language::Jpn, "ja"),
+    (Language::Kan, "kn"),
+    (Language::Kat, "ka"),
+    (Language::Kaz, "kk"),
+    (Language::Khm, "km"),
+    (Language::Kin, "rw"),
+    (Language::Kir, "ky"),
+    (Language::Kor, "ko"),
+    (Language::Kri, "kri"),
+    (Language::Kur, "ku"),
+    (Language::Lao, "lo"),
+    (Language::Lat, "la"),
+    (Language::Lav, "lv"),
+    (Language::Lin, "ln"),
+    (Language::Lit, "lt"),
+    (Language::Ltz, "lb"),
+    (Language::Lug, "lg"),
+    (Language::Lus, "lus"),
+    (Language::Mai, "mai"),
+    (Language::Mal, "ml"),
+    (Language::Mar, "mr"),
+    (Language::Mkd, "mk"),
+    (Language::Mlg, "mg"),
+    (Language::Mlt, "mt"),
+    (Language::Mni, "mni-Mtei"),
+    (Language::Mon, "mn"),
+    (Language::Mri, "mi"),
+    (Language::Msa, "ms"),
+    (Language::Mya, "my"),
+    (Language::Nep, "ne"),
+    (Language::Nld, "nl"),
+    (Language::Nor, "no"),
+    (Language::Nso, "nso"),
+    (Language::Nya, "ny"),
+    (Language::Ori, "or"),
+    (Language::Orm, "om"),
+    (Language::Pan, "pa"),
+    (Language::Pol, "pl"),
+    (Language::Por, "pt"),
+    (Language::Pus, "ps"),
+    (Language::Que, "qu"),
+    (Language::Ron, "ro"),
+    (Language::Rus, "ru"),
+    (Language::San, "sa"),
+    (Language::Sin, "si"),
+    (Language::Slk, "sk"),
+    (Language::Slv, "sl"),
+    (Language::Smo, "sm"),
+    (Language::Sna, "sn"),
+    (Language::Snd, "sd"),
+    (Language::Som, "so"),
+    (Language::Sot, "st"),
+    (Language::Spa, "es"),
+    (Language::Sqi, "sq"),
+    (Language::Srp, "sr"),
+    (Language::Sun, "su"),
+    (Language::Swa, "sw"),
+    (Language::Swe, "sv"),
+    (Language::Tam, "ta"),
+    (Language::Tat, "tt"),
+    (Language::Tel, "te"),
+    (Language::Tgk, "tg"),
+    (Language::Tgl, "tl"),
+    (Language::Tha, "th"),
+    (Language::Tir, "ti"),
+    (Language::Tso, "ts"),
+    (Language::Tuk, "tk"),
+    (Language::Tur, "tr"),
+    (Language::Uig, "ug"),
+    (Language::Ukr, "uk"),
+    (Language::Urd, "ur"),
+    (Language::Uzb, "uz"),
+    (Language::Vie, "vi"),
+    (Language::Xho, "xh"),
+    (Language::Yid, "yi"),
+    (Language::Yor, "yo"),
+    (Language::Zho, "zh-CN"),
+    (Language::Zul, "zu"),
+];
+
+/// Languages google translate supports under the code of another language, and that language
+const LANGUAGE_ALIASES: &[(Language, Language)] = &[
+    (Language::Fil, Language::Tgl),
+    (Language::Kmr, Language::Kur),
+    (Language::Kok, Language::Gom),
+    (Language::Twi, Language::Aka),
+];
+
+/// The unofficial endpoint starts answering with captchas when it is sent more than this
+pub const RATE_LIMIT: RateLimit = RateLimit::new(5, 2.0);
+
+/// Regional variants that google translate treats as separate languages
+const REGIONAL_VARIANTS: &[&str] = &["zh-CN", "zh-TW", "pt-PT", "pt-BR"];
+
+/// Lists the languages supported by google translate
+pub fn supported_languages() -> Vec<Language> {
+    let mut languages: Vec<Language> = LANGUAGE_CODES
+        .iter()
+        .map(|(lang, _)| *lang)
+        .chain(LANGUAGE_ALIASES.iter().map(|(alias, _)| *alias))
+        .collect();
+    languages.sort_by_key(|lang| lang.to_639_3());
+    languages
+}
+
+/// Converts the language tag to the code used by google translate
+///
+/// Returns `None` if google translate doesn't support the language. Regions are only kept for
+/// the variants google distinguishes. Chinese is sent as the variant written the same way: `zh-HK`
+/// and `zh-Hant` as `zh-TW`, and other regions by their script.
+pub fn language_code(lang: &search::LanguageTag) -> Option<String> {
+    let language = LANGUAGE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == lang.language)
+        .map_or(lang.language, |(_, language)| *language);
+    let code = LANGUAGE_CODES
+        .iter()
+        .find(|(google_language, _)| *google_language == language)
+        .map(|(_, code)| *code)?;
+    let primary = code.split('-').next().unwrap_or(code);
+    let variant = |region: &str| {
+        let variant = format!("{}-{}", primary, region);
+        REGIONAL_VARIANTS
+            .contains(&variant.as_str())
+            .then_some(variant)
+    };
+    // Traditional characters are used in Hong Kong and Macau, simplified ones in Singapore
+    let region = match (primary, lang.region.as_deref()) {
+        ("zh", Some("HK" | "MO")) => Some("TW"),
+        ("zh", Some("SG" | "MY")) => Some("CN"),
+        (_, region) => region,
+    };
+    let script_region = match (primary, lang.script.as_deref()) {
+        ("zh", Some("Hant")) => Some("TW"),
+        ("zh", Some("Hans")) => Some("CN"),
+        _ => None,
+    };
+
+    Some(
+        region
+            .and_then(variant)
+            .or_else(|| script_region.and_then(variant))
+            .unwrap_or(code.to_string()),
+    )
+}
+
+/// Converts a language code returned by google translate back to a language tag
+///
+/// The region of the variants google distinguishes is kept: `zh-CN` is parsed as `zh-CN`.
+pub fn parse_language_code(code: &str) -> Option<search::LanguageTag> {
+    if REGIONAL_VARIANTS.contains(&code) {
+        return search::parse_lang(code).ok();
+    }
+    match LANGUAGE_CODES
+        .iter()
+        .find(|(_, google_code)| *google_code == code)
+    {
+        Some((language, _)) => Some((*language).into()),
+        None => search::parse_lang(code).ok(),
+    }
+}
+
 /// Looks up the translation on google translate, using the endpoint by:
 /// <https://github.com/ssut/py-googletrans/issues/268#issuecomment-1146554742>
 pub fn lookup_google_translate(search_options: &search::SearchConfig) -> Result<SearchResult> {
+    lookup_google_translate_with_client(HttpClient::shared(), search_options)
+}
+
+/// Looks up the translation on google translate, reusing the connections of `client`
+pub fn lookup_google_translate_with_client(
+    client: &HttpClient,
+    search_options: &search::SearchConfig,
+) -> Result<SearchResult> {
+    let unsupported = |lang: &search::LanguageTag| {
+        TranslateError(format!(
+            "google translate doesn't support the language: {}",
+            lang.name()
+        ))
+    };
+    let source_language: String = match &search_options.source_language {
+        Some(lang) => language_code(lang).ok_or_else(|| unsupported(lang))?,
+        None => "auto".to_string(),
+    };
+    let target_language = search_options.target_language.as_ref().ok_or_else(|| {
+        TranslateError("google translate requires a destination language".to_string())
+    })?;
+    let target_language: String =
+        language_code(target_language).ok_or_else(|| unsupported(target_language))?;
+
+    let mut chunks = split_query(&search_options.query, MAX_QUERY_LEN).into_iter();
+    let first = chunks.next().unwrap_or_default();
+    let mut search_result = lookup_chunk(client, &source_language, &target_language, first)?;
+    for chunk in chunks {
+        search_result.append(lookup_chunk(
+            client,
+            &source_language,
+            &target_language,
+            chunk,
+        )?);
+    }
+    Ok(search_result)
+}
+
+/// Longest query sent in one request, in URL encoded bytes. Longer URLs are rejected by google.
+const MAX_QUERY_LEN: usize = 5000;
+
+/// Looks up one part of the query
+fn lookup_chunk(
+    client: &HttpClient,
+    source_language: &str,
+    target_language: &str,
+    query: &str,
+) -> Result<SearchResult> {
     let url = reqwest::Url::parse_with_params(
         "https://clients5.google.com/translate_a/single",
         &[
@@ -272,39 +780,137 @@ pub fn lookup_google_translate(search_options: &search::SearchConfig) -> Result<
             ("dt", "ss"),
             ("dt", "t"),
             ("client", "dict-chrome-ex"),
-            (
-                "sl",
-                match search_options.source_language {
-                    Some(lang) => lang.to_639_1().unwrap_or("auto"),
-                    None => "auto",
-                },
-            ),
-            (
-                "tl",
-                search_options
-                    .target_language
-                    .ok_or_else(|| {
-                        TranslateError(
-                            "google translate requires a destination language".to_string(),
-                        )
-                    })?
-                    .to_639_1()
-                    .ok_or(TranslateError(
-                        "The language specified doesn't have a iso639-1 language code".to_string(),
-                    ))?,
-            ),
-            ("q", &search_options.query),
+            ("sl", source_language),
+            ("tl", target_language),
+            ("q", query),
         ],
     )?;
-    let response: reqwest::blocking::Response = reqwest::blocking::get(url)?;
-    let body: HttpResponse = response.json()?;
+    let response: Response = client.get(SearchProvider::GoogleTranslate, url)?;
+    if is_html(&response) {
+        // Google answers throttled clients with a captcha page instead of a 429
+        let page = response.text();
+        if page.contains("captcha") || page.contains("unusual traffic") {
+            return Err(RateLimitedError {
+                provider: SearchProvider::GoogleTranslate,
+                retry_after: None,
+            }
+            .into());
+        }
+        return Err(TranslateError("unexpected HTML response".to_string()).into());
+    }
+    let body: HttpResponse = response.error_for_status()?.json()?;
     let search_result: SearchResult = body.try_into()?;
     Ok(search_result)
 }
 
+/// Length of the text once URL encoded
+fn encoded_len(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | ' ' | '-' | '.' | '_' | '*' => 1,
+            _ => c.len_utf8() * 3,
+        })
+        .sum()
+}
+
+/// Splits the query into chunks of at most `max_len` URL encoded bytes
+///
+/// Splits on sentence boundaries, like `pretty_format_section` does, and falls back to
+/// whitespace, then to any character, for sentences longer than `max_len`.
+fn split_query(query: &str, max_len: usize) -> Vec<&str> {
+    let mut chunks: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut len = 0;
+    for piece in split_pieces(query, max_len) {
+        let piece_len = encoded_len(piece);
+        if len + piece_len > max_len && len > 0 {
+            // Keep the space after the sentence in the previous chunk
+            let leading_space = piece.len() - piece.trim_start().len();
+            let end = piece.as_ptr() as usize - query.as_ptr() as usize + leading_space;
+            chunks.push(&query[start..end]);
+            start = end;
+            len = 0;
+        }
+        len += piece_len;
+    }
+    if start < query.len() || chunks.is_empty() {
+        chunks.push(&query[start..]);
+    }
+    chunks
+}
+
+/// Splits the text into sentences, and the sentences longer than `max_len` into smaller pieces
+fn split_pieces(text: &str, max_len: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    for sentence in text.split_inclusive(|c: char| ".?!。？！\n".contains(c)) {
+        if encoded_len(sentence) <= max_len {
+            pieces.push(sentence);
+            continue;
+        }
+        for word in sentence.split_inclusive(char::is_whitespace) {
+            if encoded_len(word) <= max_len {
+                pieces.push(word);
+                continue;
+            }
+            let mut rest = word;
+            while !rest.is_empty() {
+                let mut end = 0;
+                let mut len = 0;
+                for (i, c) in rest.char_indices() {
+                    len += encoded_len(&rest[i..i + c.len_utf8()]);
+                    if len > max_len && end > 0 {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                }
+                pieces.push(&rest[..end]);
+                rest = &rest[end..];
+            }
+        }
+    }
+    pieces
+}
+
+fn is_html(response: &Response) -> bool {
+    response.url.path().starts_with("/sorry/")
+        || response
+            .content_type()
+            .is_some_and(|content_type| content_type.starts_with("text/html"))
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
+    use crate::http::cassette::CassetteTransport;
+    use crate::http::HttpConfig;
+
+    /// A client replaying the responses of `tests/cassettes/google_translate/<name>.json`, or
+    /// recording them when `WDYM_RECORD_CASSETTES` is set
+    fn cassette_client(name: &str) -> HttpClient {
+        let path = format!(
+            "{}/tests/cassettes/google_translate/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        HttpClient::with_transport(
+            HttpConfig::default(),
+            std::sync::Arc::new(CassetteTransport::replay_or_record(path).unwrap()),
+        )
+    }
+
+    /// A client replaying `tests/cassettes/synthetic/google_translate/<name>.json`, the error
+    /// responses written by hand, which are never recorded again
+    fn synthetic_client(name: &str) -> HttpClient {
+        let path = format!(
+            "{}/tests/cassettes/synthetic/google_translate/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        HttpClient::with_transport(
+            HttpConfig::default(),
+            std::sync::Arc::new(CassetteTransport::replay(path).unwrap()),
+        )
+    }
 
     #[test]
     #[should_panic]
@@ -314,6 +920,7 @@ pub(crate) mod test {
             source_language: None,
             target_language: None,
             provider: crate::translators::SearchProvider::GoogleTranslate,
+            ..Default::default()
         };
 
         lookup_google_translate(&search_options).unwrap();
@@ -325,12 +932,31 @@ pub(crate) mod test {
         let search_options = search::SearchConfig {
             query: "book".to_string(),
             source_language: None,
-            target_language: Some(isolang::Language::Und),
+            target_language: Some(isolang::Language::Und.into()),
             provider: crate::translators::SearchProvider::GoogleTranslate,
+            ..Default::default()
         };
         lookup_google_translate(&search_options).unwrap();
     }
 
+    #[test]
+    fn captcha_page_is_rate_limited() {
+        let search_options = search::SearchConfig {
+            query: "cat".to_string(),
+            source_language: Some(isolang::Language::Eng.into()),
+            target_language: Some(isolang::Language::Jpn.into()),
+            ..Default::default()
+        };
+        // Answers like Google does to throttled clients
+        let client = synthetic_client("captcha");
+        let Err(err) = lookup_google_translate_with_client(&client, &search_options) else {
+            panic!("a captcha page should fail the lookup");
+        };
+        let err = err.downcast_ref::<RateLimitedError>().unwrap();
+        assert_eq!(err.provider, SearchProvider::GoogleTranslate);
+        assert_eq!(err.retry_after, None);
+    }
+
     #[test]
     fn deserialize_json() {
         let jsondata = r#"
@@ -340,19 +966,181 @@ pub(crate) mod test {
         assert_eq!(response.src, "en");
     }
 
+    #[test]
+    fn deserialize_spell_and_alternatives() {
+        let jsondata = r#"
+        {"sentences":[{"trans":"本","orig":"boook","backend":10}],"src":"en","alternative_translations":[{"src_phrase":"boook","alternative":[{"word_postproc":"本","score":1000,"has_preceding_space":true,"attach_to_next_token":false},{"word_postproc":"書籍","score":0,"has_preceding_space":true,"attach_to_next_token":false}],"srcunicodeoffsets":[{"begin":0,"end":5}],"raw_src_segment":"boook","start_pos":0,"end_pos":0}],"confidence":0.6,"spell":{"spell_html_res":"<b><i>book</i></b>","spell_res":"book","correction_type":[1],"related":true},"synsets":[{"pos":"noun","entry":[{"synonym":["volume","tome"],"definition_id":"m_en_gbus0102180.005"}],"base_form":"book","pos_enum":1}],"related_words":{"word":["books","booking"]},"ld_result":{"srclangs":["en"],"srclangs_confidences":[0.6],"extended_srclangs":["en"]}}
+        "#;
+        let response: HttpResponse = serde_json::from_str(jsondata).unwrap();
+        let result: search::SearchResult = SearchResult::try_from(response).unwrap().into();
+
+        assert_eq!(result.spelling_suggestion.as_deref(), Some("book"));
+        let alternatives = result.alternatives.unwrap();
+        assert_eq!(alternatives[0].orig, "boook");
+        assert_eq!(alternatives[0].alternatives, vec!["本", "書籍"]);
+        let synonyms = result.synonyms.unwrap();
+        assert_eq!(synonyms[0].pos, "noun");
+        assert_eq!(synonyms[0].words, vec!["volume", "tome"]);
+        assert_eq!(result.related_words.unwrap(), vec!["books", "booking"]);
+    }
+
+    #[test]
+    fn deserialize_language_detection() {
+        let jsondata = r#"
+        {"sentences":[{"trans":"Hallo","orig":"hallo","backend":10}],"src":"de","confidence":0.7,"spell":{},"ld_result":{"srclangs":["de","nl"],"srclangs_confidences":[0.7,0.2],"extended_srclangs":["de","nl","iw"]}}
+        "#;
+        let response: HttpResponse = serde_json::from_str(jsondata).unwrap();
+        let result: search::SearchResult = SearchResult::try_from(response).unwrap().into();
+
+        assert_eq!(result.src_lang_confidence, Some(0.7));
+        let candidates = result.src_lang_candidates.unwrap();
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].lang, Language::Deu.into());
+        assert_eq!(candidates[1].confidence, Some(0.2));
+        assert_eq!(candidates[2].lang, Language::Heb.into());
+        assert_eq!(candidates[2].confidence, None);
+    }
+
+    #[test]
+    fn split_long_query() {
+        let query = "First sentence. Second sentence? Third!";
+        assert_eq!(split_query(query, 5000), vec![query]);
+        assert_eq!(
+            split_query(query, 20),
+            vec!["First sentence. ", "Second sentence? ", "Third!"]
+        );
+        assert_eq!(
+            split_query(query, 40),
+            vec!["First sentence. Second sentence? ", "Third!"]
+        );
+
+        // A sentence longer than the limit is split on whitespace, then anywhere
+        assert_eq!(split_query("aaa bbb", 5), vec!["aaa ", "bbb"]);
+        assert_eq!(split_query("aaaaaaa", 3), vec!["aaa", "aaa", "a"]);
+
+        // Non-ASCII characters count with their URL encoded length
+        assert_eq!(split_query("計算。計算。", 30), vec!["計算。", "計算。"]);
+
+        assert_eq!(split_query("", 10), vec![""]);
+    }
+
+    #[test]
+    fn append_chunks() {
+        let chunk = |orig: &str, trans: &str, translit: &str| {
+            let jsondata = format!(
+                r#"{{"sentences":[{{"trans":"{}","orig":"{}"}},{{"translit":"{}"}}],"src":"en"}}"#,
+                trans, orig, translit
+            );
+            let response: HttpResponse = serde_json::from_str(&jsondata).unwrap();
+            SearchResult::try_from(response).unwrap()
+        };
+
+        let mut result = chunk("Good morning. ", "おはよう。", "Ohayō.");
+        result.append(chunk("Good night.", "おやすみ。", "Oyasumi."));
+
+        assert_eq!(
+            result.sentence_translation.unwrap(),
+            vec![
+                ("Good morning. ".to_string(), "おはよう。".to_string()),
+                ("Good night.".to_string(), "おやすみ。".to_string())
+            ]
+        );
+        assert_eq!(result.translit.unwrap(), "Ohayō. Oyasumi.");
+    }
+
+    #[test]
+    fn regional_language_code() {
+        let code = |lang: &str| language_code(&search::parse_lang(lang).unwrap());
+
+        assert_eq!(code("zh_TW").unwrap(), "zh-TW");
+        assert_eq!(code("zh-Hans").unwrap(), "zh-CN");
+        assert_eq!(code("pt-PT").unwrap(), "pt-PT");
+        assert_eq!(code("en-US").unwrap(), "en");
+        assert_eq!(code("japanese").unwrap(), "ja");
+        assert_eq!(code("zh").unwrap(), "zh-CN");
+        assert_eq!(code("zh-HK").unwrap(), "zh-TW");
+        assert_eq!(code("zh-MO").unwrap(), "zh-TW");
+        assert_eq!(code("zh-SG").unwrap(), "zh-CN");
+        assert_eq!(code("zh-Hant-HK").unwrap(), "zh-TW");
+        assert_eq!(code("zh-Hant-US").unwrap(), "zh-TW");
+        assert_eq!(code("zh-Hans-US").unwrap(), "zh-CN");
+        assert_eq!(code("pt-Latn-AO").unwrap(), "pt");
+    }
+
+    #[test]
+    fn non_iso639_1_language_code() {
+        let code = |lang: &str| language_code(&search::parse_lang(lang).unwrap());
+
+        assert_eq!(code("hawaiian").unwrap(), "haw");
+        assert_eq!(code("ceb").unwrap(), "ceb");
+        assert_eq!(code("hmn").unwrap(), "hmn");
+        assert_eq!(code("mni").unwrap(), "mni-Mtei");
+        assert_eq!(code("he").unwrap(), "iw");
+        assert_eq!(code("jv").unwrap(), "jw");
+        assert!(code("ain").is_none());
+
+        assert_eq!(parse_language_code("iw").unwrap().language, Language::Heb);
+        assert_eq!(parse_language_code("zh-TW").unwrap().to_string(), "zh-TW");
+        assert_eq!(parse_language_code("zh-CN").unwrap().to_string(), "zh-CN");
+        assert_eq!(parse_language_code("pt-BR").unwrap().to_string(), "pt-BR");
+        assert_eq!(
+            parse_language_code("mni-Mtei").unwrap().language,
+            Language::Mni
+        );
+    }
+
+    #[test]
+    fn language_codes_are_unique() {
+        let mut codes: Vec<&str> = LANGUAGE_CODES.iter().map(|(_, code)| *code).collect();
+        codes.sort_unstable();
+        let len = codes.len();
+        codes.dedup();
+        assert_eq!(codes.len(), len);
+
+        for (alias, _) in LANGUAGE_ALIASES {
+            assert!(supported_languages().contains(alias));
+        }
+        let code = |lang: Language| language_code(&lang.into()).unwrap();
+        assert_eq!(code(Language::Fil), "tl");
+        assert_eq!(code(Language::Kmr), "ku");
+        assert_eq!(code(Language::Kok), "gom");
+        assert_eq!(code(Language::Twi), "ak");
+        assert_eq!(parse_language_code("tl").unwrap().language, Language::Tgl);
+        assert_eq!(parse_language_code("ku").unwrap().language, Language::Kur);
+        assert_eq!(parse_language_code("gom").unwrap().language, Language::Gom);
+        assert_eq!(parse_language_code("ak").unwrap().language, Language::Aka);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unsupported_language() {
+        let search_options = search::SearchConfig {
+            query: "book".to_string(),
+            source_language: Some(Language::Ain.into()),
+            target_language: Some(Language::Eng.into()),
+            provider: crate::translators::SearchProvider::GoogleTranslate,
+            ..Default::default()
+        };
+        lookup_google_translate(&search_options).unwrap();
+    }
+
     #[test]
     fn simple_lookup() {
         let search_options = search::SearchConfig {
             provider: crate::translators::SearchProvider::GoogleTranslate,
             query: "Good Morning".to_string(),
-            source_language: Some(isolang::Language::Eng),
-            target_language: Some(isolang::Language::from_name("Japanese").unwrap()),
+            source_language: Some(isolang::Language::Eng.into()),
+            target_language: Some(isolang::Language::from_name("Japanese").unwrap().into()),
+            ..Default::default()
         };
 
-        assert!(lookup_google_translate(&search_options)
-            .unwrap()
-            .to_string()
-            .contains("お早う"));
+        assert!(lookup_google_translate_with_client(
+            &cassette_client("simple_lookup"),
+            &search_options
+        )
+        .unwrap()
+        .to_string()
+        .contains("お早う"));
     }
 
     /// Test the transliteration and translation
@@ -360,14 +1148,18 @@ pub(crate) mod test {
     fn translit_translate() {
         let search_options = search::SearchConfig {
             query: "Typer is a library for building CLI applications that users will love using and developers will love creating. Based on Python type hints. It's also a command line tool to run scripts, automatically converting them to CLI applications. The key features are: Intuitive to write: Great editor support. Completion everywhere. Less time debugging. Designed to be easy to use and learn. Less time reading docs. Easy to use: It's easy to use for the final users. Automatic help, and automatic completion for all shells. Short: Minimize code duplication. Multiple features from each parameter declaration. Fewer bugs. Start simple: The simplest example adds only 2 lines of code to your app: 1 import, 1 function call. Grow large: Grow in complexity as much as you want, create arbitrarily complex trees of commands and groups of subcommands, with options and arguments. Run scripts: Typer includes a typer command/program that you can use to run scripts, automatically converting them to CLIs, even if they don't use Typer internally. ".to_string(),
-            source_language: Some(isolang::Language::Eng),
-            target_language: Some(isolang::Language::Jpn),
+            source_language: Some(isolang::Language::Eng.into()),
+            target_language: Some(isolang::Language::Jpn.into()),
             provider: crate::translators::SearchProvider::GoogleTranslate,
+            ..Default::default()
         };
 
-        let res = lookup_google_translate(&search_options)
-            .unwrap()
-            .to_string();
+        let res = lookup_google_translate_with_client(
+            &cassette_client("translit_translate"),
+            &search_options,
+        )
+        .unwrap()
+        .to_string();
 
         // sentence translation
         assert!(res.contains("Typerは"));
@@ -380,14 +1172,18 @@ pub(crate) mod test {
     fn test_source_translit() {
         let search_options = search::SearchConfig {
             query: "計算".to_string(),
-            source_language: Some(isolang::Language::Jpn),
-            target_language: Some(isolang::Language::Eng),
+            source_language: Some(isolang::Language::Jpn.into()),
+            target_language: Some(isolang::Language::Eng.into()),
             provider: crate::translators::SearchProvider::GoogleTranslate,
+            ..Default::default()
         };
 
-        assert!(lookup_google_translate(&search_options)
-            .unwrap()
-            .to_string()
-            .contains("Keisan"));
+        assert!(lookup_google_translate_with_client(
+            &cassette_client("test_source_translit"),
+            &search_options
+        )
+        .unwrap()
+        .to_string()
+        .contains("Keisan"));
     }
 }
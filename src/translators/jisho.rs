@@ -0,0 +1,518 @@
+//! The jisho.org dictionary module
+//!
+//! Conjugated queries are deinflected first, so 食べられなかった finds the entry of 食べる, and
+//! the result tells how the query was inflected.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::http::HttpClient;
+//! use wdym::search;
+//! use wdym::translators::jisho::lookup_jisho_with_client;
+//! use wdym::translators::SearchProvider;
+//!
+//! let client = HttpClient::default();
+//! # let client = wdym::http::HttpClient::with_transport(
+//! #     Default::default(),
+//! #     std::sync::Arc::new(
+//! #         wdym::http::cassette::CassetteTransport::replay_or_record(concat!(
+//! #             env!("CARGO_MANIFEST_DIR"),
+//! #             "/tests/cassettes/jisho/inflected_lookup.json"
+//! #         ))
+//! #         .unwrap(),
+//! #     ),
+//! # );
+//! let search_options = search::SearchConfig {
+//!     query: "食べられなかった".to_string(),
+//!     provider: SearchProvider::Jisho,
+//!     ..Default::default()
+//! };
+//! let result: search::SearchResult = lookup_jisho_with_client(&client, &search_options)
+//!     .unwrap()
+//!     .into();
+//! assert_eq!(&result.src_lang.unwrap(), "ja");
+//! assert!(result.inflection.is_some());
+//! ```
+use color_eyre::Result;
+use serde::Deserialize;
+
+use crate::http::HttpClient;
+use crate::japanese::deinflect::{deinflect, Deinflection, WordTypes};
+use crate::japanese::kana::{self, Romanization};
+use crate::japanese::kanji;
+use crate::search;
+
+use super::{SearchProvider, TranslateError};
+
+#[derive(Deserialize, Debug)]
+struct HttpResponse {
+    meta: HttpResponseMeta,
+    data: Vec<Entry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HttpResponseMeta {
+    status: u16,
+}
+
+/// A word of the dictionary, with its spellings and meanings
+#[derive(Deserialize, Debug, Clone)]
+struct Entry {
+    japanese: Vec<EntryJapanese>,
+    senses: Vec<EntrySense>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct EntryJapanese {
+    word: Option<String>,
+    reading: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct EntrySense {
+    english_definitions: Vec<String>,
+    parts_of_speech: Vec<String>,
+}
+
+impl Entry {
+    /// What the word conjugates as, from the parts of speech of its senses
+    fn word_types(&self) -> WordTypes {
+        self.senses
+            .iter()
+            .flat_map(|sense| &sense.parts_of_speech)
+            .map(|pos| match pos.as_str() {
+                "Ichidan verb" => WordTypes::V1,
+                _ if pos.starts_with("Godan verb") => WordTypes::V5,
+                _ if pos.starts_with("Suru verb") => WordTypes::VS,
+                _ if pos.starts_with("Kuru verb") => WordTypes::VK,
+                "I-adjective (keiyoushi)" => WordTypes::ADJ_I,
+                _ => WordTypes::ANY,
+            })
+            .fold(WordTypes::ANY, |types, pos| types | pos)
+    }
+
+    /// Whether the entry is the word of the candidate
+    fn matches(&self, candidate: &Deinflection) -> bool {
+        let spelled = self.japanese.iter().any(|japanese| {
+            japanese.word.as_deref() == Some(&candidate.term)
+                || japanese.reading.as_deref() == Some(&candidate.term)
+        });
+        spelled
+            && (candidate.word_types == WordTypes::ANY
+                || self.word_types().intersects(candidate.word_types))
+    }
+
+    /// The spellings of the word, like `食べる【たべる】`
+    fn spellings(&self) -> Vec<String> {
+        self.japanese
+            .iter()
+            .map(|japanese| match (&japanese.word, &japanese.reading) {
+                (Some(word), Some(reading)) => format!("{}【{}】", word, reading),
+                (Some(text), None) | (None, Some(text)) => text.clone(),
+                (None, None) => String::new(),
+            })
+            .filter(|spelling| !spelling.is_empty())
+            .collect()
+    }
+
+    /// The main spelling of the word, with its reading
+    fn headword(&self) -> Option<crate::search::Word> {
+        let japanese = self.japanese.first()?;
+        match (&japanese.word, &japanese.reading) {
+            (Some(word), reading) => Some(crate::search::Word {
+                text: word.clone(),
+                reading: reading.clone(),
+            }),
+            (None, Some(reading)) => Some(crate::search::Word {
+                text: reading.clone(),
+                reading: None,
+            }),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The search result of jisho.org
+///
+/// Can be converted to `search::SearchResult`, using `from` or `into`
+pub struct SearchResult {
+    entries: Vec<Entry>,
+    is_japanese: bool,
+    /// The dictionary form found for an inflected query
+    deinflection: Option<Deinflection>,
+    romanization: Romanization,
+}
+
+impl From<SearchResult> for crate::search::SearchResult {
+    fn from(value: SearchResult) -> Self {
+        let definitions: Vec<crate::search::Definition> = value
+            .entries
+            .iter()
+            .flat_map(|entry| {
+                let headword = entry.headword();
+                let other_spellings: Vec<String> = entry.spellings().into_iter().skip(1).collect();
+                entry
+                    .senses
+                    .iter()
+                    .map(move |sense| crate::search::Definition {
+                        headword: headword.clone(),
+                        pitch_accents: None,
+                        other_spellings: (!other_spellings.is_empty())
+                            .then(|| other_spellings.clone()),
+                        meaning: sense.english_definitions.join("; "),
+                        rich_meaning: None,
+                        pos: sense.parts_of_speech.join(", "),
+                        reverse_translation: None,
+                        confidence: None,
+                        frequency: None,
+                        examples: None,
+                        relations: None,
+                    })
+            })
+            .collect();
+        // The reading of the word found, in romaji
+        let reading = value
+            .entries
+            .first()
+            .and_then(|entry| entry.japanese.first())
+            .and_then(|japanese| japanese.reading.as_deref())
+            .map(|reading| kana::to_romaji(reading, value.romanization));
+
+        crate::search::SearchResult {
+            provider: SearchProvider::Jisho,
+            translations: None,
+            definitions: (!definitions.is_empty()).then_some(definitions),
+            src_lang: Some(if value.is_japanese { "ja" } else { "en" }.to_string()),
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: reading.map(|reading| match value.is_japanese {
+                true => crate::search::Literation {
+                    orig: Some(reading),
+                    translated: None,
+                },
+                false => crate::search::Literation {
+                    orig: None,
+                    translated: Some(reading),
+                },
+            }),
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: value
+                .deinflection
+                .map(|deinflection| crate::search::Inflection {
+                    base_form: deinflection.term,
+                    reasons: deinflection
+                        .reasons
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect(),
+                }),
+            tokens: None,
+            warnings: None,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(deinflection) = &self.deinflection {
+            writeln!(
+                f,
+                "inflected from {}: {}",
+                deinflection.term,
+                deinflection.reasons.join(", ")
+            )?;
+        }
+
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.spellings().join("、"))?;
+            for sense in &entry.senses {
+                writeln!(
+                    f,
+                    "\t({}) {}",
+                    sense.parts_of_speech.join(", "),
+                    sense.english_definitions.join("; ")
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Most deinflected candidates sent to jisho, when the first results don't have the word
+pub(crate) const MAX_CANDIDATE_LOOKUPS: usize = 5;
+
+/// Looks up the query on jisho.org
+pub fn lookup_jisho(search_options: &search::SearchConfig) -> Result<SearchResult> {
+    lookup_jisho_with_client(HttpClient::shared(), search_options)
+}
+
+/// Looks up the query on jisho.org, reusing the connections of `client`
+///
+/// When no entry is spelled like the query, the dictionary forms the query may be inflected from
+/// are tried, most plausible first: against the entries already found, then by looking each of
+/// them up.
+pub fn lookup_jisho_with_client(
+    client: &HttpClient,
+    search_options: &search::SearchConfig,
+) -> Result<SearchResult> {
+    lookup_jisho_with_candidates(client, search_options, MAX_CANDIDATE_LOOKUPS)
+}
+
+/// Looks up the query on jisho.org, sending at most `max_candidate_lookups` requests for the
+/// dictionary forms it may be inflected from
+///
+/// The candidates are still matched against the entries found for the query, without requests.
+pub fn lookup_jisho_with_candidates(
+    client: &HttpClient,
+    search_options: &search::SearchConfig,
+    max_candidate_lookups: usize,
+) -> Result<SearchResult> {
+    let query = search_options.query.trim();
+    let is_japanese = query.chars().any(is_japanese_char);
+    let entries = lookup_entries(client, query)?;
+    let mut candidates = deinflect(query).into_iter();
+    let original = candidates.next().expect("The query is always a candidate");
+
+    let mut result = SearchResult {
+        entries,
+        is_japanese,
+        deinflection: None,
+        romanization: search_options.romanization,
+    };
+    if result.entries.iter().any(|entry| entry.matches(&original)) {
+        return Ok(result);
+    }
+
+    let candidates = rank_candidates(candidates.collect());
+    if let Some((entries, deinflection)) = find_candidate(&result.entries, &candidates) {
+        result.entries = entries;
+        result.deinflection = Some(deinflection);
+        return Ok(result);
+    }
+    for candidate in candidates.iter().take(max_candidate_lookups) {
+        let entries = lookup_entries(client, &candidate.term)?;
+        if let Some((entries, deinflection)) =
+            find_candidate(&entries, std::slice::from_ref(candidate))
+        {
+            result.entries = entries;
+            result.deinflection = Some(deinflection);
+            return Ok(result);
+        }
+    }
+    Ok(result)
+}
+
+/// Orders the candidates most plausible first, dropping the ones which can't be dictionary forms
+///
+/// Dictionary forms are the shortest forms of a word, reached in few inflections, so candidates
+/// are ranked by the kana after their last kanji plus their inflections, then by their
+/// inflections: 高い comes before 高くない and 高くなかう for 高くなかった, and 食べる before 食ぶ
+/// for 食べられなかった.
+fn rank_candidates(mut candidates: Vec<Deinflection>) -> Vec<Deinflection> {
+    candidates.retain(Deinflection::is_well_formed);
+    candidates.sort_by_key(|candidate| {
+        let okurigana = candidate
+            .term
+            .chars()
+            .rev()
+            .take_while(|c| !kanji::is_kanji(*c))
+            .count();
+        (okurigana + candidate.reasons.len(), candidate.reasons.len())
+    });
+    candidates
+}
+
+/// Finds the first candidate with an entry, and puts its entries first
+fn find_candidate(
+    entries: &[Entry],
+    candidates: &[Deinflection],
+) -> Option<(Vec<Entry>, Deinflection)> {
+    let candidate = candidates
+        .iter()
+        .find(|candidate| entries.iter().any(|entry| entry.matches(candidate)))?;
+    let (mut matching, rest): (Vec<Entry>, Vec<Entry>) = entries
+        .iter()
+        .cloned()
+        .partition(|entry| entry.matches(candidate));
+    matching.extend(rest);
+    Some((matching, candidate.clone()))
+}
+
+fn lookup_entries(client: &HttpClient, keyword: &str) -> Result<Vec<Entry>> {
+    let url = reqwest::Url::parse_with_params(
+        "https://jisho.org/api/v1/search/words",
+        &[("keyword", keyword)],
+    )?;
+    let body: HttpResponse = client
+        .get(SearchProvider::Jisho, url)?
+        .error_for_status()?
+        .json()?;
+    if body.meta.status != 200 {
+        return Err(TranslateError(format!("jisho returned status {}", body.meta.status)).into());
+    }
+    Ok(body.data)
+}
+
+/// Kana, kanji and the Japanese punctuation
+fn is_japanese_char(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{30ff}' // Punctuation, hiragana and katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK unified ideographs
+        | '\u{ff66}'..='\u{ff9f}') // Half width katakana
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::cassette::CassetteTransport;
+    use crate::http::HttpConfig;
+
+    /// A client replaying the responses of `tests/cassettes/jisho/<name>.json`, or recording
+    /// them when `WDYM_RECORD_CASSETTES` is set
+    fn cassette_client(name: &str) -> HttpClient {
+        let path = format!(
+            "{}/tests/cassettes/jisho/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        HttpClient::with_transport(
+            HttpConfig::default(),
+            std::sync::Arc::new(CassetteTransport::replay_or_record(path).unwrap()),
+        )
+    }
+
+    /// A client replaying `tests/cassettes/synthetic/jisho/<name>.json`, the error responses
+    /// written by hand, which are never recorded again. It doesn't retry.
+    fn synthetic_client(name: &str) -> HttpClient {
+        let path = format!(
+            "{}/tests/cassettes/synthetic/jisho/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        HttpClient::with_transport(
+            HttpConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+            std::sync::Arc::new(CassetteTransport::replay(path).unwrap()),
+        )
+    }
+
+    fn search_config(query: &str) -> search::SearchConfig {
+        search::SearchConfig {
+            query: query.to_string(),
+            provider: SearchProvider::Jisho,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn simple_lookup() {
+        let result =
+            lookup_jisho_with_client(&cassette_client("simple_lookup"), &search_config("計算"))
+                .unwrap();
+        assert!(result.deinflection.is_none());
+
+        let result: search::SearchResult = result.into();
+        let definitions = result.definitions.unwrap();
+        assert_eq!(definitions[0].meaning, "calculation; reckoning; count");
+        assert_eq!(
+            definitions[0].headword,
+            Some(search::Word {
+                text: "計算".to_string(),
+                reading: Some("けいさん".to_string())
+            })
+        );
+        assert_eq!(definitions[0].other_spellings, None);
+        assert!(result.inflection.is_none());
+        assert_eq!(result.literation.unwrap().orig.as_deref(), Some("keisan"));
+    }
+
+    #[test]
+    fn reading_in_kunrei() {
+        // しゃしん is written differently in Hepburn and Kunrei
+        let reading = |romanization| {
+            let config = search::SearchConfig {
+                romanization,
+                ..search_config("写真")
+            };
+            let result: search::SearchResult =
+                lookup_jisho_with_client(&cassette_client("kunrei_lookup"), &config)
+                    .unwrap()
+                    .into();
+            result.literation.unwrap().orig.unwrap()
+        };
+        assert_eq!(reading(Romanization::Kunrei), "syasin");
+        assert_eq!(reading(Romanization::Hepburn), "shashin");
+    }
+
+    #[test]
+    fn inflection_in_results() {
+        // Jisho also returns the entry of the dictionary form for conjugated queries
+        let result = lookup_jisho_with_client(
+            &cassette_client("inflected_lookup"),
+            &search_config("食べられなかった"),
+        )
+        .unwrap();
+
+        let result: search::SearchResult = result.into();
+        let inflection = result.inflection.unwrap();
+        assert_eq!(inflection.base_form, "食べる");
+        assert_eq!(
+            inflection.reasons,
+            vec!["potential or passive", "negative", "past"]
+        );
+        let definitions = result.definitions.unwrap();
+        assert_eq!(definitions[0].headword.as_ref().unwrap().text, "食べる");
+        assert_eq!(
+            definitions[0].other_spellings,
+            Some(vec!["喰べる【たべる】".to_string()])
+        );
+    }
+
+    #[test]
+    fn plausible_candidates_first() {
+        let terms = |query: &str| -> Vec<String> {
+            rank_candidates(deinflect(query).into_iter().skip(1).collect())
+                .into_iter()
+                .map(|candidate| candidate.term)
+                .collect()
+        };
+        let candidates = terms("高くなかった");
+        assert_eq!(candidates[0], "高い");
+        assert!(!candidates.contains(&"高くなかっる".to_string()));
+        assert_eq!(terms("書かなかった")[0], "書く");
+        assert_eq!(terms("食べられなかった")[0], "食べる");
+    }
+
+    #[test]
+    fn inflection_looked_up() {
+        // The query finds nothing, so the candidates are looked up one by one
+        let result: search::SearchResult = lookup_jisho_with_client(
+            &cassette_client("candidate_lookup"),
+            &search_config("高かった"),
+        )
+        .unwrap()
+        .into();
+        let inflection = result.inflection.unwrap();
+        assert_eq!(inflection.base_form, "高い");
+        assert_eq!(inflection.reasons, vec!["past"]);
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let Err(err) =
+            lookup_jisho_with_client(&synthetic_client("too_many_requests"), &search_config("猫"))
+        else {
+            panic!("a 429 response should fail the lookup");
+        };
+        let err = err
+            .downcast_ref::<crate::translators::RateLimitedError>()
+            .unwrap();
+        assert_eq!(err.provider, SearchProvider::Jisho);
+    }
+}
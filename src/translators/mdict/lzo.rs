@@ -0,0 +1,172 @@
+//! Decompression of LZO1X, used by the blocks of older MDict files
+//!
+//! A port of `lzo1x_decompress_safe` of the Linux kernel, without the run length extension of
+//! its newer bitstream, which MDict files don't use.
+//!
+//! The stream is a series of instructions, each copying literal bytes from the input, or a match
+//! of bytes already written. The low two bits of a match instruction are the number of literals
+//! following it, and the state remembers them, as it changes the meaning of the next short
+//! instructions.
+
+/// The stream is truncated or points outside of the output
+#[derive(Debug, Clone)]
+pub struct LzoError;
+
+impl std::fmt::Display for LzoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "corrupted LZO stream")
+    }
+}
+
+impl std::error::Error for LzoError {}
+
+/// Furthest distance of the matches of two bytes
+const M2_MAX_OFFSET: usize = 0x0800;
+
+struct Decompressor<'a> {
+    input: &'a [u8],
+    position: usize,
+    output: Vec<u8>,
+}
+
+impl Decompressor<'_> {
+    fn byte(&mut self) -> Result<usize, LzoError> {
+        let byte = *self.input.get(self.position).ok_or(LzoError)?;
+        self.position += 1;
+        Ok(byte as usize)
+    }
+
+    fn le16(&mut self) -> Result<usize, LzoError> {
+        Ok(self.byte()? | (self.byte()? << 8))
+    }
+
+    /// A length of more than the `bits` of the instruction: zero bytes add 255 each, and the
+    /// next byte is added to the most the bits hold
+    fn long_length(&mut self, max: usize) -> Result<usize, LzoError> {
+        let mut length = max;
+        loop {
+            match self.byte()? {
+                0 => length += 255,
+                byte => return Ok(length + byte),
+            }
+        }
+    }
+
+    fn copy_literals(&mut self, count: usize) -> Result<(), LzoError> {
+        let literals = self
+            .input
+            .get(self.position..self.position + count)
+            .ok_or(LzoError)?;
+        self.output.extend_from_slice(literals);
+        self.position += count;
+        Ok(())
+    }
+
+    /// Copies `length` bytes from `distance` bytes back, which may overlap the bytes copied
+    fn copy_match(&mut self, distance: usize, length: usize) -> Result<(), LzoError> {
+        let start = self
+            .output
+            .len()
+            .checked_sub(distance)
+            .filter(|_| distance > 0)
+            .ok_or(LzoError)?;
+        for index in start..start + length {
+            self.output.push(self.output[index]);
+        }
+        Ok(())
+    }
+}
+
+/// Decompresses the stream, allocating `capacity` bytes up front for the output
+pub fn decompress(input: &[u8], capacity: usize) -> Result<Vec<u8>, LzoError> {
+    let mut lzo = Decompressor {
+        input,
+        position: 0,
+        output: Vec::with_capacity(capacity),
+    };
+    // The literals after the last match, 4 after a run of literals
+    let mut state = 0;
+
+    if input.first().is_some_and(|first| *first > 17) {
+        let count = lzo.byte()? - 17;
+        lzo.copy_literals(count)?;
+        state = if count < 4 { count } else { 4 };
+    }
+    loop {
+        let instruction = lzo.byte()?;
+        // The number of literals following a match is in its low two bits
+        let (distance, length, literals) = match instruction {
+            0..=15 if state == 0 => {
+                let count = match instruction {
+                    0 => lzo.long_length(15)?,
+                    count => count,
+                };
+                lzo.copy_literals(count + 3)?;
+                state = 4;
+                continue;
+            }
+            0..=15 => {
+                let distance = 1 + (instruction >> 2) + (lzo.byte()? << 2);
+                match state {
+                    4 => (distance + M2_MAX_OFFSET, 3, instruction & 3),
+                    _ => (distance, 2, instruction & 3),
+                }
+            }
+            64.. => (
+                1 + ((instruction >> 2) & 7) + (lzo.byte()? << 3),
+                (instruction >> 5) + 1,
+                instruction & 3,
+            ),
+            32..=63 => {
+                let length = match instruction & 31 {
+                    0 => lzo.long_length(31)?,
+                    length => length,
+                } + 2;
+                let next = lzo.le16()?;
+                (1 + (next >> 2), length, next & 3)
+            }
+            16..=31 => {
+                let length = match instruction & 7 {
+                    0 => lzo.long_length(7)?,
+                    length => length,
+                } + 2;
+                let next = lzo.le16()?;
+                let distance = ((instruction & 8) << 11) + (next >> 2);
+                if distance == 0 {
+                    // The end of the stream
+                    return Ok(lzo.output);
+                }
+                (distance + 0x4000, length, next & 3)
+            }
+        };
+        lzo.copy_match(distance, length)?;
+        lzo.copy_literals(literals)?;
+        state = literals;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decompress_streams() {
+        // A run of literals, and the end of the stream
+        assert_eq!(
+            decompress(b"\x01wdym\x11\x00\x00", 4).unwrap(),
+            b"wdym".to_vec()
+        );
+        // Three literals, then a match of 9 bytes from 3 bytes back
+        assert_eq!(
+            decompress(b"\x14abc\x27\x08\x00\x11\x00\x00", 12).unwrap(),
+            b"abcabcabcabc".to_vec()
+        );
+        // A match of 3 bytes from 2 back, followed by 2 literals
+        assert_eq!(
+            decompress(b"\x01abcd\x46\x00xy\x11\x00\x00", 9).unwrap(),
+            b"abcdcdcxy".to_vec()
+        );
+        assert!(decompress(b"\x14ab", 2).is_err());
+        assert!(decompress(b"\x14abc\x27\x20\x00\x11\x00\x00", 12).is_err());
+    }
+}
@@ -0,0 +1,899 @@
+//! Dictionaries in the MDict format, looked up offline
+//!
+//! Many bilingual dictionaries are only distributed as MDict files: an `.mdx` file of entries
+//! written in HTML, with an optional `.mdd` file of the images, sounds and styles they use. They
+//! are not shipped with wdym. Put them in an `mdict` directory of the data directory, see
+//! [`crate::data`], and every `.mdx` file of it is looked up.
+//!
+//! An MDict file starts with a header of XML attributes, followed by the keys and the records,
+//! both split into blocks compressed with zlib or LZO. The keys are read when the file is opened,
+//! and the records when they are looked up. Files of versions 1.2 and 2.0 are read, but not the
+//! ones encrypted with a registration key.
+//!
+//! Entries of the form `@@@LINK=<key>` redirect to the entry of another key, and are followed.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use wdym::search;
+//! use wdym::translators::mdict::Mdict;
+//! use wdym::translators::SearchProvider;
+//!
+//! let dictionary = Mdict::open("Oxford Advanced Learner's Dictionary.mdx").unwrap();
+//! let search_options = search::SearchConfig {
+//!     query: "serendipity".to_string(),
+//!     provider: SearchProvider::Mdict,
+//!     ..Default::default()
+//! };
+//! println!("{}", dictionary.search(&search_options).unwrap());
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+use encoding_rs::{Encoding, UTF_16LE, UTF_8};
+use ripemd::{Digest, Ripemd128};
+
+use crate::data::{self, MissingDataError};
+use crate::html;
+use crate::search;
+
+use super::SearchProvider;
+
+pub mod lzo;
+
+/// The file isn't an MDict file that can be read
+#[derive(Debug, Clone)]
+pub struct MdictError(String);
+
+impl std::fmt::Display for MdictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to read the MDict file: {}", self.0)
+    }
+}
+
+impl std::error::Error for MdictError {}
+
+/// What an MDict file is read from
+trait Source: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Source for T {}
+
+/// Reads a big endian number, of 8 bytes from version 2.0 and of 4 bytes before
+fn read_number(reader: &mut impl Read, is_v2: bool) -> std::io::Result<u64> {
+    match is_v2 {
+        true => {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_be_bytes(bytes))
+        }
+        false => {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_be_bytes(bytes).into())
+        }
+    }
+}
+
+/// Reads `size` bytes, failing if the file is shorter
+fn read_bytes(reader: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(size).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != size {
+        Err(MdictError("the file is truncated".to_string()))?;
+    }
+    Ok(bytes)
+}
+
+/// Most bytes allocated up front for the data of a block, which grows past it if needed
+const MAX_CAPACITY_HINT: usize = 16 << 20;
+
+/// Decompresses a block: 4 bytes of compression type, 4 bytes of checksum, then the data
+fn decompress_block(block: &[u8], size: usize) -> Result<Vec<u8>> {
+    let (Some(kind), Some(data)) = (block.get(..4), block.get(8..)) else {
+        Err(MdictError("a block is truncated".to_string()))?
+    };
+    // The size is read from the file, and may be corrupt
+    let capacity = size.min(MAX_CAPACITY_HINT);
+    Ok(match kind {
+        [0, 0, 0, 0] => data.to_vec(),
+        [1, 0, 0, 0] => lzo::decompress(data, capacity)?,
+        [2, 0, 0, 0] => {
+            let mut res = Vec::with_capacity(capacity);
+            flate2::read::ZlibDecoder::new(data).read_to_end(&mut res)?;
+            res
+        }
+        _ => Err(MdictError(format!("unknown compression {:?}", kind)))?,
+    })
+}
+
+/// Decrypts the block of the key block info, encrypted with the checksum of its data
+fn decrypt_key_block_info(block: &mut [u8]) {
+    if block.len() < 8 {
+        return;
+    }
+    let mut hasher = Ripemd128::new();
+    hasher.update(&block[4..8]);
+    hasher.update(0x3695u32.to_le_bytes());
+    let key = hasher.finalize();
+    let mut previous = 0x36;
+    for (index, byte) in block[8..].iter_mut().enumerate() {
+        let encrypted = *byte;
+        *byte = encrypted.rotate_left(4) ^ previous ^ (index as u8) ^ key[index % key.len()];
+        previous = encrypted;
+    }
+}
+
+/// The attributes of the XML header: `<Dictionary Encoding="UTF-8" Title="..." />`
+fn parse_header(header: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = header;
+    while let Some(equals) = rest.find("=\"") {
+        let name = rest[..equals]
+            .rsplit(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        let value = &rest[equals + 2..];
+        let end = value.find('"').unwrap_or(value.len());
+        attributes.insert(name.to_string(), value[..end].to_string());
+        rest = value.get(end + 1..).unwrap_or_default();
+    }
+    attributes
+}
+
+/// The byte index of the first null character, of `width` bytes
+fn find_terminator(bytes: &[u8], width: usize) -> Option<usize> {
+    bytes
+        .chunks(width)
+        .position(|chunk| chunk.len() == width && chunk.iter().all(|byte| *byte == 0))
+        .map(|index| index * width)
+}
+
+/// Where a block of records is, in the file and in the records once decompressed
+#[derive(Debug, Clone, Copy)]
+struct RecordBlock {
+    position: u64,
+    compressed_size: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// The keys and records of an MDX or MDD file
+struct Archive {
+    source: Mutex<Box<dyn Source>>,
+    header: HashMap<String, String>,
+    encoding: &'static Encoding,
+    /// The keys, with the offsets of their records, in the order of the records
+    keys: Vec<(String, u64)>,
+    record_blocks: Vec<RecordBlock>,
+}
+
+impl std::fmt::Debug for Archive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Archive")
+            .field("header", &self.header)
+            .field("encoding", &self.encoding)
+            .field("keys", &self.keys.len())
+            .field("record_blocks", &self.record_blocks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Archive {
+    /// Reads the header and the keys of the file. The keys of MDD files are always in UTF-16.
+    fn read(mut source: Box<dyn Source>, is_mdd: bool) -> Result<Self> {
+        let mut size = [0; 4];
+        source.read_exact(&mut size)?;
+        let header = read_bytes(&mut source, u32::from_be_bytes(size).into())?;
+        // Followed by its checksum
+        source.seek(SeekFrom::Current(4))?;
+        let header = parse_header(&UTF_16LE.decode_without_bom_handling(&header).0);
+
+        let version: f32 = header
+            .get("GeneratedByEngineVersion")
+            .and_then(|version| version.trim().parse().ok())
+            .unwrap_or(2.0);
+        if version >= 3.0 {
+            Err(MdictError(format!("version {} is not supported", version)))?;
+        }
+        let is_v2 = version >= 2.0;
+        let encrypted: u8 = match header.get("Encrypted").map(String::as_str) {
+            None | Some("" | "No") => 0,
+            Some("Yes") => 1,
+            Some(flags) => flags.trim().parse().unwrap_or(0),
+        };
+        if encrypted & 1 != 0 {
+            Err(MdictError(
+                "the keys are encrypted with a registration key".to_string(),
+            ))?;
+        }
+        let encoding = match header.get("Encoding").map(|label| label.trim()) {
+            _ if is_mdd => UTF_16LE,
+            None | Some("") => UTF_8,
+            Some(label) => Encoding::for_label(label.as_bytes())
+                .ok_or(MdictError(format!("unknown encoding {}", label)))?,
+        };
+        let width = if encoding == UTF_16LE { 2 } else { 1 };
+
+        // The sizes of the key section
+        let key_header = read_bytes(&mut source, if is_v2 { 40 } else { 16 })?;
+        let mut key_header = key_header.as_slice();
+        let block_count = read_number(&mut key_header, is_v2)?;
+        let _entry_count = read_number(&mut key_header, is_v2)?;
+        let info_size = match is_v2 {
+            true => read_number(&mut key_header, is_v2)?,
+            false => 0,
+        };
+        let info_compressed_size = read_number(&mut key_header, is_v2)?;
+        let _blocks_size = read_number(&mut key_header, is_v2)?;
+        if is_v2 {
+            // Followed by their checksum
+            source.seek(SeekFrom::Current(4))?;
+        }
+
+        // The sizes of every key block
+        let mut info = read_bytes(&mut source, info_compressed_size)?;
+        if is_v2 {
+            if encrypted & 2 != 0 {
+                decrypt_key_block_info(&mut info);
+            }
+            info = decompress_block(&info, info_size as usize)?;
+        }
+        let mut info = info.as_slice();
+        let mut key_blocks = Vec::new();
+        for _ in 0..block_count {
+            let _entry_count = read_number(&mut info, is_v2)?;
+            // The first and last keys of the block, null terminated from version 2.0
+            for _ in 0..2 {
+                let size = match is_v2 {
+                    true => {
+                        let mut size = [0; 2];
+                        info.read_exact(&mut size)?;
+                        u16::from_be_bytes(size) as usize + 1
+                    }
+                    false => {
+                        let mut size = [0; 1];
+                        info.read_exact(&mut size)?;
+                        size[0] as usize
+                    }
+                };
+                info = info
+                    .get(size * width..)
+                    .ok_or(MdictError("the key block info is truncated".to_string()))?;
+            }
+            let compressed_size = read_number(&mut info, is_v2)?;
+            let size = read_number(&mut info, is_v2)?;
+            key_blocks.push((compressed_size, size));
+        }
+
+        let mut keys = Vec::new();
+        for (compressed_size, size) in key_blocks {
+            let block = read_bytes(&mut source, compressed_size)?;
+            let block = decompress_block(&block, size as usize)?;
+            let mut rest = block.as_slice();
+            while !rest.is_empty() {
+                let offset = read_number(&mut rest, is_v2)?;
+                let end = find_terminator(rest, width)
+                    .ok_or(MdictError("a key is not terminated".to_string()))?;
+                let key = encoding.decode_without_bom_handling(&rest[..end]).0;
+                keys.push((key.into_owned(), offset));
+                rest = &rest[end + width..];
+            }
+        }
+
+        // The sizes of every record block, which follow
+        let block_count = read_number(&mut source, is_v2)?;
+        let _entry_count = read_number(&mut source, is_v2)?;
+        let info_size = read_number(&mut source, is_v2)?;
+        let _blocks_size = read_number(&mut source, is_v2)?;
+        let info = read_bytes(&mut source, info_size)?;
+        let mut info = info.as_slice();
+        let mut position = source.stream_position()?;
+        let mut offset = 0;
+        let mut record_blocks = Vec::new();
+        for _ in 0..block_count {
+            let compressed_size = read_number(&mut info, is_v2)?;
+            let size = read_number(&mut info, is_v2)?;
+            record_blocks.push(RecordBlock {
+                position,
+                compressed_size,
+                offset,
+                size,
+            });
+            position += compressed_size;
+            offset += size;
+        }
+
+        Ok(Self {
+            source: Mutex::new(source),
+            header,
+            encoding,
+            keys,
+            record_blocks,
+        })
+    }
+
+    /// The record of the key at `index`, up to the record of the next key
+    fn record(&self, index: usize) -> Result<Vec<u8>> {
+        let start = self.keys[index].1;
+        let end = self.keys[index + 1..]
+            .iter()
+            .map(|(_, offset)| *offset)
+            .find(|offset| *offset > start)
+            .unwrap_or_else(|| {
+                self.record_blocks
+                    .last()
+                    .map_or(0, |block| block.offset + block.size)
+            });
+        let first = self
+            .record_blocks
+            .partition_point(|block| block.offset + block.size <= start);
+
+        let mut source = self.source.lock().expect("Lock poisoned");
+        let mut records = Vec::new();
+        let mut records_start = None;
+        for block in self.record_blocks[first..]
+            .iter()
+            .take_while(|block| block.offset < end)
+        {
+            source.seek(SeekFrom::Start(block.position))?;
+            let compressed = read_bytes(&mut *source, block.compressed_size)?;
+            records.append(&mut decompress_block(&compressed, block.size as usize)?);
+            records_start.get_or_insert(block.offset);
+        }
+        let record = records_start.and_then(|records_start| {
+            records.get((start - records_start) as usize..(end - records_start) as usize)
+        });
+        Ok(record
+            .ok_or(MdictError(format!(
+                "the record of {} is missing",
+                self.keys[index].0
+            )))?
+            .to_vec())
+    }
+}
+
+/// The prefix of entries redirecting to another key
+const LINK: &str = "@@@LINK=";
+
+/// Most redirects followed, in case they loop
+const MAX_REDIRECTS: usize = 5;
+
+/// An MDX file, and its MDD file of resources
+#[derive(Debug)]
+pub struct Mdict {
+    title: String,
+    entries: Archive,
+    /// The keys, by their text as looked up
+    index: HashMap<String, Vec<usize>>,
+    resources: Option<Archive>,
+}
+
+/// The dictionaries of a directory, and the files that couldn't be read
+#[derive(Debug, Default)]
+pub struct Installed {
+    pub dictionaries: Vec<Mdict>,
+    /// Why each file skipped couldn't be read, like `old.mdx: version 3.0 is not supported`
+    pub skipped: Vec<String>,
+}
+
+impl Installed {
+    /// Opens the MDX files, skipping the ones that can't be read
+    ///
+    /// Fails if none of them can be read.
+    pub fn open(paths: impl IntoIterator<Item = PathBuf>) -> Result<Self> {
+        let mut res = Self::default();
+        for path in paths {
+            match Mdict::open(&path) {
+                Ok(dictionary) => res.dictionaries.push(dictionary),
+                Err(err) => res.skipped.push(format!(
+                    "{}: {}",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    err
+                )),
+            }
+        }
+        if res.dictionaries.is_empty() && !res.skipped.is_empty() {
+            Err(MdictError(res.skipped.join(", ")))?;
+        }
+        Ok(res)
+    }
+}
+
+/// The dictionaries in the data directory
+static DEFAULT: Mutex<Option<Arc<Installed>>> = Mutex::new(None);
+
+const MDICT_DIRS: &[&str] = &["mdict"];
+
+impl Mdict {
+    /// Reads the keys of an MDX file
+    pub fn parse(mdx: impl Read + Seek + Send + 'static) -> Result<Self> {
+        let entries = Archive::read(Box::new(mdx), false)?;
+        // Files made without a title keep the placeholder of the MDict tools
+        let title = entries
+            .header
+            .get("Title")
+            .filter(|title| !title.is_empty() && !title.starts_with("Title ("))
+            .cloned()
+            .unwrap_or_else(|| "MDict".to_string());
+        let mut res = Self {
+            title,
+            index: HashMap::new(),
+            entries,
+            resources: None,
+        };
+        for (index, (key, _)) in res.entries.keys.iter().enumerate() {
+            res.index.entry(res.normalize(key)).or_default().push(index);
+        }
+        Ok(res)
+    }
+
+    /// Opens an MDX file, and the MDD file of the same name if there is one
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut res = Self::parse(BufReader::new(File::open(path)?))?;
+        if res.title == "MDict" {
+            if let Some(stem) = path.file_stem() {
+                res.title = stem.to_string_lossy().to_string();
+            }
+        }
+        let mdd = path.with_extension("mdd");
+        if mdd.is_file() {
+            res.resources = Some(Archive::read(
+                Box::new(BufReader::new(File::open(mdd)?)),
+                true,
+            )?);
+        }
+        Ok(res)
+    }
+
+    /// The dictionaries of the `mdict` directory of the data directory, opened on first use
+    ///
+    /// Files that can't be read, like encrypted ones, are skipped rather than hiding the others.
+    pub fn installed() -> Result<Arc<Installed>> {
+        let mut default = DEFAULT.lock().expect("Lock poisoned");
+        if let Some(dictionaries) = default.as_ref() {
+            return Ok(Arc::clone(dictionaries));
+        }
+        let missing = || MissingDataError {
+            name: "MDict".to_string(),
+            files: vec!["mdict/*.mdx".to_string()],
+        };
+        let dir = data::find_dir(MDICT_DIRS).ok_or_else(missing)?;
+        let mut paths: Vec<_> = std::fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<std::io::Result<_>>()?;
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "mdx"));
+        paths.sort();
+        if paths.is_empty() {
+            Err(missing())?;
+        }
+        let installed = Arc::new(Installed::open(paths)?);
+        *default = Some(Arc::clone(&installed));
+        Ok(installed)
+    }
+
+    /// The text of a key as looked up, following the case and punctuation settings of the file
+    fn normalize(&self, key: &str) -> String {
+        let is_yes = |name: &str| {
+            self.entries
+                .header
+                .get(name)
+                .is_some_and(|value| value.eq_ignore_ascii_case("yes"))
+        };
+        let mut key = key.trim().to_string();
+        if is_yes("StripKey") {
+            key.retain(|c| c.is_alphanumeric());
+        }
+        if !is_yes("KeyCaseSensitive") {
+            key = key.to_lowercase();
+        }
+        key
+    }
+
+    /// The keys and HTML of the entries of the key, following the redirects
+    pub fn entries(&self, key: &str) -> Result<Vec<(String, String)>> {
+        let mut res = Vec::new();
+        for index in self.index.get(&self.normalize(key)).into_iter().flatten() {
+            let mut index = *index;
+            let mut redirects = 0;
+            let html = loop {
+                let record = self.entries.record(index)?;
+                let text = self.entries.encoding.decode_without_bom_handling(&record).0;
+                let text = text.trim_end_matches(['\0', '\r', '\n']);
+                let target = match text.strip_prefix(LINK) {
+                    None => break text.to_string(),
+                    Some(_) if redirects == MAX_REDIRECTS => break String::new(),
+                    Some(target) => target.trim(),
+                };
+                match self.index.get(&self.normalize(target)) {
+                    Some(indexes) => index = indexes[0],
+                    None => break String::new(),
+                }
+                redirects += 1;
+            };
+            let key = self.entries.keys[index].0.clone();
+            if !html.is_empty() && !res.iter().any(|(found, _)| *found == key) {
+                res.push((key, html));
+            }
+        }
+        Ok(res)
+    }
+
+    /// The file of the MDD file at the path, like `/images/cat.png`
+    pub fn resource(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let Some(resources) = &self.resources else {
+            return Ok(None);
+        };
+        let path = format!(
+            "\\{}",
+            path.trim_start_matches(['/', '\\']).replace('/', "\\")
+        );
+        let index = resources
+            .keys
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(&path));
+        index.map(|index| resources.record(index)).transpose()
+    }
+
+    pub fn search(&self, search_options: &search::SearchConfig) -> Result<SearchResult> {
+        Ok(SearchResult {
+            entries: self
+                .entries(&search_options.query)?
+                .into_iter()
+                .map(|(key, html)| Entry {
+                    dictionary: self.title.clone(),
+                    key,
+                    html,
+                })
+                .collect(),
+            skipped: Vec::new(),
+        })
+    }
+}
+
+/// An entry of a dictionary
+#[derive(Debug, Clone)]
+struct Entry {
+    dictionary: String,
+    key: String,
+    html: String,
+}
+
+/// The entries found in MDict dictionaries
+///
+/// Can be converted to `search::SearchResult`, using `from` or `into`
+#[derive(Debug)]
+pub struct SearchResult {
+    entries: Vec<Entry>,
+    /// The files of the installed dictionaries that couldn't be read
+    skipped: Vec<String>,
+}
+
+impl From<SearchResult> for search::SearchResult {
+    fn from(value: SearchResult) -> Self {
+        let definitions: Vec<search::Definition> = value
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let lines = html::to_styled_lines(&entry.html);
+                search::Definition {
+                    headword: Some(search::Word {
+                        text: entry.key,
+                        reading: None,
+                    }),
+                    pitch_accents: None,
+                    other_spellings: None,
+                    meaning: lines
+                        .iter()
+                        .map(|line| line.iter().map(|span| span.text.as_str()).collect())
+                        .collect::<Vec<String>>()
+                        .join("; "),
+                    rich_meaning: Some(lines),
+                    pos: String::new(),
+                    reverse_translation: None,
+                    confidence: None,
+                    frequency: None,
+                    examples: None,
+                    relations: None,
+                }
+            })
+            .collect();
+
+        search::SearchResult {
+            provider: SearchProvider::Mdict,
+            translations: None,
+            definitions: (!definitions.is_empty()).then_some(definitions),
+            src_lang: None,
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: None,
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: None,
+            tokens: None,
+            warnings: (!value.skipped.is_empty()).then(|| {
+                value
+                    .skipped
+                    .iter()
+                    .map(|skipped| format!("Skipped the MDict file {}", skipped))
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{} [{}]", entry.key, entry.dictionary)?;
+            for line in html::to_plain_text(&entry.html) {
+                writeln!(f, "\t{}", line)?;
+            }
+        }
+        for skipped in &self.skipped {
+            writeln!(f, "skipped {}", skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Looks up the query in every installed dictionary
+pub fn lookup_mdict(search_options: &search::SearchConfig) -> Result<SearchResult> {
+    let installed = Mdict::installed()?;
+    let mut entries = Vec::new();
+    for dictionary in &installed.dictionaries {
+        entries.append(&mut dictionary.search(search_options)?.entries);
+    }
+    Ok(SearchResult {
+        entries,
+        skipped: installed.skipped.clone(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    /// Compresses a block with zlib, or stores it as LZO literals
+    fn block(data: &[u8], lzo: bool) -> Vec<u8> {
+        // The checksum isn't checked, but is the key of the encryption
+        let mut res = vec![if lzo { 1 } else { 2 }, 0, 0, 0, 1, 2, 3, 4];
+        if lzo {
+            res.push(17 + data.len() as u8);
+            res.extend(data);
+            res.extend([0x11, 0, 0]);
+        } else {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            res.append(&mut encoder.finish().unwrap());
+        }
+        res
+    }
+
+    fn encrypt(block: &mut [u8]) {
+        let mut hasher = Ripemd128::new();
+        hasher.update(&block[4..8]);
+        hasher.update(0x3695u32.to_le_bytes());
+        let key = hasher.finalize();
+        let mut previous = 0x36;
+        for (index, byte) in block[8..].iter_mut().enumerate() {
+            *byte = (*byte ^ previous ^ (index as u8) ^ key[index % key.len()]).rotate_left(4);
+            previous = *byte;
+        }
+    }
+
+    /// Writes a version 2.0 file of one key block and one record block
+    fn mdict_file(attributes: &str, entries: &[(&str, &[u8])], is_mdd: bool, lzo: bool) -> Vec<u8> {
+        mdict_file_of_version("2.0", attributes, entries, is_mdd, lzo)
+    }
+
+    /// Writes a file of one key block and one record block, in the layout of version 1.2 or 2.0
+    fn mdict_file_of_version(
+        version: &str,
+        attributes: &str,
+        entries: &[(&str, &[u8])],
+        is_mdd: bool,
+        lzo: bool,
+    ) -> Vec<u8> {
+        let is_v2 = version == "2.0";
+        let encode = |text: &str| match is_mdd {
+            true => utf16(text),
+            false => text.as_bytes().to_vec(),
+        };
+        // Numbers are of 8 bytes from version 2.0, and of 4 bytes before
+        let number = |number: usize| match is_v2 {
+            true => (number as u64).to_be_bytes().to_vec(),
+            false => (number as u32).to_be_bytes().to_vec(),
+        };
+        let width = if is_mdd { 2 } else { 1 };
+        let mut file = Vec::new();
+        let header = utf16(&format!(
+            "<Dictionary GeneratedByEngineVersion=\"{}\" {}/>\r\n\0",
+            version, attributes
+        ));
+        file.extend((header.len() as u32).to_be_bytes());
+        file.extend(header);
+        file.extend([0; 4]);
+
+        let (mut keys, mut records) = (Vec::new(), Vec::new());
+        for (key, record) in entries {
+            keys.extend(number(records.len()));
+            keys.extend(encode(key));
+            keys.extend(vec![0; width]);
+            records.extend(*record);
+        }
+        let key_block = block(&keys, lzo);
+        let mut info = number(entries.len());
+        for key in [entries[0].0, entries[entries.len() - 1].0] {
+            // The first and last keys are null terminated from version 2.0
+            let key = encode(key);
+            match is_v2 {
+                true => info.extend(((key.len() / width) as u16).to_be_bytes()),
+                false => info.push((key.len() / width) as u8),
+            }
+            info.extend(key);
+            if is_v2 {
+                info.extend(vec![0; width]);
+            }
+        }
+        info.extend(number(key_block.len()));
+        info.extend(number(keys.len()));
+        // The key block info is compressed from version 2.0
+        let mut info_block = match is_v2 {
+            true => block(&info, false),
+            false => info.clone(),
+        };
+        if attributes.contains("Encrypted=\"2\"") {
+            encrypt(&mut info_block);
+        }
+        let sizes = match is_v2 {
+            true => vec![
+                1,
+                entries.len(),
+                info.len(),
+                info_block.len(),
+                key_block.len(),
+            ],
+            false => vec![1, entries.len(), info_block.len(), key_block.len()],
+        };
+        for size in sizes {
+            file.extend(number(size));
+        }
+        if is_v2 {
+            file.extend([0; 4]);
+        }
+        file.extend(info_block);
+        file.extend(key_block);
+
+        let record_block = block(&records, lzo);
+        let record_info = [record_block.len(), records.len()];
+        for size in [
+            1,
+            entries.len(),
+            record_info.len() * number(0).len(),
+            record_block.len(),
+        ] {
+            file.extend(number(size));
+        }
+        for size in record_info {
+            file.extend(number(size));
+        }
+        file.extend(record_block);
+        file
+    }
+
+    const ENTRIES: &[(&str, &[u8])] = &[
+        ("Cat", b"<b>cat</b> <i>noun</i><br>a small animal\r\n\0"),
+        ("cats", b"@@@LINK=cat\r\n\0"),
+        ("dog", b"a loyal animal\r\n\0"),
+        ("loop", b"@@@LINK=loop\r\n\0"),
+    ];
+
+    fn search(dictionary: &Mdict, query: &str) -> String {
+        dictionary
+            .search(&search::SearchConfig {
+                query: query.to_string(),
+                provider: SearchProvider::Mdict,
+                ..Default::default()
+            })
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn read_entries() {
+        for (attributes, lzo) in [
+            ("Encoding=\"UTF-8\" Title=\"Pets\"", false),
+            ("Encoding=\"UTF-8\" Encrypted=\"2\" Title=\"Pets\"", true),
+        ] {
+            let file = mdict_file(attributes, ENTRIES, false, lzo);
+            let dictionary = Mdict::parse(Cursor::new(file)).unwrap();
+            assert_eq!(
+                search(&dictionary, "cat"),
+                "Cat [Pets]\n\tcat noun\n\ta small animal\n"
+            );
+            // Redirected
+            assert_eq!(
+                search(&dictionary, "Cats"),
+                "Cat [Pets]\n\tcat noun\n\ta small animal\n"
+            );
+            assert_eq!(search(&dictionary, "dog"), "dog [Pets]\n\ta loyal animal\n");
+            assert_eq!(search(&dictionary, "loop"), "");
+            assert_eq!(search(&dictionary, "bird"), "");
+        }
+
+        let file = mdict_file("Encrypted=\"1\"", ENTRIES, false, false);
+        assert!(Mdict::parse(Cursor::new(file)).is_err());
+    }
+
+    #[test]
+    fn skip_unreadable_files() {
+        let dir = std::env::temp_dir().join(format!("wdym-mdict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (good, bad) = (dir.join("pets.mdx"), dir.join("locked.mdx"));
+        std::fs::write(&good, mdict_file("Title=\"Pets\"", ENTRIES, false, false)).unwrap();
+        std::fs::write(&bad, mdict_file("Encrypted=\"1\"", ENTRIES, false, false)).unwrap();
+
+        let installed = Installed::open([good, bad.clone()]);
+        let only_bad = Installed::open([bad]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let installed = installed.unwrap();
+        assert_eq!(installed.dictionaries.len(), 1);
+        assert_eq!(installed.skipped.len(), 1);
+        assert!(installed.skipped[0].starts_with("locked.mdx: "));
+        assert!(only_bad.is_err());
+    }
+
+    #[test]
+    fn read_version_1() {
+        for lzo in [false, true] {
+            let file = mdict_file_of_version("1.2", "Title=\"Pets\"", ENTRIES, false, lzo);
+            let dictionary = Mdict::parse(Cursor::new(file)).unwrap();
+            assert_eq!(
+                search(&dictionary, "Cats"),
+                "Cat [Pets]\n\tcat noun\n\ta small animal\n"
+            );
+            assert_eq!(search(&dictionary, "dog"), "dog [Pets]\n\ta loyal animal\n");
+        }
+
+        let file = mdict_file_of_version("1.2", "", &[("\\cat.png", b"\x89PNG")], true, false);
+        let resources = Archive::read(Box::new(Cursor::new(file)), true).unwrap();
+        assert_eq!(resources.keys[0].0, "\\cat.png");
+        assert_eq!(resources.record(0).unwrap(), b"\x89PNG");
+    }
+
+    #[test]
+    fn corrupt_block_size() {
+        for lzo in [false, true] {
+            let data = decompress_block(&block(b"a loyal animal", lzo), usize::MAX).unwrap();
+            assert_eq!(data, b"a loyal animal");
+        }
+    }
+
+    #[test]
+    fn read_resources() {
+        let file = mdict_file("", &[("\\cat.png", b"\x89PNG")], true, false);
+        let resources = Archive::read(Box::new(Cursor::new(file)), true).unwrap();
+        let mut dictionary =
+            Mdict::parse(Cursor::new(mdict_file("", ENTRIES, false, false))).unwrap();
+        dictionary.resources = Some(resources);
+        assert_eq!(
+            dictionary.resource("/cat.png").unwrap(),
+            Some(b"\x89PNG".to_vec())
+        );
+        assert_eq!(dictionary.resource("dog.png").unwrap(), None);
+    }
+}
@@ -1,5 +1,10 @@
 //! Implementations of different translators and dictionaries
+use std::time::Duration;
+
 use clap::ValueEnum;
+use isolang::Language;
+
+use crate::http::RateLimit;
 
 #[derive(Debug, Clone)]
 pub struct TranslateError(String);
@@ -12,11 +17,67 @@ impl std::fmt::Display for TranslateError {
 
 impl std::error::Error for TranslateError {}
 
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Default)]
+/// The provider refused the request because too many were sent
+#[derive(Debug, Clone)]
+pub struct RateLimitedError {
+    pub provider: SearchProvider,
+    /// How long the provider asked to wait before trying again
+    pub retry_after: Option<Duration>,
+}
+
+impl std::fmt::Display for RateLimitedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rate limited by {}", self.provider)?;
+        if let Some(retry_after) = self.retry_after {
+            write!(f, ", retry after {} seconds", retry_after.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RateLimitedError {}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub enum SearchProvider {
     #[default]
     GoogleTranslate,
     Jisho,
+    /// The offline CC-CEDICT Chinese dictionary
+    Cedict,
+    /// The offline Princeton WordNet English dictionary
+    Wordnet,
+    /// Offline dictionaries in the MDict format
+    Mdict,
+    /// Dictionaries in the Yomitan format, imported with `import-yomitan`
+    Yomitan,
+}
+
+impl SearchProvider {
+    /// Lists the languages the provider can look up or translate
+    pub fn supported_languages(&self) -> Vec<Language> {
+        match self {
+            SearchProvider::GoogleTranslate => google_translate::supported_languages(),
+            SearchProvider::Jisho => vec![Language::Jpn, Language::Eng],
+            SearchProvider::Cedict => vec![Language::Zho, Language::Eng],
+            SearchProvider::Wordnet => vec![Language::Eng],
+            // Depends on the dictionaries installed, which don't tell their languages
+            SearchProvider::Mdict => Vec::new(),
+            SearchProvider::Yomitan => vec![Language::Jpn, Language::Eng],
+        }
+    }
+
+    /// How fast requests may be sent to the provider
+    pub fn rate_limit(&self) -> RateLimit {
+        match self {
+            SearchProvider::GoogleTranslate => google_translate::RATE_LIMIT,
+            SearchProvider::Jisho => RateLimit::new(5, 1.0),
+            // Looked up offline, without sending requests
+            SearchProvider::Cedict
+            | SearchProvider::Wordnet
+            | SearchProvider::Mdict
+            | SearchProvider::Yomitan => RateLimit::UNLIMITED,
+        }
+    }
 }
 
 impl std::fmt::Display for SearchProvider {
@@ -28,4 +89,9 @@ impl std::fmt::Display for SearchProvider {
     }
 }
 
+pub mod cedict;
 pub mod google_translate;
+pub mod jisho;
+pub mod mdict;
+pub mod wordnet;
+pub mod yomitan;
@@ -0,0 +1,566 @@
+//! The Princeton WordNet dictionary of English, looked up offline
+//!
+//! WordNet is not shipped with wdym. Download the database files of WordNet 3.0 or 3.1 from
+//! <https://wordnet.princeton.edu/download/current-version>, and put the `index.*`, `data.*` and
+//! `*.exc` files in a `wordnet` directory of the data directory, see [`crate::data`].
+//!
+//! Every meaning of a word is a synset, a set of synonyms with a gloss. The synsets are read from
+//! the data files when they are looked up, at the offsets listed by the index files.
+//!
+//! # Examples
+//! ```rust
+//! use wdym::search;
+//! use wdym::translators::wordnet::Wordnet;
+//! use wdym::translators::SearchProvider;
+//!
+//! let wordnet =
+//!     Wordnet::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/wordnet")).unwrap();
+//! let search_options = search::SearchConfig {
+//!     query: "dogs".to_string(),
+//!     provider: SearchProvider::Wordnet,
+//!     ..Default::default()
+//! };
+//! let result = wordnet.search(&search_options).unwrap();
+//! assert!(result.to_string().starts_with("dog (noun)"));
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+
+use crate::data::{self, MissingDataError};
+use crate::search::{self, Relation, RelationKind};
+
+use super::{SearchProvider, TranslateError};
+
+/// Parts of speech, each with its own index and data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Pos {
+    Noun,
+    Verb,
+    Adjective,
+    Adverb,
+}
+
+impl Pos {
+    const ALL: [Pos; 4] = [Pos::Noun, Pos::Verb, Pos::Adjective, Pos::Adverb];
+
+    /// The suffix of its files: `index.noun`, `data.noun` and `noun.exc`
+    fn file_name(&self) -> &'static str {
+        match self {
+            Pos::Noun => "noun",
+            Pos::Verb => "verb",
+            Pos::Adjective => "adj",
+            Pos::Adverb => "adv",
+        }
+    }
+
+    /// Reads the part of speech of the data files, `s` being an adjective satellite
+    fn parse(symbol: &str) -> Option<Self> {
+        match symbol {
+            "n" => Some(Pos::Noun),
+            "v" => Some(Pos::Verb),
+            "a" | "s" => Some(Pos::Adjective),
+            "r" => Some(Pos::Adverb),
+            _ => None,
+        }
+    }
+
+    /// Endings of inflected forms, and what replaces them in the base form
+    fn suffixes(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Pos::Noun => &[
+                ("s", ""),
+                ("ses", "s"),
+                ("xes", "x"),
+                ("zes", "z"),
+                ("ches", "ch"),
+                ("shes", "sh"),
+                ("men", "man"),
+                ("ies", "y"),
+            ],
+            Pos::Verb => &[
+                ("s", ""),
+                ("ies", "y"),
+                ("es", "e"),
+                ("es", ""),
+                ("ed", "e"),
+                ("ed", ""),
+                ("ing", "e"),
+                ("ing", ""),
+            ],
+            Pos::Adjective => &[("er", ""), ("est", ""), ("er", "e"), ("est", "e")],
+            Pos::Adverb => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pos::Noun => "noun",
+            Pos::Verb => "verb",
+            Pos::Adjective => "adjective",
+            Pos::Adverb => "adverb",
+        }
+        .fmt(f)
+    }
+}
+
+/// A link from a synset, or from one of its words, to another synset
+#[derive(Debug, Clone)]
+struct Pointer {
+    symbol: String,
+    offset: u64,
+    pos: Pos,
+    /// The word of the synset it links from, from 1, or 0 for the whole synset
+    source: usize,
+    /// The word of the target synset it links to, from 1, or 0 for the whole synset
+    target: usize,
+}
+
+impl Pointer {
+    fn relation(&self) -> Option<RelationKind> {
+        match self.symbol.as_str() {
+            "@" | "@i" => Some(RelationKind::Hypernym),
+            "~" | "~i" => Some(RelationKind::Hyponym),
+            "!" => Some(RelationKind::Antonym),
+            "+" => Some(RelationKind::Derived),
+            _ => None,
+        }
+    }
+}
+
+/// A meaning, with the words having it
+#[derive(Debug, Clone)]
+struct Synset {
+    pos: Pos,
+    words: Vec<String>,
+    pointers: Vec<Pointer>,
+    definition: String,
+    examples: Vec<String>,
+}
+
+impl Synset {
+    /// Reads a line of a data file
+    fn parse(line: &str) -> Option<Self> {
+        let (fields, gloss) = line.split_once(" | ").unwrap_or((line, ""));
+        let mut fields = fields.split_ascii_whitespace();
+        // Offset and lexicographer file
+        fields.nth(1)?;
+        let pos = Pos::parse(fields.next()?)?;
+        let word_count = usize::from_str_radix(fields.next()?, 16).ok()?;
+        let mut words = Vec::new();
+        for _ in 0..word_count {
+            words.push(lemma_text(fields.next()?));
+            // Lexical id
+            fields.next()?;
+        }
+        let pointer_count: usize = fields.next()?.parse().ok()?;
+        let mut pointers = Vec::new();
+        for _ in 0..pointer_count {
+            let symbol = fields.next()?.to_string();
+            let offset = fields.next()?.parse().ok()?;
+            let pos = Pos::parse(fields.next()?)?;
+            let source_target = fields.next()?;
+            pointers.push(Pointer {
+                symbol,
+                offset,
+                pos,
+                source: usize::from_str_radix(source_target.get(..2)?, 16).ok()?,
+                target: usize::from_str_radix(source_target.get(2..)?, 16).ok()?,
+            });
+        }
+
+        // The definition, followed by examples in quotes
+        let gloss = gloss.trim();
+        let (definition, examples) = gloss.split_once('"').unwrap_or((gloss, ""));
+        Some(Self {
+            pos,
+            words,
+            pointers,
+            definition: definition.trim().trim_end_matches(';').trim().to_string(),
+            examples: format!("\"{}", examples)
+                .split('"')
+                .skip(1)
+                .step_by(2)
+                .filter(|example| !example.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+/// Writes a lemma of the files as text: `Canis_familiaris` is `Canis familiaris`, and the
+/// position of adjectives, like `(a)`, is dropped
+fn lemma_text(lemma: &str) -> String {
+    let lemma = match lemma.find('(') {
+        Some(position) if lemma.ends_with(')') => &lemma[..position],
+        _ => lemma,
+    };
+    lemma.replace('_', " ")
+}
+
+/// Writes text the way lemmas are written in the index: `hot dog` is `hot_dog`
+fn index_key(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("_")
+}
+
+/// The WordNet database in a directory
+#[derive(Debug)]
+pub struct Wordnet {
+    dir: PathBuf,
+    /// Offsets of the synsets of every lemma, by part of speech
+    index: HashMap<(Pos, String), Vec<u64>>,
+    /// Base forms of the irregular inflections, like `goose` for `geese`
+    exceptions: HashMap<(Pos, String), Vec<String>>,
+}
+
+/// Names of the WordNet directory in the data directory
+const WORDNET_DIRS: &[&str] = &["wordnet", "dict"];
+
+/// The database in the data directory
+static DEFAULT: Mutex<Option<Arc<Wordnet>>> = Mutex::new(None);
+
+/// Most words listed for one kind of relation of a meaning
+const MAX_RELATED: usize = 10;
+
+impl Wordnet {
+    /// Reads the index and exception files in the directory
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut index = HashMap::new();
+        let mut exceptions = HashMap::new();
+        for pos in Pos::ALL {
+            let file = BufReader::new(File::open(dir.join(format!("index.{}", pos.file_name())))?);
+            for line in file.lines() {
+                let line = line?;
+                // The license is indented
+                if line.starts_with(' ') {
+                    continue;
+                }
+                let fields: Vec<&str> = line.split_ascii_whitespace().collect();
+                let (Some(lemma), Some(synset_count)) = (
+                    fields.first(),
+                    fields.get(2).and_then(|count| count.parse::<usize>().ok()),
+                ) else {
+                    continue;
+                };
+                let offsets = fields[fields.len().saturating_sub(synset_count)..]
+                    .iter()
+                    .filter_map(|offset| offset.parse().ok())
+                    .collect();
+                index.insert((pos, lemma.to_string()), offsets);
+            }
+
+            // The exception lists are optional
+            let Ok(file) = File::open(dir.join(format!("{}.exc", pos.file_name()))) else {
+                continue;
+            };
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                let mut words = line.split_ascii_whitespace();
+                if let Some(inflected) = words.next() {
+                    exceptions.insert(
+                        (pos, inflected.to_string()),
+                        words.map(str::to_string).collect(),
+                    );
+                }
+            }
+        }
+        Ok(Self {
+            dir,
+            index,
+            exceptions,
+        })
+    }
+
+    /// The database in the data directory, indexed on first use
+    pub fn installed() -> Result<Arc<Self>> {
+        let mut default = DEFAULT.lock().expect("Lock poisoned");
+        if let Some(wordnet) = default.as_ref() {
+            return Ok(Arc::clone(wordnet));
+        }
+        let dir = data::find_dir(WORDNET_DIRS).ok_or_else(|| MissingDataError {
+            name: "WordNet".to_string(),
+            files: WORDNET_DIRS.iter().map(|dir| format!("{}/", dir)).collect(),
+        })?;
+        let wordnet = Arc::new(Self::open(dir)?);
+        *default = Some(Arc::clone(&wordnet));
+        Ok(wordnet)
+    }
+
+    /// The lemmas the word may be an inflection of, itself first if it is a lemma
+    fn base_forms(&self, pos: Pos, word: &str) -> Vec<String> {
+        let key = (pos, word.to_string());
+        if self.index.contains_key(&key) {
+            return vec![word.to_string()];
+        }
+        if let Some(exceptions) = self.exceptions.get(&key) {
+            return exceptions.clone();
+        }
+        let mut res: Vec<String> = Vec::new();
+        for (suffix, replacement) in pos.suffixes() {
+            let Some(stem) = word.strip_suffix(suffix) else {
+                continue;
+            };
+            let base = format!("{}{}", stem, replacement);
+            if !base.is_empty()
+                && self.index.contains_key(&(pos, base.clone()))
+                && !res.contains(&base)
+            {
+                res.push(base);
+            }
+        }
+        res
+    }
+
+    /// Reads the synset at the offset of the data file
+    fn synset(&self, pos: Pos, offset: u64) -> Result<Synset> {
+        let mut file = BufReader::new(File::open(
+            self.dir.join(format!("data.{}", pos.file_name())),
+        )?);
+        file.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        file.read_line(&mut line)?;
+        Ok(Synset::parse(&line).ok_or_else(|| {
+            TranslateError(format!(
+                "malformed WordNet synset at {} in data.{}",
+                offset,
+                pos.file_name()
+            ))
+        })?)
+    }
+
+    /// The words related to the meaning of the word, at most `MAX_RELATED` of each kind
+    fn relations(&self, synset: &Synset, word: &str) -> Result<Vec<Relation>> {
+        let position = synset
+            .words
+            .iter()
+            .position(|synonym| synonym.eq_ignore_ascii_case(word))
+            .map_or(0, |position| position + 1);
+        let mut relations: Vec<Relation> = Vec::new();
+        for pointer in &synset.pointers {
+            // Lexical pointers link one word of the synset, keep the ones of the word looked up
+            if pointer.source != 0 && pointer.source != position {
+                continue;
+            }
+            let Some(kind) = pointer.relation() else {
+                continue;
+            };
+            let target = self.synset(pointer.pos, pointer.offset)?;
+            let Some(related) = target.words.get(pointer.target.saturating_sub(1)).cloned() else {
+                continue;
+            };
+            match relations.iter_mut().find(|relation| relation.kind == kind) {
+                Some(relation) if relation.words.len() >= MAX_RELATED => {}
+                Some(relation) => relation.words.push(related),
+                None => relations.push(Relation {
+                    kind,
+                    words: vec![related],
+                }),
+            }
+        }
+        Ok(relations)
+    }
+
+    /// Finds the meanings of the word or of its base forms, in every part of speech
+    pub fn search(&self, search_options: &search::SearchConfig) -> Result<SearchResult> {
+        let query = index_key(&search_options.query);
+        let mut senses = Vec::new();
+        let mut base_form = None;
+        for pos in Pos::ALL {
+            for lemma in self.base_forms(pos, &query) {
+                if lemma != query {
+                    base_form.get_or_insert_with(|| lemma_text(&lemma));
+                }
+                for offset in self.index.get(&(pos, lemma.clone())).into_iter().flatten() {
+                    let synset = self.synset(pos, *offset)?;
+                    let word = lemma_text(&lemma);
+                    let relations = self.relations(&synset, &word)?;
+                    senses.push(Sense {
+                        word,
+                        synset,
+                        relations,
+                    });
+                }
+            }
+        }
+        Ok(SearchResult { senses, base_form })
+    }
+}
+
+/// A meaning of the word looked up
+#[derive(Debug)]
+struct Sense {
+    /// The lemma of the query with this meaning
+    word: String,
+    synset: Synset,
+    relations: Vec<Relation>,
+}
+
+/// The search result of WordNet
+///
+/// Can be converted to `search::SearchResult`, using `from` or `into`
+#[derive(Debug)]
+pub struct SearchResult {
+    senses: Vec<Sense>,
+    /// The lemma an inflected query is found as
+    base_form: Option<String>,
+}
+
+impl From<SearchResult> for search::SearchResult {
+    fn from(value: SearchResult) -> Self {
+        let definitions: Vec<search::Definition> = value
+            .senses
+            .into_iter()
+            .map(|sense| {
+                let synonyms: Vec<String> = sense
+                    .synset
+                    .words
+                    .iter()
+                    .filter(|synonym| !synonym.eq_ignore_ascii_case(&sense.word))
+                    .cloned()
+                    .collect();
+                search::Definition {
+                    headword: Some(search::Word {
+                        text: sense.word,
+                        reading: None,
+                    }),
+                    pitch_accents: None,
+                    other_spellings: None,
+                    meaning: sense.synset.definition,
+                    rich_meaning: None,
+                    pos: sense.synset.pos.to_string(),
+                    reverse_translation: (!synonyms.is_empty()).then_some(synonyms),
+                    confidence: None,
+                    frequency: None,
+                    examples: (!sense.synset.examples.is_empty()).then(|| {
+                        sense
+                            .synset
+                            .examples
+                            .into_iter()
+                            .map(|text| search::Example {
+                                text,
+                                translation: None,
+                            })
+                            .collect()
+                    }),
+                    relations: (!sense.relations.is_empty()).then_some(sense.relations),
+                }
+            })
+            .collect();
+
+        search::SearchResult {
+            provider: SearchProvider::Wordnet,
+            translations: None,
+            definitions: (!definitions.is_empty()).then_some(definitions),
+            src_lang: Some("en".to_string()),
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: None,
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: value.base_form.map(|base_form| search::Inflection {
+                base_form,
+                reasons: Vec::new(),
+            }),
+            tokens: None,
+            warnings: None,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for sense in &self.senses {
+            writeln!(f, "{} ({})", sense.word, sense.synset.pos)?;
+            writeln!(f, "\t{}", sense.synset.definition)?;
+            for example in &sense.synset.examples {
+                writeln!(f, "\t\"{}\"", example)?;
+            }
+            for relation in &sense.relations {
+                writeln!(f, "\t{}: {}", relation.kind, relation.words.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Looks up the query in the installed WordNet
+pub fn lookup_wordnet(search_options: &search::SearchConfig) -> Result<SearchResult> {
+    Wordnet::installed()?.search(search_options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::SearchConfig;
+
+    fn search(query: &str) -> SearchResult {
+        let wordnet =
+            Wordnet::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/wordnet")).unwrap();
+        wordnet
+            .search(&SearchConfig {
+                query: query.to_string(),
+                provider: SearchProvider::Wordnet,
+                ..Default::default()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn synsets() {
+        assert_eq!(
+            search("Dog").to_string(),
+            "dog (noun)\n\
+            \ta member of the genus Canis (probably descended from the common wolf) that has \
+            been domesticated by man since prehistoric times\n\
+            \t\"the dog barked all night\"\n\
+            \tbroader: canine\n\
+            \tnarrower: puppy\n"
+        );
+
+        let result: search::SearchResult = search("domestic dog").into();
+        let definition = &result.definitions.unwrap()[0];
+        assert_eq!(definition.pos, "noun");
+        assert_eq!(
+            definition.reverse_translation,
+            Some(vec!["dog".to_string(), "Canis familiaris".to_string()])
+        );
+        assert!(result.inflection.is_none());
+    }
+
+    #[test]
+    fn lexical_relations() {
+        assert_eq!(
+            search("run").to_string(),
+            "run (verb)\n\
+            \tmove fast by using one's feet, with one foot off the ground at any given time\n\
+            \t\"Don't run--you'll be out of breath\"\n\
+            \t\"The children ran to the store\"\n\
+            \topposite: walk\n\
+            \trelated forms: runner\n"
+        );
+        assert!(search("good").to_string().contains("opposite: bad"));
+    }
+
+    #[test]
+    fn inflected_words() {
+        let result: search::SearchResult = search("ran").into();
+        assert_eq!(result.inflection.unwrap().base_form, "run");
+        assert!(search("puppies").to_string().starts_with("puppy (noun)"));
+        assert!(search("better").to_string().starts_with("good (adjective)"));
+        assert!(search("walking").to_string().starts_with("walk (verb)"));
+        assert!(search("cats").senses.is_empty());
+    }
+}
@@ -0,0 +1,1018 @@
+//! Dictionaries in the format of Yomichan and Yomitan, imported into a local store
+//!
+//! The community publishes many dictionaries for the Yomitan browser extension: JMdict,
+//! monolingual Japanese dictionaries, kanji dictionaries and frequency lists. They are ZIP
+//! archives of JSON files: an `index.json` naming the dictionary, and banks of terms, kanji,
+//! tags and metadata like frequencies.
+//!
+//! Import them with `wdym import-yomitan <archive>`. Their banks are copied into an SQLite
+//! database in the data directory, see [`crate::data`], indexed by spelling and reading, so the
+//! archives aren't read again on every lookup. Importing a dictionary with the title of an
+//! imported one replaces it. Frequency lists counting occurrences are converted to ranks.
+//!
+//! Conjugated queries are deinflected first, and the candidates are checked against the rules of
+//! the terms, like Yomitan does.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use std::fs::File;
+//!
+//! use wdym::search;
+//! use wdym::translators::yomitan::Yomitan;
+//! use wdym::translators::SearchProvider;
+//!
+//! let store = Yomitan::create().unwrap();
+//! store.import(File::open("jmdict_english.zip").unwrap()).unwrap();
+//! let search_options = search::SearchConfig {
+//!     query: "食べた".to_string(),
+//!     provider: SearchProvider::Yomitan,
+//!     ..Default::default()
+//! };
+//! let result = store.search(&search_options).unwrap();
+//! println!("{}", result);
+//! ```
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use color_eyre::Result;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::data;
+use crate::html;
+use crate::japanese::deinflect::{deinflect, Deinflection, WordTypes};
+use crate::japanese::kanji::KanjiInfo;
+use crate::search::{self, StyledSpan};
+
+use super::{SearchProvider, TranslateError};
+
+/// The database of the imported dictionaries, in the data directory
+const STORE_FILE: &str = "yomitan.sqlite";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS dictionaries (
+    id INTEGER PRIMARY KEY,
+    title TEXT NOT NULL UNIQUE,
+    revision TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS terms (
+    dictionary INTEGER NOT NULL,
+    expression TEXT NOT NULL,
+    reading TEXT NOT NULL,
+    definition_tags TEXT NOT NULL,
+    rules TEXT NOT NULL,
+    score INTEGER NOT NULL,
+    glossary TEXT NOT NULL,
+    sequence INTEGER,
+    term_tags TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS terms_expression ON terms (expression);
+CREATE INDEX IF NOT EXISTS terms_reading ON terms (reading);
+CREATE TABLE IF NOT EXISTS kanji (
+    dictionary INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    onyomi TEXT NOT NULL,
+    kunyomi TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    meanings TEXT NOT NULL,
+    stats TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS kanji_character ON kanji (character);
+CREATE TABLE IF NOT EXISTS tags (
+    dictionary INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    sort_order INTEGER NOT NULL,
+    notes TEXT NOT NULL,
+    score INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS tags_name ON tags (dictionary, name);
+CREATE TABLE IF NOT EXISTS term_meta (
+    dictionary INTEGER NOT NULL,
+    expression TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS term_meta_expression ON term_meta (expression);
+CREATE TABLE IF NOT EXISTS kanji_meta (
+    dictionary INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS kanji_meta_character ON kanji_meta (character);
+";
+
+/// The tables holding the banks of a dictionary
+const BANK_TABLES: [&str; 5] = ["terms", "kanji", "tags", "term_meta", "kanji_meta"];
+
+/// The prefixes of the bank files of an archive, numbered from 1: `term_bank_1.json`
+const BANK_FILES: [&str; 5] = [
+    "term_bank_",
+    "kanji_bank_",
+    "tag_bank_",
+    "term_meta_bank_",
+    "kanji_meta_bank_",
+];
+
+/// The archive isn't a dictionary that can be imported
+#[derive(Debug, Clone)]
+pub struct ImportError(String);
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to import the dictionary: {}", self.0)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// The `index.json` of an archive
+#[derive(Debug, Deserialize)]
+struct Index {
+    title: String,
+    #[serde(default)]
+    revision: String,
+    format: Option<u32>,
+    /// The format, in older dictionaries
+    version: Option<u32>,
+    /// The tags of version 1 dictionaries, which have no tag banks
+    #[serde(default, rename = "tagMeta")]
+    tag_meta: serde_json::Map<String, Value>,
+    /// How the frequencies are given, `rank-based` if missing or `occurrence-based`
+    #[serde(default, rename = "frequencyMode")]
+    frequency_mode: Option<String>,
+}
+
+/// What was imported from an archive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub title: String,
+    pub revision: String,
+    pub terms: usize,
+    pub kanji: usize,
+    pub tags: usize,
+    /// Rows of term and kanji metadata, like frequencies
+    pub meta: usize,
+}
+
+impl std::fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} terms, {} kanji, {} tags, {} metadata",
+            self.title, self.revision, self.terms, self.kanji, self.tags, self.meta
+        )
+    }
+}
+
+/// The string at `index` of a bank row, empty if missing
+fn str_at(row: &[Value], index: usize) -> &str {
+    row.get(index).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn int_at(row: &[Value], index: usize) -> i64 {
+    row.get(index).and_then(Value::as_i64).unwrap_or_default()
+}
+
+/// The word types of the space separated rules of a term
+fn word_types(rules: &str) -> WordTypes {
+    rules
+        .split_whitespace()
+        .map(|rule| match rule {
+            "v1" => WordTypes::V1,
+            "v5" => WordTypes::V5,
+            "vs" => WordTypes::VS,
+            "vk" => WordTypes::VK,
+            "adj-i" => WordTypes::ADJ_I,
+            _ => WordTypes::ANY,
+        })
+        .fold(WordTypes::ANY, |types, rule| types | rule)
+}
+
+/// Flattens the items of a glossary into lines of styled text
+///
+/// Items are plain strings, text, images, or structured content: a tree of HTML-like elements,
+/// written as HTML and rendered by [`html`]. Each item starts a new line, and images are
+/// dropped.
+fn flatten_glossary(glossary: &[Value]) -> Vec<Vec<StyledSpan>> {
+    let mut res = String::new();
+    for item in glossary {
+        res.push_str("<div>");
+        match item {
+            Value::String(text) => write_text(text, &mut res),
+            Value::Object(item) => match item.get("type").and_then(Value::as_str) {
+                Some("text") => write_text(
+                    item.get("text").and_then(Value::as_str).unwrap_or_default(),
+                    &mut res,
+                ),
+                Some("structured-content") => {
+                    if let Some(content) = item.get("content") {
+                        write_content(content, &mut res);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        res.push_str("</div>");
+    }
+    html::to_styled_lines(&res)
+}
+
+/// Writes text as HTML, keeping its line breaks
+fn write_text(text: &str, html: &mut String) {
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            html.push_str("<br>");
+        }
+        html.push_str(&html::escape(line));
+    }
+}
+
+/// Writes structured content as HTML
+fn write_content(content: &Value, html: &mut String) {
+    match content {
+        Value::String(text) => write_text(text, html),
+        Value::Array(children) => {
+            for child in children {
+                write_content(child, html);
+            }
+        }
+        Value::Object(element) => {
+            let tag = element
+                .get("tag")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match tag {
+                "br" => html.push_str("<br>"),
+                "img" => {}
+                _ if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                    write_content(element.get("content").unwrap_or(&Value::Null), html)
+                }
+                _ => {
+                    html.push('<');
+                    html.push_str(tag);
+                    if let Some(Value::Object(style)) = element.get("style") {
+                        html.push_str(" style=\"");
+                        html.push_str(&html::escape(&css(style)));
+                        html.push('"');
+                    }
+                    html.push('>');
+                    write_content(element.get("content").unwrap_or(&Value::Null), html);
+                    html.push_str("</");
+                    html.push_str(tag);
+                    html.push('>');
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The CSS of a style object, whose properties are in camel case: `fontWeight`
+fn css(style: &serde_json::Map<String, Value>) -> String {
+    let mut res = String::new();
+    for (property, value) in style {
+        let value = match value {
+            Value::String(value) => value.clone(),
+            Value::Number(value) => value.to_string(),
+            _ => continue,
+        };
+        for c in property.chars() {
+            if c.is_ascii_uppercase() {
+                res.push('-');
+            }
+            res.push(c.to_ascii_lowercase());
+        }
+        res.push_str(": ");
+        res.push_str(&value);
+        res.push_str("; ");
+    }
+    res
+}
+
+/// A term of an imported dictionary
+#[derive(Debug, Clone)]
+struct Entry {
+    /// The title of its dictionary
+    dictionary: String,
+    expression: String,
+    reading: String,
+    /// The notes of its definition tags, like `Ichidan verb`
+    tags: Vec<String>,
+    glossary: Vec<Vec<StyledSpan>>,
+    /// Whether any glossary item is structured content, and worth showing styled
+    structured: bool,
+}
+
+/// The local store of imported Yomitan dictionaries
+#[derive(Debug)]
+pub struct Yomitan {
+    connection: Mutex<Connection>,
+}
+
+/// The store in the data directory, opened on first use
+static DEFAULT: Mutex<Option<Arc<Yomitan>>> = Mutex::new(None);
+
+/// Most entries shown for a query
+const MAX_ENTRIES: usize = 30;
+
+impl Yomitan {
+    /// Opens the store at the path, creating it if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Opens the store of the data directory to import dictionaries, creating it if needed
+    pub fn create() -> Result<Self> {
+        let dir = data::data_dir().ok_or(ImportError(
+            "no data directory to store the dictionary in".to_string(),
+        ))?;
+        std::fs::create_dir_all(&dir)?;
+        Self::open(dir.join(STORE_FILE))
+    }
+
+    /// The store of the data directory, opened on first use
+    pub fn installed() -> Result<Arc<Self>> {
+        let mut default = DEFAULT.lock().expect("Lock poisoned");
+        if let Some(store) = default.as_ref() {
+            return Ok(Arc::clone(store));
+        }
+        let path = data::find(&[STORE_FILE]).ok_or(TranslateError(
+            "no Yomitan dictionary is imported, import one with `wdym import-yomitan <archive>`"
+                .to_string(),
+        ))?;
+        let store = Arc::new(Self::open(path)?);
+        *default = Some(Arc::clone(&store));
+        Ok(store)
+    }
+
+    /// Imports the dictionary of a ZIP archive, replacing an imported one with the same title
+    pub fn import(&self, archive: impl Read + Seek) -> Result<ImportSummary> {
+        let mut archive = zip::ZipArchive::new(archive)?;
+        let index: Index = {
+            let file = archive
+                .by_name("index.json")
+                .map_err(|_| ImportError("the archive has no index.json".to_string()))?;
+            serde_json::from_reader(file)?
+        };
+        let version = index.format.or(index.version).unwrap_or(3);
+        if !matches!(version, 1 | 3) {
+            Err(ImportError(format!("unsupported format {}", version)))?;
+        }
+
+        let mut connection = self.connection.lock().expect("Lock poisoned");
+        let transaction = connection.transaction()?;
+        remove_dictionary(&transaction, &index.title)?;
+        transaction.execute(
+            "INSERT INTO dictionaries (title, revision) VALUES (?1, ?2)",
+            params![index.title, index.revision],
+        )?;
+        let id = transaction.last_insert_rowid();
+        let mut summary = ImportSummary {
+            title: index.title.clone(),
+            revision: index.revision.clone(),
+            ..Default::default()
+        };
+
+        for (name, tag) in &index.tag_meta {
+            let field = |field: &str| tag.get(field).cloned().unwrap_or_default();
+            let row = [
+                name.as_str().into(),
+                field("category"),
+                field("order"),
+                field("notes"),
+                field("score"),
+            ];
+            insert_tag(&transaction, id, &row)?;
+            summary.tags += 1;
+        }
+
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        for name in names {
+            let Some(bank) = BANK_FILES
+                .into_iter()
+                .find(|bank| name.starts_with(bank) && name.ends_with(".json"))
+            else {
+                continue;
+            };
+            let mut json = String::new();
+            archive.by_name(&name)?.read_to_string(&mut json)?;
+            let rows: Vec<Vec<Value>> = serde_json::from_str(&json)
+                .map_err(|error| ImportError(format!("{} is malformed: {}", name, error)))?;
+            for row in rows {
+                match bank {
+                    "term_bank_" => {
+                        insert_term(&transaction, id, &row, version)?;
+                        summary.terms += 1;
+                    }
+                    "kanji_bank_" => {
+                        insert_kanji(&transaction, id, &row, version)?;
+                        summary.kanji += 1;
+                    }
+                    "tag_bank_" => {
+                        insert_tag(&transaction, id, &row)?;
+                        summary.tags += 1;
+                    }
+                    _ => {
+                        let table = match bank {
+                            "term_meta_bank_" => "term_meta (dictionary, expression",
+                            _ => "kanji_meta (dictionary, character",
+                        };
+                        transaction.execute(
+                            &format!("INSERT INTO {}, mode, data) VALUES (?1, ?2, ?3, ?4)", table),
+                            params![
+                                id,
+                                str_at(&row, 0),
+                                str_at(&row, 1),
+                                row.get(2).unwrap_or(&Value::Null).to_string()
+                            ],
+                        )?;
+                        summary.meta += 1;
+                    }
+                }
+            }
+        }
+        if index.frequency_mode.as_deref() == Some("occurrence-based") {
+            rank_occurrences(&transaction, id)?;
+        }
+        transaction.commit()?;
+        Ok(summary)
+    }
+
+    /// The rank of the term in the first imported frequency dictionary listing it
+    ///
+    /// Frequencies given for another reading of the term are skipped.
+    pub fn frequency(&self, expression: &str, reading: Option<&str>) -> Result<Option<u32>> {
+        let connection = self.connection.lock().expect("Lock poisoned");
+        let mut statement = connection.prepare_cached(
+            "SELECT data FROM term_meta WHERE expression = ?1 AND mode = 'freq'
+            ORDER BY dictionary",
+        )?;
+        let mut rows = statement.query([expression])?;
+        while let Some(row) = rows.next()? {
+            let data: Value = serde_json::from_str(&row.get::<_, String>(0)?)?;
+            let listed_reading = data.get("reading").and_then(Value::as_str);
+            if reading.is_some() && listed_reading.is_some() && listed_reading != reading {
+                continue;
+            }
+            if let Some(rank) = frequency_value(&data) {
+                return Ok(Some(rank));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The kanji in the first imported kanji dictionary listing it, with its rank in the first
+    /// kanji frequency dictionary listing it
+    pub fn kanji(&self, literal: char) -> Result<Option<KanjiInfo>> {
+        let connection = self.connection.lock().expect("Lock poisoned");
+        let literal_text = literal.to_string();
+        let mut info = connection
+            .prepare_cached(
+                "SELECT onyomi, kunyomi, meanings, stats FROM kanji WHERE character = ?1
+                ORDER BY dictionary LIMIT 1",
+            )?
+            .query_row([&literal_text], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .optional()?
+            .map(|(onyomi, kunyomi, meanings, stats)| {
+                let meanings: Vec<Value> = serde_json::from_str(&meanings).unwrap_or_default();
+                let stats: Value = serde_json::from_str(&stats).unwrap_or_default();
+                let stat = |name: &str| frequency_value(stats.get(name)?);
+                let words = |text: &str| text.split_whitespace().map(str::to_string).collect();
+                KanjiInfo {
+                    literal,
+                    on_readings: words(&onyomi),
+                    kun_readings: words(&kunyomi),
+                    meanings: meanings
+                        .iter()
+                        .filter_map(|meaning| Some(meaning.as_str()?.to_string()))
+                        .collect(),
+                    stroke_count: stat("strokes").and_then(|strokes| strokes.try_into().ok()),
+                    jlpt: stat("jlpt").and_then(|jlpt| jlpt.try_into().ok()),
+                    grade: stat("grade").and_then(|grade| grade.try_into().ok()),
+                    radical: None,
+                    frequency: stat("freq"),
+                }
+            });
+
+        let mut statement = connection.prepare_cached(
+            "SELECT data FROM kanji_meta WHERE character = ?1 AND mode = 'freq'
+            ORDER BY dictionary",
+        )?;
+        let mut rows = statement.query([&literal_text])?;
+        while let Some(row) = rows.next()? {
+            let data: Value = serde_json::from_str(&row.get::<_, String>(0)?)?;
+            if let Some(rank) = frequency_value(&data) {
+                info.get_or_insert_with(|| KanjiInfo {
+                    literal,
+                    ..Default::default()
+                })
+                .frequency = Some(rank);
+                break;
+            }
+        }
+        Ok(info)
+    }
+
+    /// Whether any kanji or kanji frequency was imported
+    pub fn has_kanji(&self) -> Result<bool> {
+        let connection = self.connection.lock().expect("Lock poisoned");
+        Ok(connection.query_row(
+            "SELECT EXISTS(SELECT 1 FROM kanji) OR EXISTS(SELECT 1 FROM kanji_meta)",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Whether a term of the imported dictionaries, or of their frequency lists, is spelled or
+    /// read like the text
+    pub fn contains(&self, text: &str) -> Result<bool> {
+        let connection = self.connection.lock().expect("Lock poisoned");
+        let mut statement = connection.prepare_cached(
+            "SELECT EXISTS(SELECT 1 FROM terms WHERE expression = ?1 OR reading = ?1)
+            OR EXISTS(SELECT 1 FROM term_meta WHERE expression = ?1 AND mode = 'freq')",
+        )?;
+        Ok(statement.query_row([text], |row| row.get(0))?)
+    }
+
+    /// The titles and revisions of the imported dictionaries
+    pub fn dictionaries(&self) -> Result<Vec<(String, String)>> {
+        let connection = self.connection.lock().expect("Lock poisoned");
+        let mut statement =
+            connection.prepare("SELECT title, revision FROM dictionaries ORDER BY id")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Looks up the terms spelled or read like the query, or like the first of its dictionary
+    /// forms found
+    pub fn search(&self, search_options: &search::SearchConfig) -> Result<SearchResult> {
+        let query = search_options.query.trim();
+        for candidate in deinflect(query) {
+            let entries = self.entries(&candidate)?;
+            if !entries.is_empty() {
+                return Ok(SearchResult {
+                    entries,
+                    deinflection: (!candidate.reasons.is_empty()).then_some(candidate),
+                });
+            }
+        }
+        Ok(SearchResult {
+            entries: Vec::new(),
+            deinflection: None,
+        })
+    }
+
+    /// The entries of the candidate, whose rules allow its inflections
+    fn entries(&self, candidate: &Deinflection) -> Result<Vec<Entry>> {
+        let connection = self.connection.lock().expect("Lock poisoned");
+        let mut statement = connection.prepare_cached(
+            "SELECT d.title, t.expression, t.reading, t.definition_tags, t.rules, t.glossary,
+                t.dictionary
+            FROM terms t JOIN dictionaries d ON d.id = t.dictionary
+            WHERE t.expression = ?1 OR t.reading = ?1
+            ORDER BY t.expression = ?1 DESC, t.dictionary, t.score DESC, t.sequence",
+        )?;
+        let mut tag_notes = connection.prepare_cached(
+            "SELECT notes FROM tags WHERE dictionary = ?1 AND name = ?2 AND notes != ''",
+        )?;
+
+        let mut entries = Vec::new();
+        let mut rows = statement.query([&candidate.term])?;
+        while let Some(row) = rows.next()? {
+            let rules: String = row.get(4)?;
+            if candidate.word_types != WordTypes::ANY
+                && !word_types(&rules).intersects(candidate.word_types)
+            {
+                continue;
+            }
+            let dictionary: i64 = row.get(6)?;
+            let definition_tags: String = row.get(3)?;
+            let tags = definition_tags
+                .split_whitespace()
+                .map(|name| {
+                    let notes = tag_notes.query_row(params![dictionary, name], |row| row.get(0));
+                    match notes {
+                        Ok(notes) => Ok(notes),
+                        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(name.to_string()),
+                        Err(error) => Err(error),
+                    }
+                })
+                .collect::<Result<Vec<String>, _>>()?;
+            let glossary: Vec<Value> = serde_json::from_str(&row.get::<_, String>(5)?)?;
+            entries.push(Entry {
+                dictionary: row.get(0)?,
+                expression: row.get(1)?,
+                reading: row.get(2)?,
+                tags,
+                structured: glossary.iter().any(|item| {
+                    item.get("type").and_then(Value::as_str) == Some("structured-content")
+                }),
+                glossary: flatten_glossary(&glossary),
+            });
+            if entries.len() == MAX_ENTRIES {
+                break;
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// The number of a frequency: `1200`, `"1200"`, `{"value": 1200, "displayValue": "1200㋕"}`, or
+/// any of these in the `frequency` of an object with the reading
+fn frequency_value(data: &Value) -> Option<u32> {
+    match data {
+        Value::Number(number) => number.as_u64()?.try_into().ok(),
+        Value::String(text) => {
+            let digits: String = text.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        }
+        Value::Object(object) => {
+            frequency_value(object.get("frequency").or_else(|| object.get("value"))?)
+        }
+        _ => None,
+    }
+}
+
+/// Replaces the occurrence counts of a frequency dictionary with ranks, like the other frequency
+/// dictionaries give, the most frequent term being 1
+///
+/// Terms occurring as often share their rank. Frequencies without a count are dropped.
+fn rank_occurrences(transaction: &Transaction, dictionary: i64) -> Result<()> {
+    let mut counts: Vec<(i64, Value, u32)> = Vec::new();
+    {
+        let mut statement = transaction
+            .prepare("SELECT rowid, data FROM term_meta WHERE dictionary = ?1 AND mode = 'freq'")?;
+        let mut rows = statement.query([dictionary])?;
+        while let Some(row) = rows.next()? {
+            let data: Value = serde_json::from_str(&row.get::<_, String>(1)?)?;
+            let count = frequency_value(&data).unwrap_or_default();
+            counts.push((row.get(0)?, data, count));
+        }
+    }
+    counts.sort_by_key(|(_, _, count)| std::cmp::Reverse(*count));
+    let mut rank = 0;
+    for (index, (rowid, data, count)) in counts.iter().enumerate() {
+        if *count == 0 {
+            transaction.execute("DELETE FROM term_meta WHERE rowid = ?1", [rowid])?;
+            continue;
+        }
+        if index == 0 || counts[index - 1].2 != *count {
+            rank = index + 1;
+        }
+        let ranked = match data.get("reading") {
+            Some(reading) => serde_json::json!({"reading": reading, "frequency": rank}),
+            None => serde_json::json!(rank),
+        };
+        transaction.execute(
+            "UPDATE term_meta SET data = ?2 WHERE rowid = ?1",
+            params![rowid, ranked.to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes an imported dictionary and its banks, if there is one with the title
+fn remove_dictionary(transaction: &Transaction, title: &str) -> Result<()> {
+    let id: Option<i64> = match transaction.query_row(
+        "SELECT id FROM dictionaries WHERE title = ?1",
+        [title],
+        |row| row.get(0),
+    ) {
+        Ok(id) => Some(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(error) => Err(error)?,
+    };
+    if let Some(id) = id {
+        for table in BANK_TABLES {
+            transaction.execute(
+                &format!("DELETE FROM {} WHERE dictionary = ?1", table),
+                [id],
+            )?;
+        }
+        transaction.execute("DELETE FROM dictionaries WHERE id = ?1", [id])?;
+    }
+    Ok(())
+}
+
+/// Inserts a row of a term bank
+///
+/// Version 3 rows are expression, reading, definition tags, rules, score, glossary, sequence and
+/// term tags. Version 1 rows end with the glossary strings instead.
+fn insert_term(
+    transaction: &Transaction,
+    dictionary: i64,
+    row: &[Value],
+    version: u32,
+) -> Result<()> {
+    let (glossary, sequence, term_tags) = match version {
+        1 => (
+            Value::Array(row.get(5..).unwrap_or_default().to_vec()),
+            None,
+            "",
+        ),
+        _ => (
+            row.get(5).cloned().unwrap_or(Value::Array(Vec::new())),
+            row.get(6).and_then(Value::as_i64),
+            str_at(row, 7),
+        ),
+    };
+    transaction
+        .prepare_cached(
+            "INSERT INTO terms (dictionary, expression, reading, definition_tags, rules, score,
+                glossary, sequence, term_tags)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?
+        .execute(params![
+            dictionary,
+            str_at(row, 0),
+            str_at(row, 1),
+            str_at(row, 2),
+            str_at(row, 3),
+            int_at(row, 4),
+            glossary.to_string(),
+            sequence,
+            term_tags
+        ])?;
+    Ok(())
+}
+
+/// Inserts a row of a kanji bank: character, onyomi, kunyomi, tags, meanings and stats
+///
+/// Version 1 rows end with the meanings, and have no stats.
+fn insert_kanji(
+    transaction: &Transaction,
+    dictionary: i64,
+    row: &[Value],
+    version: u32,
+) -> Result<()> {
+    let (meanings, stats) = match version {
+        1 => (
+            Value::Array(row.get(4..).unwrap_or_default().to_vec()),
+            Value::Object(Default::default()),
+        ),
+        _ => (
+            row.get(4).cloned().unwrap_or(Value::Array(Vec::new())),
+            row.get(5)
+                .cloned()
+                .unwrap_or(Value::Object(Default::default())),
+        ),
+    };
+    transaction
+        .prepare_cached(
+            "INSERT INTO kanji (dictionary, character, onyomi, kunyomi, tags, meanings, stats)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?
+        .execute(params![
+            dictionary,
+            str_at(row, 0),
+            str_at(row, 1),
+            str_at(row, 2),
+            str_at(row, 3),
+            meanings.to_string(),
+            stats.to_string()
+        ])?;
+    Ok(())
+}
+
+/// Inserts a row of a tag bank: name, category, order, notes and score
+fn insert_tag(transaction: &Transaction, dictionary: i64, row: &[Value]) -> Result<()> {
+    transaction
+        .prepare_cached(
+            "INSERT INTO tags (dictionary, name, category, sort_order, notes, score)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            dictionary,
+            str_at(row, 0),
+            str_at(row, 1),
+            int_at(row, 2),
+            str_at(row, 3),
+            int_at(row, 4)
+        ])?;
+    Ok(())
+}
+
+/// The terms found in the imported dictionaries
+///
+/// Can be converted to `search::SearchResult`, using `from` or `into`
+#[derive(Debug)]
+pub struct SearchResult {
+    entries: Vec<Entry>,
+    /// The dictionary form found for an inflected query
+    deinflection: Option<Deinflection>,
+}
+
+impl From<SearchResult> for search::SearchResult {
+    fn from(value: SearchResult) -> Self {
+        let definitions: Vec<search::Definition> = value
+            .entries
+            .into_iter()
+            .map(|entry| search::Definition {
+                headword: Some(search::Word {
+                    reading: (!entry.reading.is_empty() && entry.reading != entry.expression)
+                        .then_some(entry.reading),
+                    text: entry.expression,
+                }),
+                pitch_accents: None,
+                other_spellings: None,
+                meaning: entry
+                    .glossary
+                    .iter()
+                    .map(|line| html::plain_text(line))
+                    .collect::<Vec<String>>()
+                    .join("; "),
+                rich_meaning: entry.structured.then_some(entry.glossary),
+                pos: entry.tags.join(", "),
+                reverse_translation: None,
+                confidence: None,
+                frequency: None,
+                examples: None,
+                relations: None,
+            })
+            .collect();
+
+        search::SearchResult {
+            provider: SearchProvider::Yomitan,
+            translations: None,
+            definitions: (!definitions.is_empty()).then_some(definitions),
+            src_lang: None,
+            src_lang_confidence: None,
+            src_lang_candidates: None,
+            literation: None,
+            spelling_suggestion: None,
+            alternatives: None,
+            synonyms: None,
+            related_words: None,
+            inflection: value.deinflection.map(|deinflection| search::Inflection {
+                base_form: deinflection.term,
+                reasons: deinflection
+                    .reasons
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            }),
+            tokens: None,
+            warnings: None,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(deinflection) = &self.deinflection {
+            writeln!(
+                f,
+                "inflected from {}: {}",
+                deinflection.term,
+                deinflection.reasons.join(", ")
+            )?;
+        }
+
+        for entry in &self.entries {
+            write!(f, "{}", entry.expression)?;
+            if !entry.reading.is_empty() && entry.reading != entry.expression {
+                write!(f, "【{}】", entry.reading)?;
+            }
+            writeln!(f, " ({}) [{}]", entry.tags.join(", "), entry.dictionary)?;
+            for line in &entry.glossary {
+                writeln!(f, "\t{}", html::plain_text(line))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn lookup_yomitan(search_options: &search::SearchConfig) -> Result<SearchResult> {
+    Yomitan::installed()?.search(search_options)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+
+    /// Zips the files of the test dictionary
+    fn archive(dir: &str) -> Cursor<Vec<u8>> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for file in std::fs::read_dir(dir).unwrap() {
+            let path = file.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            writer.start_file(name, options).unwrap();
+            writer.write_all(&std::fs::read(&path).unwrap()).unwrap();
+        }
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    fn search(store: &Yomitan, query: &str) -> SearchResult {
+        store
+            .search(&search::SearchConfig {
+                query: query.to_string(),
+                provider: SearchProvider::Yomitan,
+                ..Default::default()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn import_and_search() {
+        let store = Yomitan::open(":memory:").unwrap();
+        let summary = store.import(archive("tests/data/yomitan")).unwrap();
+        assert_eq!(
+            summary.to_string(),
+            "Test JMdict (2026-01-01): 3 terms, 1 kanji, 5 tags, 3 metadata"
+        );
+        // Importing again replaces the dictionary
+        store.import(archive("tests/data/yomitan")).unwrap();
+        assert_eq!(store.dictionaries().unwrap().len(), 1);
+
+        assert_eq!(
+            search(&store, "たべる").to_string(),
+            "食べる【たべる】 (Ichidan verb, transitive verb) [Test JMdict]\n\
+            \tto eat\n\
+            \tto live on (e.g. a salary)\n"
+        );
+        // Deinflected, checking the rules of the terms
+        let result = search(&store, "食べなかった");
+        assert!(result
+            .to_string()
+            .starts_with("inflected from 食べる: negative, past\n食べる"));
+        assert!(search(&store, "猫").entries.is_empty());
+
+        assert_eq!(
+            store.frequency("食べる", Some("たべる")).unwrap(),
+            Some(500)
+        );
+        assert_eq!(store.frequency("食べる", Some("くべる")).unwrap(), None);
+        assert_eq!(store.frequency("犬", Some("いぬ")).unwrap(), Some(1200));
+
+        assert!(store.contains("食べる").unwrap());
+        assert!(store.contains("かく").unwrap());
+        assert!(!store.contains("食べ").unwrap());
+
+        let kanji = store.kanji('犬').unwrap().unwrap();
+        assert_eq!(kanji.on_readings, vec!["ケン"]);
+        assert_eq!(kanji.kun_readings, vec!["いぬ", "いぬ-"]);
+        assert_eq!(kanji.meanings, vec!["dog"]);
+        assert_eq!((kanji.stroke_count, kanji.grade), (Some(4), Some(1)));
+        assert_eq!(kanji.frequency, Some(1050));
+        assert_eq!(store.kanji('猫').unwrap(), None);
+        assert!(store.has_kanji().unwrap());
+    }
+
+    #[test]
+    fn rank_occurrence_counts() {
+        let store = Yomitan::open(":memory:").unwrap();
+        store
+            .import(archive("tests/data/yomitan-occurrences"))
+            .unwrap();
+        assert_eq!(store.frequency("犬", None).unwrap(), Some(1));
+        assert_eq!(store.frequency("食べる", Some("たべる")).unwrap(), Some(2));
+        assert_eq!(store.frequency("鳥", None).unwrap(), Some(2));
+        assert_eq!(store.frequency("猫", None).unwrap(), Some(4));
+        assert_eq!(store.frequency("魚", None).unwrap(), None);
+    }
+
+    #[test]
+    fn flatten_structured_content() {
+        let store = Yomitan::open(":memory:").unwrap();
+        store.import(archive("tests/data/yomitan")).unwrap();
+        assert_eq!(
+            search(&store, "書く").to_string(),
+            "書く【かく】 (Godan verb, transitive verb) [Test JMdict]\n\
+            \t• to write\n\
+            \t• to draw; to paint\n\
+            \tUsually written using kana: 掻(か)く is another verb\n"
+        );
+
+        let result: search::SearchResult = search(&store, "犬").into();
+        let definition = &result.definitions.unwrap()[0];
+        assert_eq!(definition.meaning, "dog; spy");
+        let lines = definition.rich_meaning.as_ref().unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0][0].style.bold);
+        assert!(!lines[1][0].style.bold);
+    }
+}